@@ -0,0 +1,213 @@
+use crate::play::{error_embed_message, YES_NO_REACTIONS};
+use crate::shared::{config, redis_url, sessions, SharedDatabase};
+use anyhow::Context;
+use chrono::Local;
+use serenity::client;
+use serenity::framework::standard::CommandResult;
+use serenity::model::channel::AttachmentType;
+use serenity::model::prelude::ChannelId;
+use serenity::model::user::User;
+use setting_config::Config;
+use std::sync::Arc;
+use std::time::Duration;
+use thrpg_database::{
+    account::{delete_user_cache, export_user, mark_deleted, purge_deleted, restore_user},
+    rating::rating_cache,
+    redis_connect,
+    repository::{ScoreRepository, UserRepository},
+};
+
+/// How often [spawn_purge] looks for accounts past the restore window
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// `/export`
+/// Sends everything stored about the user as a JSON file by DM
+pub async fn export(
+    ctx: client::Context,
+    channel_id: ChannelId,
+    user: User,
+    user_id: Option<String>,
+    postgres_connect: sea_orm::DatabaseConnection,
+) -> CommandResult {
+    let target = match target_id(&ctx, channel_id, &user, user_id).await? {
+        Some(target) => target,
+        None => return Ok(()),
+    };
+    let mut archive = export_user(&postgres_connect, &target).await?;
+    // A battle in progress is only in the session cache
+    archive["session"] = match sessions(&ctx).await.load(&target).await? {
+        Some(json) => serde_json::from_str(&json)?,
+        None => serde_json::Value::Null,
+    };
+    let data = serde_json::to_vec_pretty(&archive)?;
+    user.direct_message(&ctx, |m| {
+        m.content(format!("{}のデータです", target))
+            .add_file(AttachmentType::Bytes {
+                data: data.into(),
+                filename: format!("thrpg_{}.json", target),
+            })
+    })
+    .await
+    .context("DMの送信に失敗しました")?;
+    channel_id
+        .send_message(&ctx.http, |f| f.embed(|e| e.title("DMにデータを送信しました")))
+        .await
+        .context("埋め込みの作成に失敗しました")?;
+    Ok(())
+}
+
+/// `/delete`
+/// The account only waits for the purge, `/restore` brings it back until then
+/// The user leaves the Redis ranking and session at once
+pub async fn delete(
+    ctx: &client::Context,
+    channel_id: ChannelId,
+    user: User,
+    user_id: Option<String>,
+    postgres_connect: sea_orm::DatabaseConnection,
+) -> CommandResult {
+    let target = match target_id(ctx, channel_id, &user, user_id).await? {
+        Some(target) => target,
+        None => return Ok(()),
+    };
+    let config = config(ctx).await;
+    let question = channel_id
+        .send_message(&ctx.http, |f| {
+            f.embed(|e| {
+                e.title("本当に削除してもよろしいでしょうか？").description(format!(
+                    "{}のデータは{}日後に完全に削除されます",
+                    target,
+                    config.restore_days()
+                ))
+            })
+            .reactions(YES_NO_REACTIONS.to_vec())
+        })
+        .await
+        .context("埋め込みの作成に失敗しました")?;
+
+    if let Some(reaction) = &question
+        .await_reaction(ctx)
+        .timeout(Duration::from_secs(config.timeout_duration().unwrap_or(10)))
+        .author_id(user.id.0)
+        .await
+    {
+        let emoji = &reaction.as_inner_ref().emoji;
+        match emoji.as_data().as_str() {
+            "⭕" => {
+                if !mark_deleted(&postgres_connect, &target, Local::now().naive_local()).await? {
+                    error_embed_message(ctx, channel_id, "削除できるセーブデータがありません").await?;
+                    return Ok(());
+                }
+                match redis_url(&config) {
+                    Some(url) => {
+                        let mut redis_connect = redis_connect::connect(url).await?;
+                        delete_user_cache(&mut redis_connect, &target).await?;
+                    }
+                    None => sessions(ctx).await.remove(&target).await?,
+                }
+                channel_id
+                    .send_message(&ctx.http, |f| {
+                        f.embed(|e| {
+                            e.title("データを削除しました").description(format!(
+                                "{}日以内なら /restore で元に戻せます",
+                                config.restore_days()
+                            ))
+                        })
+                    })
+                    .await
+                    .context("埋め込みの作成に失敗しました")?;
+            }
+            "❌" => {
+                channel_id
+                    .send_message(&ctx.http, |f| f.embed(|e| e.title("削除を取り消します")))
+                    .await
+                    .context("埋め込みの作成に失敗しました")?;
+            }
+            _ => {
+                error_embed_message(ctx, channel_id, "正しい反応を選んで下さい").await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// `/restore`
+/// Undo `/delete` before the account is purged
+pub async fn restore(
+    ctx: client::Context,
+    channel_id: ChannelId,
+    user: User,
+    user_id: Option<String>,
+    postgres_connect: sea_orm::DatabaseConnection,
+) -> CommandResult {
+    let target = match target_id(&ctx, channel_id, &user, user_id).await? {
+        Some(target) => target,
+        None => return Ok(()),
+    };
+    if !restore_user(&postgres_connect, &target).await? {
+        error_embed_message(&ctx, channel_id, "削除予定のデータがありません").await?;
+        return Ok(());
+    }
+    // The rating left the ranking on `/delete`
+    if let (Some(url), Some(rating)) = (
+        redis_url(&*config(&ctx).await),
+        postgres_connect.find_rating(&target).await?,
+    ) {
+        let mut redis_connect = redis_connect::connect(url).await?;
+        rating_cache(&mut redis_connect, &target, rating.rating).await?;
+    }
+    channel_id
+        .send_message(&ctx.http, |f| f.embed(|e| e.title("データを元に戻しました")))
+        .await
+        .context("埋め込みの作成に失敗しました")?;
+    Ok(())
+}
+
+/// Accounts waiting for the purge can only use `/export` and `/restore`
+pub async fn is_pending_deletion(
+    postgres_connect: &sea_orm::DatabaseConnection,
+    user_id: &str,
+) -> anyhow::Result<bool> {
+    Ok(postgres_connect
+        .find_user(user_id)
+        .await?
+        .is_some_and(|user| user.deleted_at.is_some()))
+}
+
+/// Delete the accounts whose restore window has passed, in the background
+pub fn spawn_purge(database: SharedDatabase, config: Arc<Config>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            let before =
+                Local::now().naive_local() - chrono::Duration::days(config.restore_days() as i64);
+            match purge_deleted(&database.connection().await, before).await {
+                Ok(purged) if !purged.is_empty() => eprintln!("purged {} deleted accounts", purged.len()),
+                Ok(_) => {}
+                Err(e) => eprintln!("purging deleted accounts failed: {}", e),
+            }
+        }
+    })
+}
+
+/// The user the command works on
+/// Only the manager of the bot can choose someone else by `user_id`
+async fn target_id(
+    ctx: &client::Context,
+    channel_id: ChannelId,
+    user: &User,
+    user_id: Option<String>,
+) -> anyhow::Result<Option<String>> {
+    match user_id {
+        Some(id) if id != user.id.to_string() => {
+            if config(ctx).await.manager_id() != user.id.0 {
+                error_embed_message(ctx, channel_id, "管理者のみ実行できます").await?;
+                return Ok(None);
+            }
+            Ok(Some(id))
+        }
+        _ => Ok(Some(user.id.to_string())),
+    }
+}
@@ -0,0 +1,134 @@
+use anyhow::Context;
+use battle_machine::{
+    achievement::{Achievement, AchievementConfig},
+    event::ProgressEvent,
+};
+use chrono::Local;
+use extension::store::ExtensionStore;
+use serenity::client;
+use serenity::model::prelude::ChannelId;
+use thrpg_database::{achievement::Model as AchievementModel, repository::ProgressRepository};
+
+/// Achievements of `achievement/` and Contents extensions
+pub async fn achievement_config() -> anyhow::Result<AchievementConfig> {
+    let extension_dirs = ExtensionStore::extension_files()
+        .await
+        .map(|store| store.contents_dirs())
+        .unwrap_or_default();
+    AchievementConfig::load_with_extensions("achievement/", extension_dirs).await
+}
+
+/// Add the progress of the events
+/// Returns the achievements unlocked by them
+pub async fn record_achievements<'a, R: ProgressRepository>(
+    repo: &R,
+    config: &'a AchievementConfig,
+    user_id: &str,
+    events: &[ProgressEvent<'_>],
+) -> anyhow::Result<Vec<&'a Achievement>> {
+    let mut unlocked_now = Vec::new();
+    for achievement in config.achievement.iter() {
+        let condition = &achievement.condition;
+        let gained: Vec<u32> = events.iter().map(|e| condition.progress(e)).collect();
+        let gained = if condition.is_cumulative() {
+            gained.iter().sum()
+        } else {
+            gained.into_iter().max().unwrap_or(0)
+        };
+        if gained == 0 {
+            continue;
+        }
+
+        let current = repo.find_achievement(user_id, &achievement.id).await?;
+        if current.as_ref().is_some_and(|c| c.unlocked_at.is_some()) {
+            continue;
+        }
+        let before = current.as_ref().map_or(0, |c| c.progress);
+        let progress = if condition.is_cumulative() {
+            before + gained as i64
+        } else {
+            before.max(gained as i64)
+        };
+        let unlocked = progress >= condition.goal() as i64;
+
+        repo.save_achievement(AchievementModel {
+            user_id: user_id.to_string(),
+            achievement_id: achievement.id.clone(),
+            progress,
+            unlocked_at: unlocked.then(|| Local::now().naive_local()),
+        })
+        .await?;
+        if unlocked {
+            unlocked_now.push(achievement);
+        }
+    }
+    Ok(unlocked_now)
+}
+
+/// Add the progress of the events and announce the unlocked achievements
+pub async fn achievement_progress<R: ProgressRepository>(
+    ctx: &client::Context,
+    channel_id: ChannelId,
+    repo: &R,
+    user_id: &str,
+    events: &[ProgressEvent<'_>],
+) -> anyhow::Result<()> {
+    let config = achievement_config().await?;
+    for achievement in record_achievements(repo, &config, user_id, events).await? {
+        channel_id
+            .send_message(&ctx.http, |f| {
+                f.embed(|e| {
+                    e.title(format!("実績解除: {}", achievement.name))
+                        .description(&achievement.description)
+                })
+            })
+            .await
+            .context("埋め込みの作成に失敗しました")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battle_machine::achievement::AchievementCondition;
+    use battle_machine::chara::LevelupExpType;
+    use thrpg_database::repository::MemoryRepository;
+
+    fn config() -> AchievementConfig {
+        AchievementConfig {
+            achievement: vec![Achievement {
+                id: "level10".to_string(),
+                name: "一人前".to_string(),
+                description: "レベル10になる".to_string(),
+                condition: AchievementCondition::ReachLevel {
+                    level: 10,
+                    levelup_exp: None,
+                },
+            }],
+        }
+    }
+
+    fn level(level: u32) -> ProgressEvent<'static> {
+        ProgressEvent::LevelReached {
+            chara: "reimu",
+            levelup_exp: &LevelupExpType::Normal,
+            level,
+        }
+    }
+
+    #[tokio::test]
+    async fn achievement_is_unlocked_once() {
+        let repo = MemoryRepository::new();
+        let config = config();
+
+        assert!(record_achievements(&repo, &config, "user", &[level(5)]).await.unwrap().is_empty());
+        assert!(repo.find_achievement("user", "level10").await.unwrap().is_none());
+
+        let unlocked = record_achievements(&repo, &config, "user", &[level(12)]).await.unwrap();
+        assert_eq!(unlocked, [&config.achievement[0]]);
+        let unlocked = record_achievements(&repo, &config, "user", &[level(13)]).await.unwrap();
+        assert!(unlocked.is_empty());
+        assert_eq!(repo.unlocked_achievements("user").await.unwrap().len(), 1);
+    }
+}
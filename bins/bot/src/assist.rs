@@ -0,0 +1,120 @@
+use crate::play::embed_message;
+use anyhow::Context;
+use battle_machine::{
+    assist::{Assist, AssistEffect},
+    chara::CharaConfig,
+    rpg_core::BattleData,
+};
+use serenity::client;
+use serenity::framework::standard::CommandResult;
+use serenity::model::prelude::ChannelId;
+use serenity::model::user::User;
+use thrpg_database::{
+    repository::{RosterRepository, UserRepository},
+    userdata::Model as UserDataModel,
+};
+
+/// `/assist`
+/// Without a character, stop using the assist
+pub async fn assist<R: UserRepository + RosterRepository>(
+    ctx: client::Context,
+    channel_id: ChannelId,
+    user: User,
+    chara: Option<String>,
+    repo: R,
+) -> CommandResult {
+    let userdata = match repo.find_user(&user.id.to_string()).await? {
+        Some(ud) => ud,
+        None => {
+            embed_message(&ctx, channel_id, "まだセーブデータがありません", "").await?;
+            return Ok(());
+        }
+    };
+
+    let title = match &chara {
+        Some(chara) => {
+            let owned = repo.find_roster(&userdata.user_id, chara).await?.is_some();
+            if !owned {
+                embed_message(&ctx, channel_id, "まだ仲間になっていないキャラクターです", "").await?;
+                return Ok(());
+            }
+            if chara == &userdata.player {
+                embed_message(&ctx, channel_id, "操作中のキャラクターはアシストにできません", "").await?;
+                return Ok(());
+            }
+            let config = CharaConfig::from_file_name(chara).await?;
+            match config.assist {
+                Some(ability) => format!("アシストを{} ({})にしました", config.meta.name, ability.name),
+                None => {
+                    embed_message(&ctx, channel_id, "このキャラクターはアシストの技を持っていません", "")
+                        .await?;
+                    return Ok(());
+                }
+            }
+        }
+        None => "アシストを外しました".to_string(),
+    };
+
+    repo.set_assist(&userdata.user_id, chara).await?;
+    embed_message(&ctx, channel_id, title, "").await?;
+    Ok(())
+}
+
+/// Assist of the user for the next battle
+/// `None` if it is not set or it is the active character
+pub async fn assist_of(userdata: &UserDataModel) -> anyhow::Result<Option<(String, CharaConfig)>> {
+    match &userdata.assist {
+        Some(chara) if chara != &userdata.player => {
+            Ok(Some((chara.clone(), CharaConfig::from_file_name(chara).await?)))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Call the assist of the battle and show the move
+pub async fn call_assist(
+    ctx: &client::Context,
+    channel_id: ChannelId,
+    battle: &mut BattleData,
+) -> anyhow::Result<()> {
+    let name = match battle.assist() {
+        Some(assist) if !assist.used => assist.name.clone(),
+        Some(_) => {
+            embed_message(ctx, channel_id, "アシストはもう呼べません", "1回の戦闘で1度だけです").await?;
+            return Ok(());
+        }
+        None => {
+            embed_message(ctx, channel_id, "アシストがいません", "/assist で設定できます").await?;
+            return Ok(());
+        }
+    };
+    let ability = battle.call_assist().context("assist was already called")?;
+    let description = match ability.effect {
+        AssistEffect::Heal { hp } => format!("HPが{}回復した", hp),
+        AssistEffect::Shield { amount } => format!("{}ダメージを防ぐ結界を張った", amount),
+        AssistEffect::Strike { damage } => format!("{}に{}ダメージ", battle.enemy().meta.name, damage),
+    };
+    embed_message(ctx, channel_id, format!("{}の{}！", name, ability.name), description).await
+}
+
+/// Give the assist of the battle its share of the exp
+pub async fn share_assist_exp<R: RosterRepository>(
+    repo: &R,
+    user_id: &str,
+    battle: &BattleData,
+    exp: u32,
+) -> anyhow::Result<()> {
+    let assist = match battle.assist() {
+        Some(assist) => assist,
+        None => return Ok(()),
+    };
+    let roster = match repo.find_roster(user_id, &assist.chara).await? {
+        Some(roster) => roster,
+        None => return Ok(()),
+    };
+    let exp = roster.exp + Assist::exp_share(exp) as i64;
+    // Every exp curve uses the same level formula
+    let level = battle.calculate_player_level(exp as f64);
+    repo.set_level(user_id, &assist.chara, level as i64, exp).await?;
+    Ok(())
+}
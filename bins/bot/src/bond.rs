@@ -0,0 +1,101 @@
+use anyhow::Context;
+use battle_machine::{bond::affinity_gain, chara::CharaConfig, event::ProgressEvent};
+use serenity::client;
+use serenity::model::prelude::ChannelId;
+use thrpg_database::repository::RosterRepository;
+
+/// Raise the affinity of the character by the events
+/// Returns the affinity before and after
+/// `None` if the user does not own the character or gained nothing
+pub async fn gain_affinity<R: RosterRepository>(
+    repo: &R,
+    user_id: &str,
+    chara_id: &str,
+    events: &[ProgressEvent<'_>],
+) -> anyhow::Result<Option<(u32, u32)>> {
+    let gained: u32 = events.iter().map(affinity_gain).sum();
+    if gained == 0 {
+        return Ok(None);
+    }
+    let roster = match repo.find_roster(user_id, chara_id).await? {
+        Some(roster) => roster,
+        None => return Ok(None),
+    };
+    repo.add_affinity(user_id, chara_id, gained as i64).await?;
+    let before = roster.affinity.max(0) as u32;
+    Ok(Some((before, before + gained)))
+}
+
+/// Raise the affinity of the character by the events and announce the reached tiers
+pub async fn bond_progress<R: RosterRepository>(
+    ctx: &client::Context,
+    channel_id: ChannelId,
+    repo: &R,
+    user_id: &str,
+    chara_id: &str,
+    events: &[ProgressEvent<'_>],
+) -> anyhow::Result<()> {
+    let (before, after) = match gain_affinity(repo, user_id, chara_id, events).await? {
+        Some(affinity) => affinity,
+        None => return Ok(()),
+    };
+
+    let chara = CharaConfig::from_file_name(chara_id).await?;
+    for tier in chara.bond_reached(before, after) {
+        channel_id
+            .send_message(&ctx.http, |f| {
+                f.embed(|e| {
+                    e.title(format!("{}との絆が深まった: {}", chara.meta.name, tier.name));
+                    if !tier.bonus.is_empty() {
+                        e.field(
+                            "能力補正",
+                            tier.bonus
+                                .iter()
+                                .map(|b| match (b.flat, b.percent) {
+                                    (Some(flat), _) => format!("{:?} {:+}", b.stat, flat),
+                                    (None, Some(percent)) => format!("{:?} {:+}%", b.stat, percent),
+                                    (None, None) => format!("{:?}", b.stat),
+                                })
+                                .collect::<Vec<String>>()
+                                .join("\n"),
+                            true,
+                        );
+                    }
+                    if let Some(special) = &tier.special {
+                        e.field("必殺技", &special.name, true);
+                    }
+                    if let Some(quote) = &tier.quote {
+                        e.description(format!("「{}」", quote));
+                    }
+                    e
+                })
+            })
+            .await
+            .context("埋め込みの作成に失敗しました")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battle_machine::bond::AFFINITY_PER_STORY;
+    use thrpg_database::repository::MemoryRepository;
+
+    #[tokio::test]
+    async fn story_raises_the_affinity_of_owned_characters() {
+        let repo = MemoryRepository::new();
+        repo.find_or_unlock("user", "reimu").await.unwrap();
+        let events = [ProgressEvent::StoryCleared { story: "scarlet" }];
+
+        let gained = gain_affinity(&repo, "user", "reimu", &events).await.unwrap();
+        assert_eq!(gained, Some((0, AFFINITY_PER_STORY)));
+        let gained = gain_affinity(&repo, "user", "reimu", &events).await.unwrap();
+        assert_eq!(gained, Some((AFFINITY_PER_STORY, AFFINITY_PER_STORY * 2)));
+        assert_eq!(gain_affinity(&repo, "user", "marisa", &events).await.unwrap(), None);
+        assert!(repo.find_roster("user", "marisa").await.unwrap().is_none());
+
+        let events = [ProgressEvent::ItemUsed { item: "potion" }];
+        assert_eq!(gain_affinity(&repo, "user", "reimu", &events).await.unwrap(), None);
+    }
+}
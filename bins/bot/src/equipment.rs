@@ -0,0 +1,135 @@
+use crate::play::embed_message;
+use battle_machine::equipment::EquipmentConfig;
+use serenity::client;
+use serenity::framework::standard::CommandResult;
+use serenity::model::prelude::ChannelId;
+use serenity::model::user::User;
+use thrpg_database::{
+    equipment::Model as EquipmentModel,
+    repository::{
+        InventoryRepository, RepositoryTransaction, RosterRepository, TransactionRepository,
+        UserRepository,
+    },
+    userdata::Model as UserDataModel,
+};
+
+/// Gear worn by the character
+pub async fn equipped_gear<R: RosterRepository>(
+    repo: &R,
+    user_id: &str,
+    chara: &str,
+) -> anyhow::Result<Vec<EquipmentConfig>> {
+    let mut gear = Vec::new();
+    for model in repo.equipped(user_id, chara).await? {
+        gear.push(EquipmentConfig::from_file_name(&model.equipment_id).await?);
+    }
+    Ok(gear)
+}
+
+/// `/equip`
+/// Equip gear from the inventory to the active character
+/// Gear that was in the same slot goes back to the inventory
+pub async fn equip<R: TransactionRepository + UserRepository>(
+    ctx: client::Context,
+    channel_id: ChannelId,
+    user: User,
+    equipment_id: String,
+    repo: R,
+) -> CommandResult {
+    let userdata = match repo.find_user(&user.id.to_string()).await? {
+        Some(ud) => ud,
+        None => {
+            embed_message(&ctx, channel_id, "まだセーブデータがありません", "").await?;
+            return Ok(());
+        }
+    };
+    let gear = match EquipmentConfig::from_file_name(&equipment_id).await {
+        Ok(gear) => gear,
+        Err(_) => {
+            embed_message(&ctx, channel_id, "その装備はありません", "").await?;
+            return Ok(());
+        }
+    };
+
+    if !equip_gear(&repo, &userdata, &gear).await? {
+        embed_message(&ctx, channel_id, format!("{}を持っていません", gear.name), "").await?;
+        return Ok(());
+    }
+    embed_message(
+        &ctx,
+        channel_id,
+        format!("{}に{}を装備しました", userdata.player, gear.name),
+        "",
+    )
+    .await?;
+    Ok(())
+}
+
+/// Move the gear from the inventory to the active character
+/// Returns `false` if the user does not have it
+pub async fn equip_gear<R: TransactionRepository>(
+    repo: &R,
+    userdata: &UserDataModel,
+    gear: &EquipmentConfig,
+) -> anyhow::Result<bool> {
+    let txn = repo.begin_transaction().await?;
+    if !txn.use_item(&userdata.user_id, &gear.id, 1).await? {
+        txn.rollback().await?;
+        return Ok(false);
+    }
+    let previous = txn
+        .equip(EquipmentModel {
+            user_id: userdata.user_id.clone(),
+            chara: userdata.player.clone(),
+            slot: gear.slot.as_str().to_string(),
+            equipment_id: gear.id.clone(),
+        })
+        .await?;
+    if let Some(previous) = previous {
+        txn.add_item(&userdata.user_id, &previous, "Equipment", 1).await?;
+    }
+    txn.commit().await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battle_machine::equipment::EquipmentSlot;
+    use thrpg_database::repository::{test_user, MemoryRepository};
+
+    fn weapon(id: &str) -> EquipmentConfig {
+        EquipmentConfig {
+            id: id.to_string(),
+            name: id.to_string(),
+            slot: EquipmentSlot::Weapon,
+            bonus: Vec::new(),
+            immunity: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn equipped_gear_goes_back_to_the_inventory() {
+        let repo = MemoryRepository::new();
+        let userdata = test_user("user");
+        assert!(!equip_gear(&repo, &userdata, &weapon("gohei")).await.unwrap());
+        assert!(repo.equipped("user", "reimu").await.unwrap().is_empty());
+
+        repo.add_item("user", "gohei", "Equipment", 1).await.unwrap();
+        repo.add_item("user", "hakkero", "Equipment", 1).await.unwrap();
+        assert!(equip_gear(&repo, &userdata, &weapon("gohei")).await.unwrap());
+        assert!(equip_gear(&repo, &userdata, &weapon("hakkero")).await.unwrap());
+
+        let equipped = repo.equipped("user", "reimu").await.unwrap();
+        assert_eq!(equipped.len(), 1);
+        assert_eq!(equipped[0].equipment_id, "hakkero");
+        let owned: Vec<String> = repo
+            .owned("user", "Equipment")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.item_id)
+            .collect();
+        assert_eq!(owned, ["gohei"]);
+    }
+}
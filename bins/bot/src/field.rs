@@ -0,0 +1,20 @@
+use battle_machine::{field::FieldConfig, rpg_core::BattleData};
+use extension::store::ExtensionStore;
+use serenity::builder::CreateEmbed;
+
+/// Fields of `field/` and Contents extensions
+pub async fn field_config() -> anyhow::Result<FieldConfig> {
+    let extension_dirs = ExtensionStore::extension_files()
+        .await
+        .map(|store| store.contents_dirs())
+        .unwrap_or_default();
+    FieldConfig::load_with_extensions("field/", extension_dirs).await
+}
+
+/// Show the field of the battle in the embed
+pub fn field_embed<'a>(e: &'a mut CreateEmbed, battle: &BattleData) -> &'a mut CreateEmbed {
+    if let Some(field) = battle.field() {
+        e.field(format!("フィールド: {}", field.name), &field.description, false);
+    }
+    e
+}
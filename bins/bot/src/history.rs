@@ -0,0 +1,119 @@
+use anyhow::Context;
+use battle_machine::rpg_core::{BattleData, StatusCharaType};
+use serenity::client;
+use serenity::framework::standard::CommandResult;
+use serenity::model::prelude::ChannelId;
+use serenity::model::user::User;
+use thrpg_database::{
+    battle_history::{BattleStats, Model as BattleHistoryModel},
+    repository::BattleRepository,
+};
+
+/// The user and character on one side of a finished battle
+pub(crate) struct Fighter<'a> {
+    pub user_id: &'a str,
+    /// `chara/{chara}.toml`
+    pub chara: &'a str,
+    pub level: i64,
+}
+
+/// Row of `battle_history` for the battle seen from `side`
+/// `opponent_id` is `None` against the computer
+pub(crate) fn history_entry(
+    battle: &BattleData,
+    side: StatusCharaType,
+    won: bool,
+    fighter: &Fighter<'_>,
+    opponent_id: Option<&str>,
+    opponent_level: i64,
+) -> BattleHistoryModel {
+    let (opponent, opponent_side) = match side {
+        StatusCharaType::Player => (battle.enemy(), StatusCharaType::Enemy),
+        StatusCharaType::Enemy => (battle.player(), StatusCharaType::Player),
+    };
+    BattleHistoryModel {
+        id: 0,
+        battle_uuid: sea_orm::prelude::Uuid::parse_str(&battle.uuid().to_string()).unwrap(),
+        user_id: fighter.user_id.to_string(),
+        mode: battle.play_mode().kind().to_string(),
+        chara: fighter.chara.to_string(),
+        chara_level: fighter.level,
+        opponent_id: opponent_id.map(str::to_string),
+        opponent_name: opponent.meta.name.clone(),
+        opponent_level,
+        won,
+        turns: battle.elapsed_turns() as i64,
+        damage_dealt: battle.damage_taken(opponent_side) as i64,
+        damage_taken: battle.damage_taken(side) as i64,
+        started_at: battle.start_time(),
+        finished_at: chrono::Local::now().naive_local(),
+    }
+}
+
+/// `/stats`
+/// Shows the stats of `target`, or of the user who used the command
+pub async fn stats<R: BattleRepository>(
+    ctx: client::Context,
+    channel_id: ChannelId,
+    user: User,
+    target: Option<User>,
+    repository: R,
+) -> CommandResult {
+    let target = target.unwrap_or(user);
+    let history = repository.history_of(&target.id.to_string()).await?;
+    if history.is_empty() {
+        channel_id
+            .send_message(&ctx.http, |f| f.embed(|e| e.title("まだ戦績がありません")))
+            .await
+            .context("埋め込みの作成に失敗しました")?;
+        return Ok(());
+    }
+    let stats = BattleStats::from_history(&history);
+    let mut modes: Vec<(&String, &u64)> = stats.modes.iter().collect();
+    modes.sort();
+    let modes: Vec<String> = modes
+        .into_iter()
+        .map(|(mode, battles)| format!("{}: {}戦", mode, battles))
+        .collect();
+
+    channel_id
+        .send_message(&ctx.http, |f| {
+            f.embed(|e| {
+                e.title(format!("{}の戦績", target.name))
+                    .description(format!(
+                        "{}戦 {}勝 {}敗 (勝率 {:.1}%)",
+                        stats.battles,
+                        stats.wins,
+                        stats.losses,
+                        stats.win_rate * 100.0
+                    ))
+                    .field(
+                        "よく使うキャラクター",
+                        stats.favorite_chara.as_deref().unwrap_or("-"),
+                        true,
+                    )
+                    .field(
+                        "連勝",
+                        format!("最長 {} / 現在 {}", stats.longest_win_streak, stats.current_win_streak),
+                        true,
+                    )
+                    .field(
+                        "ダメージ",
+                        format!("与えた {} / 受けた {}", stats.damage_dealt, stats.damage_taken),
+                        false,
+                    )
+                    .field(
+                        "平均",
+                        format!(
+                            "{:.1}ターン / {:.0}秒",
+                            stats.average_turns, stats.average_seconds
+                        ),
+                        true,
+                    )
+                    .field("モード", modes.join("\n"), true)
+            })
+        })
+        .await
+        .context("埋め込みの作成に失敗しました")?;
+    Ok(())
+}
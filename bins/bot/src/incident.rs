@@ -0,0 +1,434 @@
+use crate::assist::{assist_of, call_assist};
+use crate::bond::bond_progress;
+use crate::equipment::equipped_gear;
+use crate::field::field_config;
+use crate::history::{history_entry, Fighter};
+use crate::play::{
+    embed_message, error_embed_message, hp_embed, operation_enemy, string_option, use_battle_item,
+    BATTLE_ASSIST, BATTLE_GUARD, BATTLE_ITEM, BATTLE_PLAY, INCIDENT_REACTIONS,
+};
+use crate::quest::quest_progress;
+use crate::shared::config;
+use crate::stamina::consume_stamina;
+use anyhow::Context;
+use battle_machine::{
+    builder::BattleBuilder,
+    chara::CharaConfig,
+    event::ProgressEvent,
+    incident::{Encounter, Incident, IncidentConfig, IncidentRun},
+    mode::PlayMode,
+    rpg_core::{BattleData, StatusCharaType},
+};
+use chrono::{Duration, Local, NaiveDateTime};
+use extension::store::ExtensionStore;
+use serenity::client;
+use serenity::framework::standard::CommandResult;
+use serenity::model::prelude::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::prelude::{ChannelId, GuildId};
+use serenity::model::user::User;
+use std::time::Duration as StdDuration;
+use thrpg_database::{
+    incident_clear::Model as IncidentClearModel,
+    incident_run::Model as IncidentRunModel,
+    repository::{ProgressRepository, Repository, RepositoryTransaction, TransactionRepository},
+    userdata::Model as UserDataModel,
+};
+
+/// Number of users shown in `/incident ranking`
+const RANKING_SIZE: usize = 10;
+
+/// Incidents of `incident/` and Story and Contents extensions
+pub async fn incident_config() -> anyhow::Result<IncidentConfig> {
+    let extension_dirs = ExtensionStore::extension_files()
+        .await
+        .map(|store| {
+            let mut dirs = store.story_dirs();
+            dirs.extend(store.contents_dirs());
+            dirs
+        })
+        .unwrap_or_default();
+    IncidentConfig::load_with_extensions("incident/", extension_dirs).await
+}
+
+/// `/incident` subcommands
+pub async fn incident<R: Repository + TransactionRepository>(
+    ctx: client::Context,
+    command: &ApplicationCommandInteraction,
+    repo: R,
+) -> CommandResult {
+    let channel_id = command.channel_id;
+    let config = incident_config().await?;
+    let subcommand = match command.data.options.first() {
+        Some(s) => s,
+        None => return Ok(()),
+    };
+    let found = string_option(&subcommand.options, "id").and_then(|id| config.find(&id).cloned());
+
+    match (subcommand.name.as_str(), found) {
+        ("list", _) => {
+            channel_id
+                .send_message(&ctx.http, |f| {
+                    f.embed(|e| {
+                        e.title("異変");
+                        if config.incident.is_empty() {
+                            e.description("異変はありません");
+                        }
+                        for incident in config.incident.iter() {
+                            e.field(
+                                format!("`{}` {}", incident.id, incident.name),
+                                format!("{} (全{}ステージ)", incident.description, incident.stage.len()),
+                                false,
+                            );
+                        }
+                        e
+                    })
+                })
+                .await
+                .context("埋め込みの作成に失敗しました")?;
+        }
+        ("play", Some(incident)) => {
+            play_incident(
+                &ctx,
+                channel_id,
+                command.guild_id,
+                &command.user,
+                &incident,
+                &repo,
+            )
+            .await?;
+        }
+        ("ranking", Some(incident)) => {
+            let best = repo.best_times(&incident.id, RANKING_SIZE).await?;
+            let lines: Vec<String> = best
+                .iter()
+                .enumerate()
+                .map(|(i, clear)| {
+                    format!(
+                        "{}. <@{}> {} ({})",
+                        i + 1,
+                        clear.user_id,
+                        format_duration(Duration::seconds(clear.elapsed_seconds)),
+                        clear.chara
+                    )
+                })
+                .collect();
+            embed_message(
+                &ctx,
+                channel_id,
+                format!("{} クリアタイム", incident.name),
+                if lines.is_empty() {
+                    "まだ誰も解決していません".to_string()
+                } else {
+                    lines.join("\n")
+                },
+            )
+            .await?;
+        }
+        _ => {
+            embed_message(&ctx, channel_id, "異変が見つかりません", "/incident list で確認して下さい").await?;
+        }
+    }
+    Ok(())
+}
+
+/// Play battles from the saved stage until the incident is cleared, lost or left
+async fn play_incident<R: Repository + TransactionRepository>(
+    ctx: &client::Context,
+    channel_id: ChannelId,
+    guild_id: Option<GuildId>,
+    user: &User,
+    incident: &Incident,
+    repo: &R,
+) -> anyhow::Result<()> {
+    let userdata = match repo.find_user(&user.id.to_string()).await? {
+        Some(ud) => ud,
+        None => {
+            embed_message(ctx, channel_id, "まだセーブデータがありません", "").await?;
+            return Ok(());
+        }
+    };
+    let roster = repo.find_or_unlock(&userdata.user_id, &userdata.player).await?;
+    let gear = equipped_gear(repo, &userdata.user_id, &userdata.player).await?;
+    let assist = assist_of(&userdata).await?;
+    let fields = field_config().await?;
+    let mode = PlayMode::Story {
+        id: incident.id.clone(),
+    };
+
+    let mut run: IncidentRun = match repo.find_run(&userdata.user_id, &incident.id).await? {
+        Some(saved) => serde_json::from_value(saved.run)?,
+        // Only a new run costs stamina, continuing from a checkpoint is free
+        None => {
+            if !consume_stamina(ctx, channel_id, repo, guild_id, &userdata.user_id, &mode).await? {
+                return Ok(());
+            }
+            IncidentRun::new(incident, Local::now().naive_local())
+        }
+    };
+
+    while let (Some(stage), Some(enemy)) = (run.stage(incident), run.enemy(incident)) {
+        let mut builder = BattleBuilder::new(
+            mode.clone(),
+            Some(userdata.clone().try_into()?),
+            Some(CharaConfig::from_file_name(enemy).await?),
+            None,
+        );
+        builder
+            .player_status_setting(roster.level as i16)
+            .enemy_status_setting(stage.level)
+            .player_equipment(gear.clone())
+            .player_affinity(roster.affinity as u32);
+        // The assist can be called once in every battle of the incident
+        if let Some((id, chara)) = &assist {
+            builder.assist(id, chara);
+        }
+        // The field of the stage comes before the one of the enemy
+        match &stage.field {
+            Some(id) => builder.field(fields.find(id).cloned()),
+            None => builder.choose_field(&fields, &mut rand::thread_rng()),
+        };
+        let mut battle = builder.build();
+        let max = battle.player().charabase;
+        battle.carry_over(&run);
+
+        let encounter = match run.encounter {
+            Encounter::MidBoss => "中ボス",
+            Encounter::Boss => "ボス",
+        };
+        embed_message(
+            ctx,
+            channel_id,
+            format!(
+                "ステージ{} {} {}: {}",
+                run.stage + 1,
+                stage.name,
+                encounter,
+                battle.enemy().meta.name
+            ),
+            format!(
+                "HP {}/{} MP {}/{}",
+                battle.player().charabase.hp,
+                max.hp,
+                battle.player().charabase.mp,
+                max.mp
+            ),
+        )
+        .await?;
+
+        let winner = fight(ctx, channel_id, user, repo, &userdata, &mut battle).await?;
+        if let Some(winner) = winner {
+            let fighter = Fighter {
+                user_id: &userdata.user_id,
+                chara: &userdata.player,
+                level: roster.level,
+            };
+            let entry = history_entry(
+                &battle,
+                StatusCharaType::Player,
+                winner == StatusCharaType::Player,
+                &fighter,
+                None,
+                stage.level as i64,
+            );
+            repo.record_history(vec![entry]).await?;
+        }
+        match winner {
+            Some(StatusCharaType::Player) => {
+                run.win(incident, &battle.player().charabase, &max);
+                save(repo, &userdata, incident, &run).await?;
+            }
+            Some(StatusCharaType::Enemy) => {
+                run.lose();
+                save(repo, &userdata, incident, &run).await?;
+                embed_message(
+                    ctx,
+                    channel_id,
+                    format!("{}に倒されてしまった", battle.enemy().meta.name),
+                    format!("次はステージ{}からです", run.stage + 1),
+                )
+                .await?;
+                return Ok(());
+            }
+            // The run stays as it was before this battle
+            None => {
+                embed_message(ctx, channel_id, "中断しました", "/incident play で続きから遊べます").await?;
+                return Ok(());
+            }
+        }
+    }
+
+    let elapsed = record_clear(repo, &userdata, incident, &run, Local::now().naive_local()).await?;
+
+    embed_message(
+        ctx,
+        channel_id,
+        format!("{}を解決した！", incident.name),
+        format!(
+            "タイム {} / やられた回数 {}",
+            format_duration(elapsed),
+            run.defeats
+        ),
+    )
+    .await?;
+    let events = [ProgressEvent::StoryCleared { story: &incident.id }];
+    quest_progress(ctx, channel_id, repo, &userdata.user_id, &events).await?;
+    bond_progress(
+        ctx,
+        channel_id,
+        repo,
+        &userdata.user_id,
+        &userdata.player,
+        &events,
+    )
+    .await
+}
+
+/// One battle of an incident
+/// Returns `None` when the user didn't operate in time
+async fn fight<R: Repository + TransactionRepository>(
+    ctx: &client::Context,
+    channel_id: ChannelId,
+    user: &User,
+    repo: &R,
+    userdata: &UserDataModel,
+    battle: &mut BattleData,
+) -> anyhow::Result<Option<StatusCharaType>> {
+    let timeout = config(ctx).await.timeout_duration().unwrap_or(10);
+    loop {
+        if let Some(winner) = battle.winner() {
+            return Ok(Some(winner));
+        }
+        match battle.turn_side() {
+            StatusCharaType::Player => {
+                let operation_embed =
+                    operation_enemy(ctx, channel_id, INCIDENT_REACTIONS.to_vec()).await?;
+                let reaction = match operation_embed
+                    .await_reaction(ctx)
+                    .timeout(StdDuration::from_secs(timeout))
+                    .author_id(user.id)
+                    .await
+                {
+                    Some(reaction) => reaction,
+                    None => return Ok(None),
+                };
+                match reaction.as_inner_ref().emoji.as_data().as_str() {
+                    BATTLE_PLAY => {
+                        battle.result_battle().await;
+                    }
+                    BATTLE_GUARD => {
+                        battle.result_guard().await;
+                    }
+                    BATTLE_ASSIST => {
+                        call_assist(ctx, channel_id, battle).await?;
+                    }
+                    BATTLE_ITEM => {
+                        use_battle_item(ctx, channel_id, repo, userdata, battle).await?;
+                        continue;
+                    }
+                    _ => {
+                        error_embed_message(ctx, channel_id, "正しい反応を選んで下さい").await?;
+                        continue;
+                    }
+                }
+            }
+            StatusCharaType::Enemy => {
+                battle.result_battle().await;
+            }
+        }
+        hp_embed(ctx, channel_id, battle).await?;
+    }
+}
+
+async fn save<R: ProgressRepository>(
+    repo: &R,
+    userdata: &UserDataModel,
+    incident: &Incident,
+    run: &IncidentRun,
+) -> anyhow::Result<()> {
+    repo.save_run(IncidentRunModel {
+        user_id: userdata.user_id.clone(),
+        incident_id: incident.id.clone(),
+        run: serde_json::to_value(run)?,
+        updated_at: Local::now().naive_local(),
+    })
+    .await?;
+    Ok(())
+}
+
+/// Record the clear time and remove the run, returns the time it took
+async fn record_clear<R: TransactionRepository>(
+    repo: &R,
+    userdata: &UserDataModel,
+    incident: &Incident,
+    run: &IncidentRun,
+    now: NaiveDateTime,
+) -> anyhow::Result<Duration> {
+    let elapsed = run.elapsed(now);
+    let txn = repo.begin_transaction().await?;
+    txn.record_clear(IncidentClearModel {
+        id: 0,
+        user_id: userdata.user_id.clone(),
+        incident_id: incident.id.clone(),
+        chara: userdata.player.clone(),
+        elapsed_seconds: elapsed.num_seconds(),
+        defeats: run.defeats as i64,
+        cleared_at: now,
+    })
+    .await?;
+    txn.delete_run(&userdata.user_id, &incident.id).await?;
+    txn.commit().await?;
+    Ok(elapsed)
+}
+
+/// `1:02:03` or `2:03`
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.num_seconds().max(0);
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use thrpg_database::repository::{test_user, MemoryRepository};
+
+    #[tokio::test]
+    async fn clear_replaces_the_run_with_the_time() {
+        let repo = MemoryRepository::new();
+        let incident = Incident {
+            id: "scarlet".to_string(),
+            name: "紅霧異変".to_string(),
+            description: String::new(),
+            recover_percent: 0,
+            stage: vec![],
+        };
+        let started = Local::now().naive_local();
+        let mut run = IncidentRun::new(&incident, started);
+        run.defeats = 2;
+        let slow = test_user("slow");
+        let fast = test_user("fast");
+        for userdata in [&slow, &fast] {
+            save(&repo, userdata, &incident, &run).await.unwrap();
+        }
+
+        let elapsed = record_clear(&repo, &slow, &incident, &run, started + Duration::minutes(30))
+            .await
+            .unwrap();
+        assert_eq!(elapsed, Duration::minutes(30));
+        record_clear(&repo, &fast, &incident, &run, started + Duration::minutes(10))
+            .await
+            .unwrap();
+
+        assert!(repo.find_run("slow", "scarlet").await.unwrap().is_none());
+        let best = repo.best_times("scarlet", RANKING_SIZE).await.unwrap();
+        let times: Vec<(&str, i64, i64)> = best
+            .iter()
+            .map(|clear| (clear.user_id.as_str(), clear.elapsed_seconds, clear.defeats))
+            .collect();
+        assert_eq!(times, [("fast", 600, 2), ("slow", 1800, 2)]);
+    }
+}
@@ -1,18 +1,53 @@
+mod incident;
 mod info;
 mod play;
+mod quest;
+mod ranked;
+mod reward;
+mod shop;
+mod stamina;
+mod status;
+mod tournament;
+mod unlock;
+mod account;
+mod achievement;
+mod assist;
+mod bond;
 mod chara_utill;
+mod equipment;
+mod field;
+mod history;
+mod migrate;
+mod settings;
+mod shared;
 
-use play::{delete,play};
+use account::{delete, export, is_pending_deletion, restore, spawn_purge};
+use play::{duel,error_embed_message,play,setchara};
+use quest::quests;
+use ranked::rating;
+use history::stats;
+use incident::incident;
 use info::info;
-use battle_machine::mode::PlayMode;
-use extension::{
-    extension_manage::{ExtensionAuthority, ExtensionManager},
-    store::ExtensionStore,
+use assist::assist;
+use equipment::equip;
+use migrate::{migrate_command, migrate_on_startup};
+use settings::{dynamic_prefix, guild_settings, settings, starts_battle, SETTINGS_TTL};
+use shared::{
+    connect_redis, connect_sessions, database, ConfigKey, DatabaseKey, GuildSettingsKey, RedisKey,
+    SessionKey, SharedDatabase,
 };
+use shop::{shop, MAX_QUANTITY};
+use stamina::stamina;
+use status::status;
+use tournament::tournament;
+use unlock::gacha;
+use battle_machine::mode::PlayMode;
+use extension::extension_manage::{ExtensionAuthority, ExtensionManager};
 use once_cell::sync::Lazy;
 use setting_config::Config;
 use std::collections::HashSet;
-use thrpg_database::postgres_connect::connect;
+use std::sync::Arc;
+use thrpg_database::guild_settings::GuildSettingsCache;
 use wasmer::Exports;
 
 use serenity::{
@@ -25,7 +60,11 @@ use serenity::{
     model::{
         gateway::{GatewayIntents, Ready},
         interactions::application_command::ApplicationCommandOptionType,
-        prelude::{application_command::ApplicationCommand, Interaction, Message, UserId},
+        prelude::{
+            application_command::ApplicationCommand,
+            interaction::application_command::{ApplicationCommandInteraction, CommandDataOptionValue},
+            Interaction, Message, UserId,
+        },
     },
     Client,
 };
@@ -55,7 +94,7 @@ impl BOTInfo {
     }
 }
 struct Handler {
-    config: Config,
+    config: Arc<Config>,
 }
 
 #[async_trait]
@@ -93,10 +132,212 @@ impl EventHandler for Handler {
                                 .kind(ApplicationCommandOptionType::String)
                         })
                 })
+                .create_application_command(|command| {
+                    command
+                        .name("duel")
+                        .description("battle with another user")
+                        .create_option(|option| {
+                            option
+                                .name("opponent")
+                                .description("The user you want to battle")
+                                .required(true)
+                                .kind(ApplicationCommandOptionType::User)
+                        })
+                })
+                .create_application_command(|command| {
+                    command
+                        .name("tournament")
+                        .description("guild tournament")
+                        .create_option(|option| {
+                            option
+                                .name("create")
+                                .description("Create a tournament (admin)")
+                                .kind(ApplicationCommandOptionType::SubCommand)
+                                .create_sub_option(|option| {
+                                    option
+                                        .name("name")
+                                        .description("Tournament name")
+                                        .kind(ApplicationCommandOptionType::String)
+                                })
+                                .create_sub_option(|option| {
+                                    option
+                                        .name("format")
+                                        .description("Tournament format")
+                                        .kind(ApplicationCommandOptionType::String)
+                                        .add_string_choice("single elimination", "SingleElimination")
+                                        .add_string_choice("round robin", "RoundRobin")
+                                })
+                                .create_sub_option(|option| {
+                                    option
+                                        .name("timeout_hours")
+                                        .description("Hours to play a match before it is decided as a no-show")
+                                        .kind(ApplicationCommandOptionType::Integer)
+                                })
+                        })
+                        .create_option(|option| {
+                            option
+                                .name("join")
+                                .description("Join the tournament")
+                                .kind(ApplicationCommandOptionType::SubCommand)
+                        })
+                        .create_option(|option| {
+                            option
+                                .name("start")
+                                .description("Close registration and make the bracket (admin)")
+                                .kind(ApplicationCommandOptionType::SubCommand)
+                        })
+                        .create_option(|option| {
+                            option
+                                .name("bracket")
+                                .description("Show the bracket")
+                                .kind(ApplicationCommandOptionType::SubCommand)
+                        })
+                        .create_option(|option| {
+                            option
+                                .name("play")
+                                .description("Play your next match")
+                                .kind(ApplicationCommandOptionType::SubCommand)
+                        })
+                })
+                .create_application_command(|command| {
+                    command
+                        .name("incident")
+                        .description("boss rush through an incident")
+                        .create_option(|option| {
+                            option
+                                .name("list")
+                                .description("Show the incidents")
+                                .kind(ApplicationCommandOptionType::SubCommand)
+                        })
+                        .create_option(|option| {
+                            option
+                                .name("play")
+                                .description("Start or continue an incident")
+                                .kind(ApplicationCommandOptionType::SubCommand)
+                                .create_sub_option(|option| {
+                                    option
+                                        .name("id")
+                                        .description("Incident id")
+                                        .required(true)
+                                        .kind(ApplicationCommandOptionType::String)
+                                })
+                        })
+                        .create_option(|option| {
+                            option
+                                .name("ranking")
+                                .description("Fastest clears of an incident")
+                                .kind(ApplicationCommandOptionType::SubCommand)
+                                .create_sub_option(|option| {
+                                    option
+                                        .name("id")
+                                        .description("Incident id")
+                                        .required(true)
+                                        .kind(ApplicationCommandOptionType::String)
+                                })
+                        })
+                })
+                .create_application_command(|command| {
+                    command
+                        .name("gacha")
+                        .description("unlock characters with gold")
+                        .create_option(|option| {
+                            option
+                                .name("pool")
+                                .description("Pool id, or show the rates of every pool")
+                                .kind(ApplicationCommandOptionType::String)
+                        })
+                })
+                .create_application_command(|command| {
+                    command
+                        .name("stamina")
+                        .description("your stamina for battles")
+                        .create_option(|option| {
+                            option
+                                .name("item")
+                                .description("Item id to refill the stamina")
+                                .kind(ApplicationCommandOptionType::String)
+                        })
+                })
+                .create_application_command(|command| {
+                    command
+                        .name("shop")
+                        .description("buy items and equipment")
+                        .create_option(|option| {
+                            option
+                                .name("buy")
+                                .description("Item id, or show the catalog")
+                                .kind(ApplicationCommandOptionType::String)
+                        })
+                        .create_option(|option| {
+                            option
+                                .name("quantity")
+                                .description("How many to buy")
+                                .kind(ApplicationCommandOptionType::Integer)
+                                .min_int_value(1)
+                                .max_int_value(MAX_QUANTITY)
+                        })
+                })
+                .create_application_command(|command| {
+                    command.name("rating").description("top ratings of ranked duels")
+                })
+                .create_application_command(|command| {
+                    command.name("quests").description("daily and weekly quests")
+                })
+                .create_application_command(|command| {
+                    command
+                        .name("assist")
+                        .description("an owned character called once per battle")
+                        .create_option(|option| {
+                            option
+                                .name("chara")
+                                .description("Assist character, or remove the assist")
+                                .kind(ApplicationCommandOptionType::String)
+                        })
+                })
+                .create_application_command(|command| {
+                    command
+                        .name("equip")
+                        .description("equip gear to your character")
+                        .create_option(|option| {
+                            option
+                                .name("equipment")
+                                .description("Equipment id in your inventory")
+                                .required(true)
+                                .kind(ApplicationCommandOptionType::String)
+                        })
+                })
                 .create_application_command(|command| {
                     command
                         .name("delete")
                         .description("delete player's account")
+                        .create_option(|option| {
+                            option
+                                .name("user_id")
+                                .description("The user to delete, only for the bot manager")
+                                .kind(ApplicationCommandOptionType::String)
+                        })
+                })
+                .create_application_command(|command| {
+                    command
+                        .name("restore")
+                        .description("bring back your deleted account")
+                        .create_option(|option| {
+                            option
+                                .name("user_id")
+                                .description("The user to restore, only for the bot manager")
+                                .kind(ApplicationCommandOptionType::String)
+                        })
+                })
+                .create_application_command(|command| {
+                    command
+                        .name("export")
+                        .description("get your data by DM")
+                        .create_option(|option| {
+                            option
+                                .name("user_id")
+                                .description("The user to export, only for the bot manager")
+                                .kind(ApplicationCommandOptionType::String)
+                        })
                 })
                 .create_application_command(|command| {
                     command
@@ -127,6 +368,69 @@ impl EventHandler for Handler {
                                 .kind(ApplicationCommandOptionType::String)
                         })
                 })
+                .create_application_command(|command| {
+                    command
+                        .name("stats")
+                        .description("battle records")
+                        .create_option(|option| {
+                            option
+                                .name("user")
+                                .description("The user whose records you want to see")
+                                .kind(ApplicationCommandOptionType::User)
+                        })
+                })
+                .create_application_command(|command| {
+                    command
+                        .name("settings")
+                        .description("server settings, only administrators can change them")
+                        .create_option(|option| {
+                            option
+                                .name("prefix")
+                                .description("Prefix of the text commands")
+                                .kind(ApplicationCommandOptionType::String)
+                        })
+                        .create_option(|option| {
+                            option
+                                .name("language")
+                                .description("Language of the bot")
+                                .kind(ApplicationCommandOptionType::String)
+                                .add_string_choice("Japanese", "Japanese")
+                                .add_string_choice("English", "English")
+                        })
+                        .create_option(|option| {
+                            option
+                                .name("stamina")
+                                .description("Whether battles cost stamina")
+                                .kind(ApplicationCommandOptionType::Boolean)
+                        })
+                        .create_option(|option| {
+                            option
+                                .name("difficulty")
+                                .description("Default difficulty")
+                                .kind(ApplicationCommandOptionType::String)
+                                .add_string_choice("easy", "Easy")
+                                .add_string_choice("normal", "Normal")
+                                .add_string_choice("hard", "Hard")
+                        })
+                        .create_option(|option| {
+                            option
+                                .name("allow_channel")
+                                .description("Allow battles in this channel, every channel when none is allowed")
+                                .kind(ApplicationCommandOptionType::Channel)
+                        })
+                        .create_option(|option| {
+                            option
+                                .name("remove_channel")
+                                .description("Stop allowing battles in this channel")
+                                .kind(ApplicationCommandOptionType::Channel)
+                        })
+                        .create_option(|option| {
+                            option
+                                .name("reset")
+                                .description("Go back to the settings of the bot")
+                                .kind(ApplicationCommandOptionType::Boolean)
+                        })
+                })
                 .create_application_command(|command| {
                     command
                         .name("info")
@@ -163,51 +467,339 @@ impl EventHandler for Handler {
 
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
         if let Interaction::ApplicationCommand(command) = interaction {
+            let db = database(&ctx).await;
+            let refusal = match refusal(&ctx, &command, &db).await {
+                Ok(refusal) => refusal,
+                Err(e) => {
+                    eprintln!("checking the command failed: {}", e);
+                    Some("データの読み込みに失敗しました。しばらくしてからもう一度試して下さい")
+                }
+            };
+            if let Some(refusal) = refusal {
+                if let Err(e) = error_embed_message(&ctx, command.channel_id, refusal).await {
+                    eprintln!("replying to the command failed: {}", e);
+                }
+                return;
+            }
             match command.data.name.as_str() {
                 "play" => play(
                     ctx,
                     command.channel_id,
+                    command.guild_id,
                     command.user,
-                    connect(self.config.postgresql_config().db_address.as_str())
+                    db,
+                )
+                .await
+                .unwrap(),
+                "duel" => {
+                    let opponent = command.data.options.iter().find_map(|option| {
+                        match option.resolved.as_ref() {
+                            Some(CommandDataOptionValue::User(user, _)) => Some(user.clone()),
+                            _ => None,
+                        }
+                    });
+                    if let Some(opponent) = opponent {
+                        duel(
+                            ctx,
+                            command.channel_id,
+                            command.guild_id,
+                            command.user,
+                            opponent,
+                            db,
+                        )
                         .await
-                        .unwrap(),
+                        .unwrap();
+                    }
+                }
+                "tournament" => tournament(
+                    ctx,
+                    &command,
+                    db,
                 )
                 .await
                 .unwrap(),
-                "info" => {
-                    info(ctx, command.channel_id, command.user).await.unwrap();
+                "incident" => incident(
+                    ctx,
+                    &command,
+                    db,
+                )
+                .await
+                .unwrap(),
+                "gacha" => {
+                    let pool = command.data.options.iter().find_map(|option| {
+                        match option.resolved.as_ref() {
+                            Some(CommandDataOptionValue::String(pool)) => Some(pool.clone()),
+                            _ => None,
+                        }
+                    });
+                    gacha(
+                        ctx,
+                        command.channel_id,
+                        command.user,
+                        pool,
+                        db,
+                    )
+                    .await
+                    .unwrap()
+                }
+                "assist" => {
+                    let chara = command.data.options.iter().find_map(|option| {
+                        match option.resolved.as_ref() {
+                            Some(CommandDataOptionValue::String(chara)) => Some(chara.clone()),
+                            _ => None,
+                        }
+                    });
+                    assist(
+                        ctx,
+                        command.channel_id,
+                        command.user,
+                        chara,
+                        db,
+                    )
+                    .await
+                    .unwrap()
+                }
+                "chara" => {
+                    let chara = command.data.options.iter().find_map(|option| {
+                        match option.resolved.as_ref() {
+                            Some(CommandDataOptionValue::String(chara)) => Some(chara.clone()),
+                            _ => None,
+                        }
+                    });
+                    if let Some(chara) = chara {
+                        setchara(
+                            &ctx,
+                            command.channel_id,
+                            chara,
+                            command.user,
+                            db,
+                        )
+                        .await
+                        .unwrap()
+                    }
+                }
+                "stamina" => {
+                    let item = command.data.options.iter().find_map(|option| {
+                        match option.resolved.as_ref() {
+                            Some(CommandDataOptionValue::String(item)) => Some(item.clone()),
+                            _ => None,
+                        }
+                    });
+                    stamina(
+                        ctx,
+                        command.channel_id,
+                        command.user,
+                        item,
+                        db,
+                    )
+                    .await
+                    .unwrap()
+                }
+                "shop" => {
+                    let buy = command.data.options.iter().find_map(|option| {
+                        match (option.name.as_str(), option.resolved.as_ref()) {
+                            ("buy", Some(CommandDataOptionValue::String(id))) => Some(id.clone()),
+                            _ => None,
+                        }
+                    });
+                    let quantity = command.data.options.iter().find_map(|option| {
+                        match (option.name.as_str(), option.resolved.as_ref()) {
+                            ("quantity", Some(CommandDataOptionValue::Integer(i))) => Some(*i),
+                            _ => None,
+                        }
+                    });
+                    shop(
+                        ctx,
+                        command.channel_id,
+                        command.user,
+                        buy,
+                        quantity,
+                        db,
+                    )
+                    .await
+                    .unwrap()
                 }
-                "delete" => delete(
-                    &ctx,
+                "equip" => {
+                    let equipment = command.data.options.iter().find_map(|option| {
+                        match option.resolved.as_ref() {
+                            Some(CommandDataOptionValue::String(id)) => Some(id.clone()),
+                            _ => None,
+                        }
+                    });
+                    if let Some(equipment) = equipment {
+                        equip(
+                            ctx,
+                            command.channel_id,
+                            command.user,
+                            equipment,
+                            db,
+                        )
+                        .await
+                        .unwrap()
+                    }
+                }
+                "rating" => rating(
+                    ctx,
+                    command.channel_id,
+                    db,
+                )
+                .await
+                .unwrap(),
+                "quests" => quests(
+                    ctx,
                     command.channel_id,
                     command.user,
-                    connect(self.config.postgresql_config().db_address.as_str())
-                        .await
-                        .unwrap(),
+                    db,
                 )
                 .await
                 .unwrap(),
+                "status" => status(
+                    ctx,
+                    command.channel_id,
+                    command.user,
+                    db,
+                )
+                .await
+                .unwrap(),
+                "stats" => {
+                    let target = command.data.options.iter().find_map(|option| {
+                        match option.resolved.as_ref() {
+                            Some(CommandDataOptionValue::User(user, _)) => Some(user.clone()),
+                            _ => None,
+                        }
+                    });
+                    stats(
+                        ctx,
+                        command.channel_id,
+                        command.user,
+                        target,
+                        db,
+                    )
+                    .await
+                    .unwrap()
+                }
+                "settings" => settings(
+                    ctx,
+                    &command,
+                    db,
+                )
+                .await
+                .unwrap(),
+                "info" => {
+                    info(ctx, command.channel_id, command.user).await.unwrap();
+                }
+                "delete" => {
+                    let user_id = command.data.options.iter().find_map(|option| {
+                        match option.resolved.as_ref() {
+                            Some(CommandDataOptionValue::String(id)) => Some(id.clone()),
+                            _ => None,
+                        }
+                    });
+                    delete(
+                        &ctx,
+                        command.channel_id,
+                        command.user,
+                        user_id,
+                        db,
+                    )
+                    .await
+                    .unwrap()
+                }
+                "restore" => {
+                    let user_id = command.data.options.iter().find_map(|option| {
+                        match option.resolved.as_ref() {
+                            Some(CommandDataOptionValue::String(id)) => Some(id.clone()),
+                            _ => None,
+                        }
+                    });
+                    restore(
+                        ctx,
+                        command.channel_id,
+                        command.user,
+                        user_id,
+                        db,
+                    )
+                    .await
+                    .unwrap()
+                }
+                "export" => {
+                    let user_id = command.data.options.iter().find_map(|option| {
+                        match option.resolved.as_ref() {
+                            Some(CommandDataOptionValue::String(id)) => Some(id.clone()),
+                            _ => None,
+                        }
+                    });
+                    export(
+                        ctx,
+                        command.channel_id,
+                        command.user,
+                        user_id,
+                        db,
+                    )
+                    .await
+                    .unwrap()
+                }
                 _ => todo!(),
             };
         }
     }
 }
 
+/// Why the command can't be run, checked before every command
+async fn refusal(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    db: &sea_orm::DatabaseConnection,
+) -> anyhow::Result<Option<&'static str>> {
+    if !matches!(command.data.name.as_str(), "export" | "restore" | "info")
+        && is_pending_deletion(db, &command.user.id.to_string()).await?
+    {
+        return Ok(Some("削除予定のアカウントです。/restore で元に戻せます"));
+    }
+    if starts_battle(command)
+        && !guild_settings(ctx, command.guild_id)
+            .await?
+            .allows_battle(command.channel_id)
+    {
+        return Ok(Some("このチャンネルでは戦闘できません"));
+    }
+    Ok(None)
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let extension_store = ExtensionStore::extension_files().await?;
-    let config = setting_config::config_parse_toml().await;
+    let config = Arc::new(setting_config::config_parse_toml().await);
 
-    let framework = StandardFramework::new()
-        .configure(|c| c.prefix(config.clone().prefix().unwrap_or(&"th!".to_string())));
+    let database = SharedDatabase::connect(config.postgresql_config().db_address.as_str()).await?;
+    let db = database.connection().await;
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() == Some("migrate") {
+        return migrate_command(&db, args.next().as_deref()).await;
+    }
+    migrate_on_startup(&db).await?;
+    database.spawn_health_check();
+    spawn_purge(database.clone(), config.clone());
+    let sessions = connect_sessions(&config).await?;
+    let redis = connect_redis(&config).await?;
+
+    let framework = StandardFramework::new().configure(|c| c.prefix("").dynamic_prefix(dynamic_prefix));
 
-    let handler = Handler { config };
+    let handler = Handler {
+        config: config.clone(),
+    };
 
     let mut client = Client::builder(config.token(), GatewayIntents::all())
         .event_handler(handler)
         .framework(framework)
+        .type_map_insert::<ConfigKey>(config.clone())
+        .type_map_insert::<DatabaseKey>(database)
+        .type_map_insert::<SessionKey>(sessions)
+        .type_map_insert::<GuildSettingsKey>(Arc::new(GuildSettingsCache::new(SETTINGS_TTL)))
         .await
         .map_err(|e| anyhow::anyhow!("client can't start: {:?}", e))?;
+    if let Some(redis) = redis {
+        client.data.write().await.insert::<RedisKey>(redis);
+    }
 
     client.start().await.map_err(|e| anyhow::anyhow!(e))
 }
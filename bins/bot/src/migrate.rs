@@ -0,0 +1,26 @@
+use sea_orm::DatabaseConnection;
+use thrpg_database::migration::{check_schema, migration_status, Migrator, MigratorTrait};
+
+/// `bot migrate [up|down|status]`, runs instead of the bot
+pub async fn migrate_command(db: &DatabaseConnection, command: Option<&str>) -> anyhow::Result<()> {
+    match command.unwrap_or("up") {
+        "up" => Migrator::up(db, None).await?,
+        "down" => Migrator::down(db, Some(1)).await?,
+        "status" => {
+            for (name, applied) in migration_status(db).await? {
+                println!("{} {}", if applied { "applied" } else { "pending" }, name);
+            }
+        }
+        other => anyhow::bail!("unknown migrate command: {}", other),
+    }
+    Ok(())
+}
+
+/// Apply the pending migrations and report entities that differ from the tables
+pub async fn migrate_on_startup(db: &DatabaseConnection) -> anyhow::Result<()> {
+    Migrator::up(db, None).await?;
+    for mismatch in check_schema(db).await? {
+        eprintln!("schema mismatch: {}", mismatch);
+    }
+    Ok(())
+}
@@ -2,25 +2,51 @@ use anyhow::Context;
 use battle_machine::{
     builder::{BattleBuilder, RandomOption},
     chara::CharaConfig,
+    item::ItemConfig,
     mode::PlayMode,
+    reward::RewardSummary,
+    rpg_core::{BattleData, Controller, StatusCharaType},
+    event::ProgressEvent,
 };
+use crate::achievement::achievement_progress;
+use crate::assist::{assist_of, call_assist, share_assist_exp};
+use crate::bond::bond_progress;
+use crate::equipment::equipped_gear;
+use crate::field::{field_config, field_embed};
+use crate::history::{history_entry, Fighter};
+use crate::quest::quest_progress;
+use crate::ranked::record_ranked_match;
+use crate::reward::grant_rewards;
+use crate::settings::guild_settings;
+use crate::shared::{config, redis, sessions};
+use crate::stamina::consume_stamina;
+use crate::unlock::{unlock_config, unlock_progress};
 use once_cell::sync::Lazy;
-use sea_orm::{ActiveModelTrait, ActiveValue, EntityTrait, ModelTrait};
+use serde::{Deserialize, Serialize};
 use serenity::client;
 use serenity::framework::standard::CommandResult;
+use serenity::futures::StreamExt;
 use serenity::model::channel::Message;
 use serenity::model::channel::ReactionType;
-use serenity::model::prelude::ChannelId;
+use serenity::model::prelude::interaction::application_command::{
+    CommandDataOption, CommandDataOptionValue,
+};
+use serenity::model::prelude::{ChannelId, GuildId, UserId};
 use serenity::model::user::User;
-use setting_config::config_parse_toml;
+use setting_config::DEFAULT_STAMINA_MAX;
 use std::time::Duration;
 use thrpg_database::{
-    playdata::Entity as PlaydataEntity,
-    userdata::{
-        ActiveModel as UserDataActiveModel, Entity as UserDataEntity, Model as UserDataModel,
+    duel::Model as DuelModel,
+    playdata::Model as PlayDataModel,
+    repository::{
+        BattleRepository, InventoryRepository, ProgressRepository, Repository,
+        RepositoryTransaction, RosterRepository, TransactionRepository, UserRepository,
     },
+    session::SessionCache,
+    roster::Model as RosterModel,
+    userdata::Model as UserDataModel,
 };
-use setting_i18n::{localizer, appear_enemy};
+use setting_i18n::{localizer, appear_enemy_in};
 
 
 pub static BATTLE_REACTIONS: Lazy<Vec<ReactionType>> = Lazy::new(|| {
@@ -28,10 +54,28 @@ pub static BATTLE_REACTIONS: Lazy<Vec<ReactionType>> = Lazy::new(|| {
     vec.push(ReactionType::Unicode(BATTLE_PLAY.to_string()));
     vec.push(ReactionType::Unicode(BATTLE_GUARD.to_string()));
     vec.push(ReactionType::Unicode(BATTLE_ITEM.to_string()));
+    vec.push(ReactionType::Unicode(BATTLE_ASSIST.to_string()));
     vec.push(ReactionType::Unicode(BATTLE_SAVE.to_string()));
     vec
 });
 
+pub static DUEL_REACTIONS: Lazy<Vec<ReactionType>> = Lazy::new(|| {
+    vec![
+        ReactionType::Unicode(BATTLE_PLAY.to_string()),
+        ReactionType::Unicode(BATTLE_GUARD.to_string()),
+    ]
+});
+
+/// Incidents save after every battle, so there is no save reaction
+pub static INCIDENT_REACTIONS: Lazy<Vec<ReactionType>> = Lazy::new(|| {
+    vec![
+        ReactionType::Unicode(BATTLE_PLAY.to_string()),
+        ReactionType::Unicode(BATTLE_GUARD.to_string()),
+        ReactionType::Unicode(BATTLE_ITEM.to_string()),
+        ReactionType::Unicode(BATTLE_ASSIST.to_string()),
+    ]
+});
+
 pub static YES_NO_REACTIONS: Lazy<Vec<ReactionType>> = Lazy::new(|| {
     let mut vec = Vec::new();
     vec.push(ReactionType::Unicode("⭕".to_string()));
@@ -39,87 +83,147 @@ pub static YES_NO_REACTIONS: Lazy<Vec<ReactionType>> = Lazy::new(|| {
     vec
 });
 
-const BATTLE_PLAY: &str = "⚔";
-const BATTLE_ITEM: &str = "💊";
+pub(crate) const BATTLE_PLAY: &str = "⚔";
+pub(crate) const BATTLE_ITEM: &str = "💊";
 const BATTLE_SAVE: &str = "✒️";
-const BATTLE_GUARD: &str = "\u{1F6E1}";
+pub(crate) const BATTLE_GUARD: &str = "\u{1F6E1}";
+pub(crate) const BATTLE_ASSIST: &str = "🤝";
+
+/// How long a battle nobody operates stays in the session cache
+const SESSION_TTL: Duration = Duration::from_secs(30 * 60);
 
-pub async fn play(
+/// A battle of `/play` mirrored to the session cache after every turn
+#[derive(Serialize, Deserialize)]
+struct LiveBattle {
+    battle: BattleData,
+    enemy_level: i64,
+}
+
+pub async fn play<R: Repository + TransactionRepository>(
     ctx: client::Context,
     channel_id: ChannelId,
+    guild_id: Option<GuildId>,
     user: User,
-    postgres_connect: sea_orm::DatabaseConnection,
+    repo: R,
 ) -> CommandResult {
     if !user.bot {
-        let userdata = match UserDataEntity::find_by_id(user.id.to_string())
-            .one(&postgres_connect)
-            .await
-            .map_err(|e| anyhow::anyhow!(e))?
-        {
+        let config = config(&ctx).await;
+        let timeout = config.timeout_duration().unwrap_or(10);
+        let settings = guild_settings(&ctx, guild_id).await?;
+        let userdata = match repo.find_user(&user.id.to_string()).await? {
             Some(ud) => ud,
             None => {
-                let activemodel = UserDataActiveModel {
-                    exp: ActiveValue::Set(1),
-                    level: ActiveValue::Set(1),
-                    player: ActiveValue::Set("Reimu".to_string()),
-                    user_id: ActiveValue::Set(user.id.0.to_string()),
-                    battle_uuid: ActiveValue::Set(None),
+                let first_chara = unlock_config()
+                    .await?
+                    .default_unlocked
+                    .first()
+                    .cloned()
+                    .unwrap_or("reimu".to_string());
+                let userdata = UserDataModel {
+                    player: first_chara,
+                    assist: None,
+                    user_id: user.id.0.to_string(),
+                    battle_uuid: None,
+                    gold: 0,
+                    stamina: config
+                        .stamina_config()
+                        .map_or(DEFAULT_STAMINA_MAX, |c| c.max()) as i64,
+                    stamina_updated_at: chrono::Local::now().naive_local(),
+                    deleted_at: None,
                 };
-                activemodel.insert(&postgres_connect).await?
+                repo.insert_user(userdata).await?
             }
         };
 
+        let roster = repo.find_or_unlock(&userdata.user_id, &userdata.player).await?;
+
         let playdata = {
             match userdata.battle_uuid {
-                Some(r) => PlaydataEntity::find_by_id(r)
-                    .one(&postgres_connect)
-                    .await
-                    .map_err(|e| anyhow::anyhow!(e))?,
+                Some(r) => repo.find_battle(r).await?,
                 None => None,
             }
         };
 
-        let mut battle = match playdata {
-            Some(d) => {
+        let gear = equipped_gear(&repo, &userdata.user_id, &userdata.player).await?;
+        let assist = assist_of(&userdata).await?;
+
+        // A battle left by another process or before a restart comes first
+        let sessions = sessions(&ctx).await;
+        let live = load_live_battle(sessions.as_ref(), &userdata.user_id).await?;
+        let (mut battle, enemy_level) = match (live, playdata) {
+            (Some(live), _) => (live.battle, live.enemy_level),
+            (None, Some(d)) => {
+                // The saved characters already have their level, gear, bond and field
+                let enemy_level = d.enemy_level;
                 let mut builder: BattleBuilder = d.try_into()?;
-                builder.player_status_setting(userdata.level as i16);
-                builder.enemy_status_setting(userdata.level as i16);
-                builder.build()
+                builder.player_equipment(gear);
+                if let Some((id, chara)) = &assist {
+                    builder.assist(id, chara);
+                }
+                (builder.build(), enemy_level)
             }
-            None => {
+            (None, None) => {
+                // Resuming a saved battle is free
+                if !consume_stamina(
+                    &ctx,
+                    channel_id,
+                    &repo,
+                    guild_id,
+                    &userdata.user_id,
+                    &PlayMode::Simple,
+                )
+                .await?
+                {
+                    return Ok(());
+                }
                 let mut init =
                     BattleBuilder::new(PlayMode::Simple, Some(userdata.clone().try_into()?), None, None);
 
                 init.enemy_random(RandomOption::default(), todo!()).await;
-                init.player_status_setting(1).enemy_status_setting(1);
+                // The enemy grows with the active character
+                let enemy_level = settings.difficulty.enemy_level(roster.level);
+                init.player_status_setting(roster.level as i16)
+                    .enemy_status_setting(enemy_level as i16);
+                init.player_equipment(gear).player_affinity(roster.affinity as u32);
+                if let Some((id, chara)) = &assist {
+                    init.assist(id, chara);
+                }
+                init.choose_field(&field_config().await?, &mut rand::thread_rng());
 
-                init.build()
+                (init.build(), enemy_level)
             }
         };
+        let quote = battle
+            .player()
+            .bond_quote(roster.affinity as u32, &mut rand::thread_rng())
+            .map(|q| q.to_string());
         channel_id
             .send_message(&ctx.http, |m| {
                 m.embed(|e| {
                     e.title(
-                        appear_enemy(&battle.enemy().meta.name)
+                        appear_enemy_in(settings.language.as_deref(), &battle.enemy().meta.name)
                     )
                     .description(if &battle.elapsed_turns() != &0 {
                         format!("{}ターン目です", &battle.elapsed_turns())
                     } else {
                         format!("最初からです")
-                    })
+                    });
+                    if let Some(quote) = &quote {
+                        e.field(&battle.player().meta.name, format!("「{}」", quote), false);
+                    }
+                    field_embed(e, &battle)
                 })
             })
             .await?;
 
         loop {
+            store_live_battle(sessions.as_ref(), &userdata.user_id, &battle, enemy_level).await?;
             if battle.turn() != battle.enemy() || battle.turn() == battle.player() {
                 let operation_embed =
                     operation_enemy(&ctx, channel_id, BATTLE_REACTIONS.to_vec()).await?;
                 if let Some(reaction) = &operation_embed
                     .await_reaction(&ctx)
-                    .timeout(Duration::from_secs(
-                        config_parse_toml().await.timeout_duration().unwrap_or(10),
-                    ))
+                    .timeout(Duration::from_secs(timeout))
                     .author_id(user.id.0)
                     .await
                 {
@@ -139,34 +243,29 @@ pub async fn play(
                                     .await
                                     .context("埋め込みの作成に失敗しました")?;
                             } else if result.enemy().charabase.hp <= 0 {
-                                channel_id
-                                    .send_message(&ctx.http, |f| {
-                                        f.embed(|e| {
-                                            e.title(format!(
-                                                "{}を倒した",
-                                                result.enemy().meta.name
-                                            ))
-                                        })
-                                    })
-                                    .await
-                                    .context("埋め込みの作成に失敗しました")?;
+                                victory(
+                                    &ctx,
+                                    channel_id,
+                                    &repo,
+                                    &userdata,
+                                    &roster,
+                                    &battle,
+                                    enemy_level,
+                                )
+                                .await?;
                                 battle.reset_turn();
-                                let user_exp =
-                                    userdata.exp as f64 + battle.enemy().meta.get_exp as f64;
-                                let player_level = battle.calculate_player_level(user_exp);
-                                let usermodel = UserDataActiveModel {
-                                    user_id: ActiveValue::Set(userdata.user_id),
-                                    exp: ActiveValue::Set(user_exp as i64),
-                                    level: ActiveValue::Set(player_level as i64),
-                                    player: ActiveValue::Set(userdata.player.clone()),
-                                    battle_uuid: ActiveValue::Set(Some(sea_orm::prelude::Uuid::parse_str(&battle.uuid().to_string()).unwrap())),
-                                };
-                                usermodel.save(&postgres_connect).await?;
                                 break;
                             } else {
                                 break;
                             }
                         }
+                        BATTLE_ITEM => {
+                            use_battle_item(&ctx, channel_id, &repo, &userdata, &mut battle)
+                                .await?;
+                        }
+                        BATTLE_ASSIST => {
+                            call_assist(&ctx, channel_id, &mut battle).await?;
+                        }
                         BATTLE_GUARD => {
                             let result = battle.result_guard().await;
                             channel_id
@@ -181,18 +280,7 @@ pub async fn play(
                                 .await?;
                         }
                         BATTLE_SAVE => {
-                            let player_level = battle.calculate_player_level(
-                                userdata.exp as f64 + battle.enemy().meta.get_exp as f64,
-                            );
-                            let active_userdata: UserDataActiveModel = UserDataModel {
-                                user_id: user.id.0.to_string(),
-                                exp: userdata.exp as i64 + battle.enemy().meta.get_exp as i64,
-                                level: player_level as i64,
-                                player: userdata.player.clone(),
-                                battle_uuid: Some(sea_orm::prelude::Uuid::parse_str(&battle.uuid().to_string()).unwrap()),
-                            }
-                            .into();
-                            active_userdata.insert(&postgres_connect).await?;
+                            save_progress(&repo, &userdata, &battle, enemy_level).await?;
 
                             let question = channel_id
                                 .send_message(&ctx.http, |f| {
@@ -208,9 +296,7 @@ pub async fn play(
 
                             if let Some(reaction) = &question
                                 .await_reaction(&ctx)
-                                .timeout(Duration::from_secs(
-                                    config_parse_toml().await.timeout_duration().unwrap_or(10),
-                                ))
+                                .timeout(Duration::from_secs(timeout))
                                 .author_id(user.id)
                                 .await
                             {
@@ -247,9 +333,7 @@ pub async fn play(
                 battle.add_turn();
                 if let Some(reaction) = &operation_embed
                     .await_reaction(&ctx)
-                    .timeout(Duration::from_secs(
-                        config_parse_toml().await.timeout_duration().unwrap_or(10),
-                    ))
+                    .timeout(Duration::from_secs(timeout))
                     .author_id(user.id)
                     .await
                 {
@@ -269,23 +353,29 @@ pub async fn play(
                                     .await
                                     .context("埋め込みの作成に失敗しました")?;
                             } else if result.enemy().charabase.hp <= 0 {
-                                channel_id
-                                    .send_message(&ctx.http, |f| {
-                                        f.embed(|e| {
-                                            e.title(format!(
-                                                "{}を倒した",
-                                                result.enemy().meta.name
-                                            ))
-                                        })
-                                    })
-                                    .await
-                                    .context("埋め込みの作成に失敗しました")?;
+                                victory(
+                                    &ctx,
+                                    channel_id,
+                                    &repo,
+                                    &userdata,
+                                    &roster,
+                                    &battle,
+                                    enemy_level,
+                                )
+                                .await?;
                                 battle.reset_turn();
                                 break;
                             } else {
                                 break;
                             }
                         }
+                        BATTLE_ITEM => {
+                            use_battle_item(&ctx, channel_id, &repo, &userdata, &mut battle)
+                                .await?;
+                        }
+                        BATTLE_ASSIST => {
+                            call_assist(&ctx, channel_id, &mut battle).await?;
+                        }
                         BATTLE_GUARD => {
                             let result = battle.result_guard().await;
                             channel_id
@@ -300,18 +390,7 @@ pub async fn play(
                                 .await?;
                         }
                         BATTLE_SAVE => {
-                            let player_level = battle.calculate_player_level(
-                                userdata.exp as f64 + battle.enemy().meta.get_exp as f64,
-                            );
-                            let active_userdata: UserDataActiveModel = UserDataModel {
-                                user_id: user.id.0.to_string(),
-                                exp: userdata.exp as i64 + battle.enemy().meta.get_exp as i64,
-                                level: player_level as i64,
-                                player: userdata.player.clone(),
-                                battle_uuid: Some(sea_orm::prelude::Uuid::parse_str(&battle.uuid().to_string()).unwrap()),
-                            }
-                            .into();
-                            active_userdata.insert(&postgres_connect).await?;
+                            save_progress(&repo, &userdata, &battle, enemy_level).await?;
                             let question = channel_id
                                 .send_message(&ctx.http, |f| {
                                     f.embed(|e| {
@@ -326,9 +405,7 @@ pub async fn play(
 
                             if let Some(reaction) = &question
                                 .await_reaction(&ctx)
-                                .timeout(Duration::from_secs(
-                                    config_parse_toml().await.timeout_duration().unwrap_or(10),
-                                ))
+                                .timeout(Duration::from_secs(timeout))
                                 .author_id(user.id)
                                 .await
                             {
@@ -384,6 +461,8 @@ pub async fn play(
                         })
                         .await
                         .context("埋め込みの作成に失敗しました")?;
+                    record_battle(&repo, &userdata, &roster, &battle, enemy_level, false)
+                        .await?;
                     battle.reset_turn();
                     break;
                 } else {
@@ -391,112 +470,533 @@ pub async fn play(
                 }
             }
         }
+        // A decided battle can't be picked up again
+        if battle.winner().is_some() {
+            sessions.remove(&userdata.user_id).await?;
+        }
     }
     Ok(())
 }
 
-pub async fn delete(
-    ctx: &serenity::client::Context,
+async fn load_live_battle(
+    sessions: &dyn SessionCache,
+    user_id: &str,
+) -> anyhow::Result<Option<LiveBattle>> {
+    // A session written by an older version of the bot is dropped
+    Ok(sessions
+        .load(user_id)
+        .await?
+        .and_then(|json| serde_json::from_str(&json).ok()))
+}
+
+async fn store_live_battle(
+    sessions: &dyn SessionCache,
+    user_id: &str,
+    battle: &BattleData,
+    enemy_level: i64,
+) -> anyhow::Result<()> {
+    let json = serde_json::to_string(&LiveBattle {
+        battle: battle.clone(),
+        enemy_level,
+    })?;
+    sessions.store(user_id, &json, SESSION_TTL).await
+}
+
+/// User vs user battle
+/// Both users must accept, then each turn waits for the reaction of the user whose turn it is
+pub async fn duel<R: Repository + TransactionRepository>(
+    ctx: client::Context,
     channel_id: ChannelId,
+    guild_id: Option<GuildId>,
     user: User,
-    postgres_connect: sea_orm::DatabaseConnection,
-) -> CommandResult {
+    opponent: User,
+    repo: R,
+) -> anyhow::Result<Option<UserId>> {
+    if user.bot || opponent.bot || user.id == opponent.id {
+        error_embed_message(&ctx, channel_id, "対戦相手が正しくありません").await?;
+        return Ok(None);
+    }
+
+    let mut participants = Vec::new();
+    for participant in [&user, &opponent] {
+        // An account waiting for the purge can't be challenged
+        match repo.find_user(&participant.id.to_string()).await? {
+            Some(ud) if ud.deleted_at.is_none() => {
+                let roster = repo.find_or_unlock(&ud.user_id, &ud.player).await?;
+                let gear = equipped_gear(&repo, &ud.user_id, &ud.player).await?;
+                participants.push((ud, roster, gear));
+            }
+            _ => {
+                error_embed_message(
+                    &ctx,
+                    channel_id,
+                    format!("{}はまだセーブデータがありません", participant.name),
+                )
+                .await?;
+                return Ok(None);
+            }
+        }
+    }
+    let (opponent_data, opponent_roster, opponent_gear) = participants.pop().unwrap();
+    let (user_data, user_roster, user_gear) = participants.pop().unwrap();
+
+    let config = config(&ctx).await;
+    let timeout = config.timeout_duration().unwrap_or(10);
     let question = channel_id
         .send_message(&ctx.http, |f| {
             f.embed(|e| {
-                e.title("本当に削除してもよろしいでしょうか？")
-                    .description("削除したデータは二度と戻ってきません")
+                e.title(format!("{}が{}に対戦を申し込みました", user.name, opponent.name))
+                    .description("二人とも⭕を押すと対戦が始まります")
             })
             .reactions(YES_NO_REACTIONS.to_vec())
         })
         .await
         .context("埋め込みの作成に失敗しました")?;
 
-    if let Some(reaction) = &question
-        .await_reaction(&ctx)
-        .timeout(Duration::from_secs(
-            config_parse_toml().await.timeout_duration().unwrap_or(10),
-        ))
-        .author_id(user.id.0)
-        .await
-    {
-        let emoji = &reaction.as_inner_ref().emoji;
-        match emoji.as_data().as_str() {
+    // Both users answer at the same time, the first ❌ cancels
+    let users = [user.id, opponent.id];
+    let mut reactions = question
+        .await_reactions(&ctx)
+        .timeout(Duration::from_secs(timeout))
+        .filter(move |reaction| reaction.user_id.is_some_and(|id| users.contains(&id)))
+        .build();
+    let mut accepted = Vec::new();
+    while accepted.len() < users.len() {
+        let reaction = match reactions.next().await {
+            Some(reaction) => reaction,
+            None => break,
+        };
+        let reaction = reaction.as_inner_ref();
+        match reaction.emoji.as_data().as_str() {
             "⭕" => {
-                match UserDataEntity::find_by_id(user.id.to_string())
-                    .one(&postgres_connect)
-                    .await
-                    .map_err(|e| anyhow::anyhow!(e))?
-                {
-                    Some(ud) => {
-                        ud.delete(&postgres_connect).await?;
-                    }
-                    None => todo!(),
+                if let Some(id) = reaction.user_id.filter(|id| !accepted.contains(id)) {
+                    accepted.push(id);
                 }
             }
-            "❌" => {
-                channel_id
-                    .send_message(&ctx.http, |f| f.embed(|e| e.title("削除を取り消します")))
-                    .await
-                    .context("埋め込みの作成に失敗しました")?;
-            }
-            _ => {
-                error_embed_message(ctx, channel_id, "正しい反応を選んで下さい").await?;
-            }
+            "❌" => break,
+            _ => {}
         }
     }
+    reactions.stop();
+    if accepted.len() < users.len() {
+        channel_id
+            .send_message(&ctx.http, |f| f.embed(|e| e.title("対戦を取り消します")))
+            .await
+            .context("埋め込みの作成に失敗しました")?;
+        return Ok(None);
+    }
 
-    Ok(())
+    // The challenger pays for the duel
+    if !consume_stamina(
+        &ctx,
+        channel_id,
+        &repo,
+        guild_id,
+        &user_data.user_id,
+        &PlayMode::Duel,
+    )
+    .await?
+    {
+        return Ok(None);
+    }
+
+    let mut builder = BattleBuilder::new(
+        PlayMode::Duel,
+        Some(user_data.clone().try_into()?),
+        Some(opponent_data.clone().try_into()?),
+        None,
+    );
+    builder
+        .player_status_setting(user_roster.level as i16)
+        .enemy_status_setting(opponent_roster.level as i16)
+        .player_equipment(user_gear)
+        .enemy_equipment(opponent_gear)
+        .player_user(user.id.0)
+        .enemy_user(opponent.id.0)
+        .choose_field(&field_config().await?, &mut rand::thread_rng());
+    let mut battle = builder.build();
+    battle.start_running();
+
+    let mut forfeit = false;
+    let winner = loop {
+        if let Some(winner) = battle.winner() {
+            break winner;
+        }
+        let side = battle.turn_side();
+        let turn_user = match battle.turn_controller() {
+            Controller::User(id) => id,
+            Controller::Computer => unreachable!("duel sides are always operated by users"),
+        };
+        let turn_name = match side {
+            StatusCharaType::Player => battle.player().meta.name.clone(),
+            StatusCharaType::Enemy => battle.enemy().meta.name.clone(),
+        };
+        let operation_embed = channel_id
+            .send_message(&ctx.http, |f| {
+                f.content(format!("<@{}>", turn_user))
+                    .embed(|e| {
+                        e.title(format!("{}のターンです", turn_name))
+                            .description("リアクションを押して操作してね")
+                    })
+                    .reactions(DUEL_REACTIONS.to_vec())
+            })
+            .await
+            .context("埋め込みの作成に失敗しました")?;
+
+        match &operation_embed
+            .await_reaction(&ctx)
+            .timeout(Duration::from_secs(timeout))
+            .author_id(turn_user)
+            .await
+        {
+            Some(reaction) => match reaction.as_inner_ref().emoji.as_data().as_str() {
+                BATTLE_PLAY => {
+                    battle.result_battle().await;
+                    hp_embed(&ctx, channel_id, &battle).await?;
+                }
+                BATTLE_GUARD => {
+                    battle.result_guard().await;
+                    channel_id
+                        .send_message(&ctx.http, |f| {
+                            f.embed(|e| e.title(format!("{}は防御した", turn_name)))
+                        })
+                        .await?;
+                }
+                _ => {
+                    error_embed_message(&ctx, channel_id, "正しい反応を選んで下さい").await?;
+                }
+            },
+            // Not operating in time is a loss
+            None => {
+                forfeit = true;
+                break match side {
+                    StatusCharaType::Player => StatusCharaType::Enemy,
+                    StatusCharaType::Enemy => StatusCharaType::Player,
+                };
+            }
+        }
+    };
+    battle.finish_running();
+
+    let user_fighter = Fighter {
+        user_id: &user_data.user_id,
+        chara: &user_data.player,
+        level: user_roster.level,
+    };
+    let opponent_fighter = Fighter {
+        user_id: &opponent_data.user_id,
+        chara: &opponent_data.player,
+        level: opponent_roster.level,
+    };
+    repo
+        .record_history(vec![
+            history_entry(
+                &battle,
+                StatusCharaType::Player,
+                winner == StatusCharaType::Player,
+                &user_fighter,
+                Some(&opponent_data.user_id),
+                opponent_roster.level,
+            ),
+            history_entry(
+                &battle,
+                StatusCharaType::Enemy,
+                winner == StatusCharaType::Enemy,
+                &opponent_fighter,
+                Some(&user_data.user_id),
+                user_roster.level,
+            ),
+        ])
+        .await?;
+
+    let (winner_user, winner_data, loser_data) = match winner {
+        StatusCharaType::Player => (&user, &user_data, &opponent_data),
+        StatusCharaType::Enemy => (&opponent, &opponent_data, &user_data),
+    };
+    channel_id
+        .send_message(&ctx.http, |f| {
+            f.embed(|e| {
+                e.title(format!("{}の勝利！", winner_user.name))
+                    .description(if forfeit {
+                        "時間切れで決着しました".to_string()
+                    } else {
+                        format!("{}ターンで決着しました", battle.elapsed_turns())
+                    })
+            })
+        })
+        .await
+        .context("埋め込みの作成に失敗しました")?;
+
+    let battle_uuid = battle.uuid();
+    repo
+        .record_duel(DuelModel {
+            battle_uuid,
+            winner_id: winner_data.user_id.clone(),
+            loser_id: loser_data.user_id.clone(),
+            winner_chara: winner_data.player.clone(),
+            loser_chara: loser_data.player.clone(),
+            elapsed_turns: battle.elapsed_turns() as i64,
+            forfeit,
+            finished_at: chrono::Local::now().naive_local(),
+        })
+        .await?;
+    record_ranked_match(
+        redis(&ctx).await,
+        &repo,
+        battle_uuid,
+        &winner_data.user_id,
+        &loser_data.user_id,
+    )
+    .await?;
+
+    Ok(Some(winner_user.id))
 }
 
-pub async fn setchara(
+pub(crate) async fn hp_embed(
+    ctx: &serenity::client::Context,
+    channel_id: ChannelId,
+    battle: &BattleData,
+) -> Result<Message, anyhow::Error> {
+    channel_id
+        .send_message(&ctx.http, |f| {
+            f.embed(|e| {
+                e.title("のこりhp")
+                    .field(&battle.player().meta.name, battle.player().charabase.hp, true)
+                    .field(&battle.enemy().meta.name, battle.enemy().charabase.hp, true);
+                field_embed(e, battle)
+            })
+        })
+        .await
+        .context("埋め込みの作成に失敗しました")
+}
+
+pub async fn setchara<R: UserRepository + RosterRepository>(
     ctx: &serenity::client::Context,
     channel_id: ChannelId,
     chara: String,
     user: User,
-    postgres_connect: sea_orm::DatabaseConnection,
+    repo: R,
 ) -> CommandResult {
-    let chara_data = CharaConfig::chara_new(&chara)
-        .await
-        .context("Invalid arg")?;
+    let chara_data = CharaConfig::from_file_name_noasync(&chara).context("Invalid arg")?;
+    let owned = repo
+        .find_roster(&user.id.to_string(), &chara)
+        .await?
+        .is_some();
+    if !owned && !unlock_config().await?.is_default_unlocked(&chara) {
+        error_embed_message(ctx, channel_id, "まだ仲間になっていないキャラクターです").await?;
+        return Ok(());
+    }
     channel_id
         .send_message(&ctx.http, |f| {
             f.embed(|e| {
                 e.title(format!(
                     "キャラクターを{}に変更しました",
-                    &chara_data.charabase.name
+                    &chara_data.meta.name
                 ))
                 .description(" ")
             })
         })
         .await
         .context("埋め込みの作成に失敗しました")?;
-    let userdata = UserDataEntity::find_by_id(user.id.0.to_string())
-        .one(&postgres_connect)
-        .await?;
+    let userdata = repo.find_user(&user.id.0.to_string()).await?;
 
-    if let Some(data) = userdata {
-        let new_model: UserDataActiveModel = UserDataModel {
-            player: chara_data.charabase.name,
-            ..data
-        }
-        .into();
-        new_model.update(&postgres_connect).await?;
+    if userdata.is_some() {
+        repo.set_player(&user.id.to_string(), &chara).await?;
     } else {
-        let active_userdata: UserDataActiveModel = UserDataModel {
+        let userdata = UserDataModel {
             user_id: user.id.to_string(),
-            player: chara_data.charabase.name,
-            level: 1,
-            exp: 1,
+            player: chara.clone(),
+            assist: None,
             battle_uuid: None,
+            gold: 0,
+            stamina: config(ctx)
+                .await
+                .stamina_config()
+                .map_or(DEFAULT_STAMINA_MAX, |c| c.max()) as i64,
+            stamina_updated_at: chrono::Local::now().naive_local(),
+            deleted_at: None,
+        };
+        repo.insert_user(userdata).await?;
+    }
+    // Each character keeps its own level
+    repo.find_or_unlock(&user.id.to_string(), &chara).await?;
+    Ok(())
+}
+/// Give the exp and the rewards and unlock characters after defeating the enemy
+/// The assist gains its share of the exp too
+async fn victory<R: Repository + TransactionRepository>(
+    ctx: &serenity::client::Context,
+    channel_id: ChannelId,
+    repo: &R,
+    userdata: &UserDataModel,
+    roster: &RosterModel,
+    battle: &BattleData,
+    enemy_level: i64,
+) -> anyhow::Result<()> {
+    record_battle(repo, userdata, roster, battle, enemy_level, true).await?;
+    let level = award_exp(repo, roster, battle).await?;
+    share_assist_exp(
+        repo,
+        &userdata.user_id,
+        battle,
+        battle.enemy().meta.get_exp,
+    )
+    .await?;
+    let summary = RewardSummary {
+        exp: battle.enemy().meta.get_exp,
+        level_before: roster.level as u32,
+        level_after: level,
+        gold: battle.calculate_gold(enemy_level as u32),
+        drops: battle.resolve_drops(),
+    };
+    grant_rewards(
+        ctx,
+        channel_id,
+        repo,
+        &userdata.user_id,
+        &battle.enemy().meta.name,
+        &summary,
+    )
+    .await?;
+    let events = [
+        ProgressEvent::EnemyDefeated { battle },
+        ProgressEvent::LevelReached {
+            chara: &userdata.player,
+            levelup_exp: &battle.player().meta.levelup_exp,
+            level,
+        },
+    ];
+    unlock_progress(ctx, channel_id, repo, &userdata.user_id, &events).await?;
+    achievement_progress(ctx, channel_id, repo, &userdata.user_id, &events).await?;
+    quest_progress(ctx, channel_id, repo, &userdata.user_id, &events).await?;
+    bond_progress(
+        ctx,
+        channel_id,
+        repo,
+        &userdata.user_id,
+        &userdata.player,
+        &events,
+    )
+    .await
+}
+
+/// Save the finished battle against the computer and add it to the history of the user
+/// A finished battle is no longer resumed by `/play`
+async fn record_battle<R: TransactionRepository>(
+    repo: &R,
+    userdata: &UserDataModel,
+    roster: &RosterModel,
+    battle: &BattleData,
+    enemy_level: i64,
+    won: bool,
+) -> anyhow::Result<()> {
+    let fighter = Fighter {
+        user_id: &userdata.user_id,
+        chara: &userdata.player,
+        level: roster.level,
+    };
+    let entry = history_entry(battle, StatusCharaType::Player, won, &fighter, None, enemy_level);
+    let txn = repo.begin_transaction().await?;
+    txn.save_battle(PlayDataModel {
+        enemy_level,
+        ..battle.try_into()?
+    })
+    .await?;
+    txn.set_battle(&userdata.user_id, None).await?;
+    txn.record_history(vec![entry]).await?;
+    txn.commit().await?;
+    Ok(())
+}
+
+/// Use the first owned battle item on the player
+/// The turn is not consumed when the user has no item
+pub(crate) async fn use_battle_item<R>(
+    ctx: &serenity::client::Context,
+    channel_id: ChannelId,
+    repo: &R,
+    userdata: &UserDataModel,
+    battle: &mut BattleData,
+) -> anyhow::Result<()>
+where
+    R: InventoryRepository + ProgressRepository + TransactionRepository,
+{
+    // Items such as stamina refills can't be used in a battle
+    let mut usable = None;
+    for entry in repo.owned(&userdata.user_id, "Item").await? {
+        if let Ok(item) = ItemConfig::from_file_name(&entry.item_id).await {
+            if item.effect.is_battle() {
+                usable = Some(item);
+                break;
+            }
+        }
+    }
+    let item = match usable {
+        Some(item) => item,
+        None => {
+            error_embed_message(ctx, channel_id, "使えるアイテムがありません").await?;
+            return Ok(());
         }
-        .into();
-        active_userdata.insert(&postgres_connect).await?;
+    };
+    if !repo.use_item(&userdata.user_id, &item.id, 1).await? {
+        error_embed_message(ctx, channel_id, "使えるアイテムがありません").await?;
+        return Ok(());
     }
+    battle.use_item(StatusCharaType::Player, &item);
+    channel_id
+        .send_message(&ctx.http, |f| {
+            f.embed(|e| {
+                e.title(format!("{}は{}を使った", battle.player().meta.name, item.name))
+                    .description(&item.description)
+            })
+        })
+        .await
+        .context("埋め込みの作成に失敗しました")?;
+    quest_progress(
+        ctx,
+        channel_id,
+        repo,
+        &userdata.user_id,
+        &[ProgressEvent::ItemUsed { item: &item.id }],
+    )
+    .await
+}
+
+/// Save the battle and remember it so that `/play` resumes it
+/// The exp is only given by [award_exp] when the enemy is defeated
+async fn save_progress<R: TransactionRepository>(
+    repo: &R,
+    userdata: &UserDataModel,
+    battle: &BattleData,
+    enemy_level: i64,
+) -> anyhow::Result<()> {
+    let txn = repo.begin_transaction().await?;
+    txn.save_battle(PlayDataModel {
+        enemy_level,
+        ..battle.try_into()?
+    })
+    .await?;
+    txn.set_battle(&userdata.user_id, Some(battle.uuid())).await?;
+    txn.commit().await?;
     Ok(())
 }
+
+/// Write the exp of the defeated enemy to the active character
+/// Returns the new level of the character
+async fn award_exp<R: RosterRepository>(
+    repo: &R,
+    roster: &RosterModel,
+    battle: &BattleData,
+) -> anyhow::Result<u32> {
+    let user_exp = roster.exp as f64 + battle.enemy().meta.get_exp as f64;
+    let player_level = battle.calculate_player_level(user_exp);
+
+    repo.set_level(&roster.user_id, &roster.chara, player_level as i64, user_exp as i64)
+        .await?;
+    Ok(player_level as u32)
+}
+
 /// 操作の埋め込み
-async fn operation_enemy(
+pub(crate) async fn operation_enemy(
     ctx: &serenity::client::Context,
     channel_id: ChannelId,
     reactions: Vec<ReactionType>,
@@ -510,7 +1010,7 @@ async fn operation_enemy(
         .context("埋め込みの作成に失敗しました")
 }
 
-async fn error_embed_message<M: Into<String>>(
+pub(crate) async fn error_embed_message<M: Into<String>>(
     ctx: &serenity::client::Context,
     channel_id: ChannelId,
     context: M,
@@ -522,3 +1022,70 @@ async fn error_embed_message<M: Into<String>>(
         .await
         .context("埋め込みの作成に失敗しました")
 }
+
+/// Embed with a title, the description is left out when it is empty
+pub(crate) async fn embed_message<T: Into<String>, D: Into<String>>(
+    ctx: &serenity::client::Context,
+    channel_id: ChannelId,
+    title: T,
+    description: D,
+) -> anyhow::Result<()> {
+    let description = description.into();
+    channel_id
+        .send_message(&ctx.http, |f| {
+            f.embed(|e| {
+                e.title(title.into());
+                if !description.is_empty() {
+                    e.description(description);
+                }
+                e
+            })
+        })
+        .await
+        .context("埋め込みの作成に失敗しました")?;
+    Ok(())
+}
+
+/// Value of a string option of a slash command
+pub(crate) fn string_option(options: &[CommandDataOption], name: &str) -> Option<String> {
+    options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| match option.resolved.as_ref() {
+            Some(CommandDataOptionValue::String(s)) => Some(s.clone()),
+            _ => None,
+        })
+}
+
+/// Value of an integer option of a slash command
+pub(crate) fn integer_option(options: &[CommandDataOption], name: &str) -> Option<i64> {
+    options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| match option.resolved.as_ref() {
+            Some(CommandDataOptionValue::Integer(i)) => Some(*i),
+            _ => None,
+        })
+}
+
+/// Value of a boolean option of a slash command
+pub(crate) fn boolean_option(options: &[CommandDataOption], name: &str) -> Option<bool> {
+    options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| match option.resolved.as_ref() {
+            Some(CommandDataOptionValue::Boolean(b)) => Some(*b),
+            _ => None,
+        })
+}
+
+/// Value of a channel option of a slash command
+pub(crate) fn channel_option(options: &[CommandDataOption], name: &str) -> Option<ChannelId> {
+    options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| match option.resolved.as_ref() {
+            Some(CommandDataOptionValue::Channel(channel)) => Some(channel.id),
+            _ => None,
+        })
+}
@@ -0,0 +1,196 @@
+use crate::reward::give_rewards;
+use anyhow::Context;
+use battle_machine::{
+    event::ProgressEvent,
+    quest::{Quest, QuestConfig},
+};
+use chrono::{Local, NaiveDate};
+use extension::store::ExtensionStore;
+use serenity::client;
+use serenity::framework::standard::CommandResult;
+use serenity::model::prelude::ChannelId;
+use serenity::model::user::User;
+use thrpg_database::{
+    quest::Model as QuestModel,
+    repository::{ProgressRepository, RepositoryTransaction, TransactionRepository},
+};
+
+const PROGRESS_BAR_WIDTH: usize = 10;
+
+/// Quests of `quest/` and Contents extensions
+pub async fn quest_config() -> anyhow::Result<QuestConfig> {
+    let extension_dirs = ExtensionStore::extension_files()
+        .await
+        .map(|store| store.contents_dirs())
+        .unwrap_or_default();
+    QuestConfig::load_with_extensions("quest/", extension_dirs).await
+}
+
+/// Add the progress of the events to the active quests of the day and reward the completed ones
+/// Returns the completed quests with one line per reward
+pub async fn record_quests<'a, R: ProgressRepository + TransactionRepository>(
+    repo: &R,
+    config: &'a QuestConfig,
+    user_id: &str,
+    events: &[ProgressEvent<'_>],
+    today: NaiveDate,
+) -> anyhow::Result<Vec<(&'a Quest, Vec<String>)>> {
+    let mut completed_now = Vec::new();
+    for (quest, period) in config.active_quests(today) {
+        let gained: u32 = events.iter().map(|e| quest.objective.progress(e)).sum();
+        if gained == 0 {
+            continue;
+        }
+
+        let current = repo.find_quest(user_id, &quest.id, &period).await?;
+        if current.as_ref().is_some_and(|c| c.completed_at.is_some()) {
+            continue;
+        }
+        let goal = quest.objective.goal() as i64;
+        let progress = (current.as_ref().map_or(0, |c| c.progress) + gained as i64).min(goal);
+        let completed = progress >= goal;
+
+        // The reward is given together with the completion so it can't be received twice
+        let txn = repo.begin_transaction().await?;
+        txn.save_quest(QuestModel {
+            user_id: user_id.to_string(),
+            quest_id: quest.id.clone(),
+            period,
+            progress,
+            completed_at: completed.then(|| Local::now().naive_local()),
+        })
+        .await?;
+        if completed {
+            let reward_lines = give_rewards(&txn, user_id, &quest.rewards).await?;
+            completed_now.push((quest, reward_lines));
+        }
+        txn.commit().await?;
+    }
+    Ok(completed_now)
+}
+
+/// Add the progress of the events to the active quests and give the rewards of completed ones
+pub async fn quest_progress<R: ProgressRepository + TransactionRepository>(
+    ctx: &client::Context,
+    channel_id: ChannelId,
+    repo: &R,
+    user_id: &str,
+    events: &[ProgressEvent<'_>],
+) -> anyhow::Result<()> {
+    let config = quest_config().await?;
+    let today = Local::now().naive_local().date();
+    for (quest, reward_lines) in record_quests(repo, &config, user_id, events, today).await? {
+        channel_id
+            .send_message(&ctx.http, |f| {
+                f.embed(|e| {
+                    e.title(format!("クエスト達成: {}", quest.name));
+                    if !reward_lines.is_empty() {
+                        e.field("報酬", reward_lines.join("\n"), false);
+                    }
+                    e
+                })
+            })
+            .await
+            .context("埋め込みの作成に失敗しました")?;
+    }
+    Ok(())
+}
+
+/// `/quests`
+/// Active quests with progress bars
+pub async fn quests<R: ProgressRepository>(
+    ctx: client::Context,
+    channel_id: ChannelId,
+    user: User,
+    repo: R,
+) -> CommandResult {
+    let config = quest_config().await?;
+    let active = config.active_quests(Local::now().naive_local().date());
+    let periods = active.iter().map(|(_, period)| period.clone()).collect();
+    let progress = repo.quests_in(&user.id.to_string(), periods).await?;
+
+    channel_id
+        .send_message(&ctx.http, |f| {
+            f.embed(|e| {
+                e.title("クエスト");
+                if active.is_empty() {
+                    e.description("受けられるクエストはありません");
+                }
+                for (quest, period) in active.iter() {
+                    let row = progress
+                        .iter()
+                        .find(|p| p.quest_id == quest.id && p.period == *period);
+                    let goal = quest.objective.goal();
+                    let current = row.map_or(0, |r| r.progress as u32);
+                    let status = if row.is_some_and(|r| r.completed_at.is_some()) {
+                        "達成済み"
+                    } else {
+                        ""
+                    };
+                    e.field(
+                        format!("[{:?}] {}", quest.rotation, quest.name),
+                        format!("{} {}/{} {}", progress_bar(current, goal), current, goal, status),
+                        false,
+                    );
+                }
+                e
+            })
+        })
+        .await
+        .context("埋め込みの作成に失敗しました")?;
+    Ok(())
+}
+
+fn progress_bar(current: u32, goal: u32) -> String {
+    let filled = if goal == 0 {
+        PROGRESS_BAR_WIDTH
+    } else {
+        (current.min(goal) as usize * PROGRESS_BAR_WIDTH) / goal as usize
+    };
+    format!("{}{}", "▰".repeat(filled), "▱".repeat(PROGRESS_BAR_WIDTH - filled))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battle_machine::quest::{QuestObjective, QuestRotation};
+    use battle_machine::reward::Reward;
+    use thrpg_database::repository::{test_user, MemoryRepository, UserRepository};
+
+    fn config() -> QuestConfig {
+        QuestConfig {
+            daily_count: 0,
+            weekly_count: 0,
+            quest: vec![Quest {
+                id: "potions".to_string(),
+                name: "薬の試用".to_string(),
+                rotation: QuestRotation::Permanent,
+                objective: QuestObjective::UseItem {
+                    item: Some("potion".to_string()),
+                    count: 2,
+                },
+                rewards: vec![Reward::Gold { amount: 100 }],
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn completed_quest_gives_the_reward_once() {
+        let repo = MemoryRepository::new();
+        repo.insert_user(test_user("user")).await.unwrap();
+        let config = config();
+        let today = Local::now().naive_local().date();
+        let used = [ProgressEvent::ItemUsed { item: "potion" }];
+
+        assert!(record_quests(&repo, &config, "user", &used, today).await.unwrap().is_empty());
+        let completed = record_quests(&repo, &config, "user", &used, today).await.unwrap();
+        assert_eq!(completed, [(&config.quest[0], vec!["100G".to_string()])]);
+        assert!(record_quests(&repo, &config, "user", &used, today).await.unwrap().is_empty());
+        assert_eq!(repo.find_user("user").await.unwrap().unwrap().gold, 100);
+
+        let other = [ProgressEvent::ItemUsed { item: "ether" }];
+        let period = QuestRotation::Permanent.period(today);
+        assert!(record_quests(&repo, &config, "user", &other, today).await.unwrap().is_empty());
+        assert_eq!(repo.find_quest("user", "potions", &period).await.unwrap().unwrap().progress, 2);
+    }
+}
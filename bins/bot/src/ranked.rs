@@ -0,0 +1,136 @@
+use crate::shared::redis;
+use anyhow::Context;
+use battle_machine::rating::{update_rating, MatchResult, Rating, RatingConstants};
+use chrono::{Local, NaiveDateTime};
+use sea_orm::prelude::Uuid;
+use serenity::client;
+use serenity::framework::standard::CommandResult;
+use serenity::model::prelude::ChannelId;
+use thrpg_database::{
+    rating::{rating_cache, rating_top, Model as RatingModel},
+    redis_connect::MultiplexedConnection,
+    repository::ScoreRepository,
+};
+
+/// Length of one rating period in days
+/// The rating deviation grows for every period without a ranked match
+const RATING_PERIOD_DAYS: i64 = 1;
+
+/// Number of users shown in `/rating`
+const RANKING_SIZE: u64 = 10;
+
+/// Update the ratings of both users after a ranked match
+/// The Redis ranking is only a cache of the saved ratings, failing to update it is logged
+pub async fn record_ranked_match<R: ScoreRepository>(
+    redis: Option<MultiplexedConnection>,
+    scores: &R,
+    battle_uuid: Uuid,
+    winner_id: &str,
+    loser_id: &str,
+) -> anyhow::Result<()> {
+    let constants = RatingConstants::default();
+    let now = Local::now().naive_local();
+    let initial = Rating::initial(&constants);
+    let unrated: Vec<RatingModel> = [winner_id, loser_id]
+        .iter()
+        .map(|user_id| RatingModel {
+            user_id: user_id.to_string(),
+            rating: initial.rating,
+            deviation: initial.deviation,
+            matches: 0,
+            last_match: now,
+        })
+        .collect();
+
+    // Calculated from the ratings read in the same transaction that writes them
+    let updated = scores
+        .update_ratings(
+            battle_uuid,
+            &unrated,
+            Box::new(move |current| {
+                let (winner, winner_idle) = current_rating(&current[0], now);
+                let (loser, loser_idle) = current_rating(&current[1], now);
+                [
+                    (
+                        &current[0],
+                        update_rating(&winner, &loser, MatchResult::Win, winner_idle, &constants),
+                    ),
+                    (
+                        &current[1],
+                        update_rating(&loser, &winner, MatchResult::Lose, loser_idle, &constants),
+                    ),
+                ]
+                .into_iter()
+                .map(|(model, rating)| RatingModel {
+                    user_id: model.user_id.clone(),
+                    rating: rating.rating,
+                    deviation: rating.deviation,
+                    matches: model.matches + 1,
+                    last_match: now,
+                })
+                .collect()
+            }),
+        )
+        .await?;
+
+    if let Some(mut redis) = redis {
+        for model in updated.iter() {
+            if let Err(e) = rating_cache(&mut redis, &model.user_id, model.rating).await {
+                eprintln!("caching the rating of {} failed: {}", model.user_id, e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// `/rating`
+pub async fn rating<R: ScoreRepository>(
+    ctx: client::Context,
+    channel_id: ChannelId,
+    repo: R,
+) -> CommandResult {
+    let top = top_ratings(redis(&ctx).await, &repo).await?;
+    let lines: Vec<String> = top
+        .iter()
+        .enumerate()
+        .map(|(i, (user_id, rating))| format!("{}. <@{}> {:.0}", i + 1, user_id, rating))
+        .collect();
+    channel_id
+        .send_message(&ctx.http, |f| {
+            f.embed(|e| {
+                e.title("レーティング").description(if lines.is_empty() {
+                    "まだ誰もランク戦をしていません".to_string()
+                } else {
+                    lines.join("\n")
+                })
+            })
+        })
+        .await
+        .context("埋め込みの作成に失敗しました")?;
+    Ok(())
+}
+
+/// From the Redis ranking, or the database when Redis is not configured or fails
+async fn top_ratings<R: ScoreRepository>(
+    redis: Option<MultiplexedConnection>,
+    repo: &R,
+) -> anyhow::Result<Vec<(String, f64)>> {
+    if let Some(mut redis) = redis {
+        match rating_top(&mut redis, RANKING_SIZE as isize).await {
+            Ok(top) => return Ok(top),
+            Err(e) => eprintln!("reading the rating ranking failed: {}", e),
+        }
+    }
+    let top = repo.top_ratings(RANKING_SIZE).await?;
+    Ok(top.into_iter().map(|model| (model.user_id, model.rating)).collect())
+}
+
+/// Rating and idle rating periods of a user
+fn current_rating(model: &RatingModel, now: NaiveDateTime) -> (Rating, u32) {
+    let idle = match model.matches {
+        0 => 0,
+        _ => (now - model.last_match).num_days() / RATING_PERIOD_DAYS,
+    };
+    (Rating::new(model.rating, model.deviation), idle.max(0) as u32)
+}
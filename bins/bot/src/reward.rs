@@ -0,0 +1,91 @@
+use anyhow::Context;
+use battle_machine::{
+    equipment::EquipmentConfig,
+    item::ItemConfig,
+    reward::{Reward, RewardSummary},
+};
+use serenity::client;
+use serenity::model::prelude::ChannelId;
+use thrpg_database::repository::{
+    InventoryRepository, RepositoryTransaction, RosterRepository, TransactionRepository,
+    UserRepository,
+};
+
+/// Give the rewards to the user and show them in one embed
+pub async fn grant_rewards<R: TransactionRepository>(
+    ctx: &client::Context,
+    channel_id: ChannelId,
+    repo: &R,
+    user_id: &str,
+    enemy_name: &str,
+    summary: &RewardSummary,
+) -> anyhow::Result<()> {
+    let txn = repo.begin_transaction().await?;
+    // Gold drops are added by give_rewards
+    txn.add_gold(user_id, summary.gold as i64).await?;
+    let drop_lines = give_rewards(&txn, user_id, &summary.drops).await?;
+    txn.commit().await?;
+
+    channel_id
+        .send_message(&ctx.http, |f| {
+            f.embed(|e| {
+                e.title(format!("{}を倒した", enemy_name))
+                    .field("経験値", format!("+{}", summary.exp), true)
+                    .field("ゴールド", format!("+{}G", summary.total_gold()), true);
+                if summary.is_level_up() {
+                    e.field(
+                        "レベルアップ！",
+                        format!("Lv.{} → Lv.{}", summary.level_before, summary.level_after),
+                        true,
+                    );
+                }
+                if !drop_lines.is_empty() {
+                    e.field("ドロップ", drop_lines.join("\n"), false);
+                }
+                e
+            })
+        })
+        .await
+        .context("埋め込みの作成に失敗しました")?;
+    Ok(())
+}
+
+/// Give the rewards in the transaction
+/// Returns one line per reward for the embed
+pub async fn give_rewards<R: UserRepository + InventoryRepository + RosterRepository>(
+    txn: &R,
+    user_id: &str,
+    rewards: &[Reward],
+) -> anyhow::Result<Vec<String>> {
+    let mut lines = Vec::new();
+    for reward in rewards {
+        match reward {
+            Reward::Item { id, quantity } => {
+                txn.add_item(user_id, id, "Item", *quantity as i64).await?;
+                let name = ItemConfig::from_file_name(id)
+                    .await
+                    .map_or(id.clone(), |i| i.name);
+                lines.push(format!("{} ×{}", name, quantity));
+            }
+            Reward::Equipment { id } => {
+                txn.add_item(user_id, id, "Equipment", 1).await?;
+                let name = EquipmentConfig::from_file_name(id)
+                    .await
+                    .map_or(id.clone(), |e| e.name);
+                lines.push(name);
+            }
+            Reward::Gold { amount } => {
+                txn.add_gold(user_id, *amount as i64).await?;
+                lines.push(format!("{}G", amount));
+            }
+            Reward::UnlockToken { chara } => {
+                if txn.unlock(user_id, chara).await? {
+                    lines.push(format!("{}が仲間になった！", chara));
+                } else {
+                    lines.push(format!("{}の札 (既に仲間です)", chara));
+                }
+            }
+        }
+    }
+    Ok(lines)
+}
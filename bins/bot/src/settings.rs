@@ -0,0 +1,199 @@
+use crate::play::{boolean_option, channel_option, error_embed_message, string_option};
+use crate::shared::{config, database, guild_settings_cache};
+use anyhow::Context;
+use battle_machine::mode::Difficulty;
+use chrono::Local;
+use serenity::client;
+use serenity::futures::future::BoxFuture;
+use serenity::framework::standard::CommandResult;
+use serenity::model::channel::Message;
+use serenity::model::prelude::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::prelude::{ChannelId, GuildId};
+use setting_config::Config;
+use std::time::Duration;
+use thrpg_database::guild_settings::{self, Model as GuildSettingsModel};
+
+/// Prefix when neither `/settings` nor `THRPG.toml` has one
+pub const DEFAULT_PREFIX: &str = "th!";
+
+/// How long the settings of a guild are used before they are read again
+pub const SETTINGS_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Settings of a guild, `THRPG.toml` is used for what `/settings` did not change
+#[derive(Debug, Clone)]
+pub struct GuildSettings {
+    pub prefix: String,
+    pub language: Option<String>,
+    /// Every channel when `None`
+    pub battle_channels: Option<Vec<u64>>,
+    pub stamina_enabled: bool,
+    /// Enemies of `/play`, `Normal` when it is not set
+    pub difficulty: Difficulty,
+}
+
+impl GuildSettings {
+    pub fn resolve(config: &Config, guild_id: Option<GuildId>, stored: Option<&GuildSettingsModel>) -> Self {
+        Self {
+            prefix: stored
+                .and_then(|s| s.prefix.clone())
+                .or_else(|| config.prefix().map(str::to_string))
+                .unwrap_or_else(|| DEFAULT_PREFIX.to_string()),
+            language: stored
+                .and_then(|s| s.language.clone())
+                .or_else(|| config.language().map(str::to_string)),
+            battle_channels: stored.and_then(|s| s.battle_channel_ids()),
+            stamina_enabled: stored.and_then(|s| s.stamina_enabled).unwrap_or_else(|| {
+                config
+                    .stamina_config()
+                    .is_some_and(|c| c.is_enabled(guild_id.map(|g| g.0)))
+            }),
+            difficulty: stored
+                .and_then(|s| s.difficulty.as_deref())
+                .and_then(|d| Difficulty::try_from_value(d).ok())
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn allows_battle(&self, channel_id: ChannelId) -> bool {
+        self.battle_channels
+            .as_ref()
+            .is_none_or(|channels| channels.contains(&channel_id.0))
+    }
+}
+
+/// Settings of the guild, cached for [SETTINGS_TTL]
+/// Outside guilds only `THRPG.toml` is used
+pub async fn guild_settings(ctx: &client::Context, guild_id: Option<GuildId>) -> anyhow::Result<GuildSettings> {
+    let config = config(ctx).await;
+    let stored = match guild_id {
+        Some(guild_id) => {
+            guild_settings_cache(ctx)
+                .await
+                .get(&database(ctx).await, &guild_id.to_string())
+                .await?
+        }
+        None => None,
+    };
+    Ok(GuildSettings::resolve(&config, guild_id, stored.as_ref()))
+}
+
+/// Prefix of the guild for the framework
+pub fn dynamic_prefix<'fut>(
+    ctx: &'fut client::Context,
+    msg: &'fut Message,
+) -> BoxFuture<'fut, Option<String>> {
+    Box::pin(async move {
+        match guild_settings(ctx, msg.guild_id).await {
+            Ok(settings) => Some(settings.prefix),
+            Err(e) => {
+                eprintln!("reading guild settings failed: {}", e);
+                None
+            }
+        }
+    })
+}
+
+/// `/play`, `/duel` and the `play` subcommands of `/incident` and `/tournament`
+pub fn starts_battle(command: &ApplicationCommandInteraction) -> bool {
+    match command.data.name.as_str() {
+        "play" | "duel" => true,
+        "incident" | "tournament" => command
+            .data
+            .options
+            .first()
+            .is_some_and(|subcommand| subcommand.name == "play"),
+        _ => false,
+    }
+}
+
+/// `/settings`
+/// Anyone can see the settings, only administrators of the guild can change them
+pub async fn settings(
+    ctx: client::Context,
+    command: &ApplicationCommandInteraction,
+    postgres_connect: sea_orm::DatabaseConnection,
+) -> CommandResult {
+    let channel_id = command.channel_id;
+    let guild_id = match command.guild_id {
+        Some(guild_id) => guild_id,
+        None => {
+            error_embed_message(&ctx, channel_id, "設定はサーバー内で使って下さい").await?;
+            return Ok(());
+        }
+    };
+    let options = &command.data.options;
+    if !options.is_empty() {
+        let is_admin = command
+            .member
+            .as_ref()
+            .and_then(|member| member.permissions)
+            .is_some_and(|permissions| permissions.administrator());
+        if !is_admin {
+            error_embed_message(&ctx, channel_id, "管理者のみ実行できます").await?;
+            return Ok(());
+        }
+        let key = guild_id.to_string();
+        if boolean_option(options, "reset") == Some(true) {
+            guild_settings::reset(&postgres_connect, &key).await?;
+        } else {
+            let now = Local::now().naive_local();
+            let mut stored = guild_settings::find(&postgres_connect, &key)
+                .await?
+                .unwrap_or_else(|| GuildSettingsModel::empty(&key, now));
+            if let Some(prefix) = string_option(options, "prefix") {
+                let prefix = prefix.trim().to_string();
+                if prefix.is_empty() || prefix.contains(char::is_whitespace) {
+                    error_embed_message(&ctx, channel_id, "プレフィックスに空白は使えません").await?;
+                    return Ok(());
+                }
+                stored.prefix = Some(prefix);
+            }
+            if let Some(language) = string_option(options, "language") {
+                stored.language = Some(language);
+            }
+            if let Some(stamina) = boolean_option(options, "stamina") {
+                stored.stamina_enabled = Some(stamina);
+            }
+            if let Some(difficulty) = string_option(options, "difficulty") {
+                stored.difficulty = Some(difficulty);
+            }
+            let mut channels = stored.battle_channel_ids().unwrap_or_default();
+            if let Some(channel) = channel_option(options, "allow_channel") {
+                if !channels.contains(&channel.0) {
+                    channels.push(channel.0);
+                }
+            }
+            if let Some(channel) = channel_option(options, "remove_channel") {
+                channels.retain(|id| *id != channel.0);
+            }
+            stored.set_battle_channel_ids(channels);
+            stored.updated_at = now;
+            guild_settings::save(&postgres_connect, stored).await?;
+        }
+        guild_settings_cache(&ctx).await.invalidate(&key);
+    }
+
+    let settings = guild_settings(&ctx, Some(guild_id)).await?;
+    let channels = match &settings.battle_channels {
+        Some(channels) => channels
+            .iter()
+            .map(|id| format!("<#{}>", id))
+            .collect::<Vec<_>>()
+            .join(" "),
+        None => "すべて".to_string(),
+    };
+    channel_id
+        .send_message(&ctx.http, |f| {
+            f.embed(|e| {
+                e.title("サーバー設定")
+                    .field("プレフィックス", &settings.prefix, true)
+                    .field("言語", settings.language.as_deref().unwrap_or("未設定"), true)
+                    .field("スタミナ", if settings.stamina_enabled { "有効" } else { "無効" }, true)
+                    .field("難易度", settings.difficulty.as_str(), true)
+                    .field("戦闘できるチャンネル", channels, false)
+            })
+        })
+        .await
+        .context("埋め込みの作成に失敗しました")?;
+    Ok(())
+}
@@ -0,0 +1,201 @@
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbErr, Statement};
+use serenity::client::Context;
+use serenity::prelude::TypeMapKey;
+use setting_config::Config;
+use std::sync::Arc;
+use std::time::Duration;
+use thrpg_database::database_connect::{connect, is_in_memory};
+use thrpg_database::guild_settings::GuildSettingsCache;
+use thrpg_database::redis_connect::{connect_multiplexed, MultiplexedConnection};
+use thrpg_database::session::{MemorySessionCache, RedisSessionCache, SessionCache};
+use tokio::sync::RwLock;
+
+/// How often the pool is checked by [SharedDatabase::spawn_health_check]
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// `THRPG.toml`, read once at startup
+pub struct ConfigKey;
+
+impl TypeMapKey for ConfigKey {
+    type Value = Arc<Config>;
+}
+
+/// The pool shared by every interaction
+pub struct DatabaseKey;
+
+impl TypeMapKey for DatabaseKey {
+    type Value = SharedDatabase;
+}
+
+/// Live battles, see [thrpg_database::session]
+pub struct SessionKey;
+
+impl TypeMapKey for SessionKey {
+    type Value = Arc<dyn SessionCache>;
+}
+
+/// Settings of `/settings`, see [crate::settings]
+pub struct GuildSettingsKey;
+
+impl TypeMapKey for GuildSettingsKey {
+    type Value = Arc<GuildSettingsCache>;
+}
+
+/// Redis connection shared by every interaction, only inserted when Redis is configured
+pub struct RedisKey;
+
+impl TypeMapKey for RedisKey {
+    type Value = MultiplexedConnection;
+}
+
+/// `None` when `redis_config.db_address` is not set
+pub async fn connect_redis(config: &Config) -> anyhow::Result<Option<MultiplexedConnection>> {
+    match redis_url(config) {
+        Some(url) => Ok(Some(connect_multiplexed(url).await?)),
+        None => Ok(None),
+    }
+}
+
+/// Redis when `redis_config.db_address` is set, otherwise this process only
+pub async fn connect_sessions(config: &Config) -> anyhow::Result<Arc<dyn SessionCache>> {
+    match redis_url(config) {
+        Some(url) => Ok(Arc::new(RedisSessionCache::connect(url).await?)),
+        None => Ok(Arc::new(MemorySessionCache::new())),
+    }
+}
+
+/// `redis_config.db_address`, `None` when it is missing or empty
+pub fn redis_url(config: &Config) -> Option<String> {
+    config
+        .redis_config()
+        .and_then(|redis| redis.db_address.clone())
+        .filter(|url| !url.is_empty())
+}
+
+/// Connection pool that is replaced when it stops answering
+#[derive(Clone)]
+pub struct SharedDatabase {
+    url: String,
+    connection: Arc<RwLock<DatabaseConnection>>,
+}
+
+impl SharedDatabase {
+    pub async fn connect(url: impl Into<String>) -> Result<Self, DbErr> {
+        let url = url.into();
+        let connection = connect(url.as_str()).await?;
+        Ok(Self {
+            url,
+            connection: Arc::new(RwLock::new(connection)),
+        })
+    }
+
+    /// The current pool, cloning it is cheap
+    pub async fn connection(&self) -> DatabaseConnection {
+        self.connection.read().await.clone()
+    }
+
+    /// Run `SELECT 1` on the pool
+    pub async fn health_check(&self) -> Result<(), DbErr> {
+        let db = self.connection().await;
+        db.execute(Statement::from_string(db.get_database_backend(), "SELECT 1".to_string()))
+            .await?;
+        Ok(())
+    }
+
+    /// Open a new pool and use it from now on
+    /// The old pool is closed when the last interaction using it finishes
+    /// An in-memory SQLite database is kept, a new pool would open an empty one without its data
+    pub async fn reconnect(&self) -> Result<(), DbErr> {
+        if is_in_memory(&self.url) {
+            return Err(DbErr::Conn(
+                "an in-memory database can't be reconnected without losing its data".to_string(),
+            ));
+        }
+        let connection = connect(self.url.as_str()).await?;
+        *self.connection.write().await = connection;
+        Ok(())
+    }
+
+    /// Check the pool in the background and reconnect when the check fails
+    pub fn spawn_health_check(&self) -> tokio::task::JoinHandle<()> {
+        let database = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = database.health_check().await {
+                    eprintln!("database health check failed: {}", e);
+                    match database.reconnect().await {
+                        Ok(()) => eprintln!("reconnected to the database"),
+                        Err(e) => eprintln!("reconnecting to the database failed: {}", e),
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// Config stored in the data of the client
+pub async fn config(ctx: &Context) -> Arc<Config> {
+    ctx.data
+        .read()
+        .await
+        .get::<ConfigKey>()
+        .cloned()
+        .expect("ConfigKey is inserted when the client is built")
+}
+
+/// Current pool stored in the data of the client
+pub async fn database(ctx: &Context) -> DatabaseConnection {
+    let database = ctx
+        .data
+        .read()
+        .await
+        .get::<DatabaseKey>()
+        .cloned()
+        .expect("DatabaseKey is inserted when the client is built");
+    database.connection().await
+}
+
+/// Live battles stored in the data of the client
+pub async fn sessions(ctx: &Context) -> Arc<dyn SessionCache> {
+    ctx.data
+        .read()
+        .await
+        .get::<SessionKey>()
+        .cloned()
+        .expect("SessionKey is inserted when the client is built")
+}
+
+/// Redis connection stored in the data of the client, `None` when Redis is not configured
+/// Cloning it is cheap and every clone shares the connection
+pub async fn redis(ctx: &Context) -> Option<MultiplexedConnection> {
+    ctx.data.read().await.get::<RedisKey>().cloned()
+}
+
+/// Settings of guilds stored in the data of the client
+pub async fn guild_settings_cache(ctx: &Context) -> Arc<GuildSettingsCache> {
+    ctx.data
+        .read()
+        .await
+        .get::<GuildSettingsKey>()
+        .cloned()
+        .expect("GuildSettingsKey is inserted when the client is built")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use thrpg_database::migration::{Migrator, MigratorTrait};
+    use thrpg_database::repository::UserRepository;
+
+    #[tokio::test]
+    async fn in_memory_database_keeps_its_pool() {
+        let database = SharedDatabase::connect("sqlite::memory:").await.unwrap();
+        Migrator::up(&database.connection().await, None).await.unwrap();
+
+        assert!(database.reconnect().await.is_err());
+        // The migrated tables are still there
+        assert!(database.connection().await.find_user("user").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,97 @@
+use crate::play::embed_message;
+use anyhow::Context;
+use battle_machine::{
+    equipment::EquipmentConfig,
+    item::{ItemConfig, ItemKind, ShopCatalog, ShopEntry},
+};
+use serenity::client;
+use serenity::framework::standard::CommandResult;
+use serenity::model::prelude::ChannelId;
+use serenity::model::user::User;
+use thrpg_database::repository::{
+    InventoryRepository, RepositoryTransaction, TransactionRepository, UserRepository,
+};
+
+/// Most items bought at once
+pub const MAX_QUANTITY: i64 = 99;
+
+/// `/shop`
+/// Without an item, show the catalog
+pub async fn shop<R: TransactionRepository + UserRepository>(
+    ctx: client::Context,
+    channel_id: ChannelId,
+    user: User,
+    buy: Option<String>,
+    quantity: Option<i64>,
+    repo: R,
+) -> CommandResult {
+    let catalog = ShopCatalog::load("shop/").await?;
+    let userdata = match repo.find_user(&user.id.to_string()).await? {
+        Some(ud) => ud,
+        None => {
+            embed_message(&ctx, channel_id, "まだセーブデータがありません", "").await?;
+            return Ok(());
+        }
+    };
+
+    let entry = match buy.as_ref().and_then(|id| catalog.find(id)) {
+        Some(entry) => entry,
+        None => {
+            let mut lines = Vec::new();
+            for entry in catalog.entry.iter() {
+                lines.push(format!(
+                    "`{}` {} {}G",
+                    entry.id,
+                    entry_name(entry).await,
+                    entry.price
+                ));
+            }
+            embed_message(
+                &ctx,
+                channel_id,
+                format!("ショップ (所持金 {}G)", userdata.gold),
+                lines.join("\n"),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let quantity = quantity.unwrap_or(1).clamp(1, MAX_QUANTITY);
+    let total = entry.price as i64 * quantity;
+    let txn = repo.begin_transaction().await?;
+    // Fails without spending if another purchase used the gold first
+    if !txn.spend_gold(&userdata.user_id, total).await? {
+        txn.rollback().await?;
+        embed_message(&ctx, channel_id, format!("{}Gが足りません", total), "").await?;
+        return Ok(());
+    }
+    txn.add_item(&userdata.user_id, &entry.id, entry.kind.as_str(), quantity)
+        .await?;
+    // Read in the transaction, so it is the balance right after this purchase
+    let balance = txn
+        .find_user(&userdata.user_id)
+        .await?
+        .context("no save data")?
+        .gold;
+    txn.commit().await?;
+
+    embed_message(
+        &ctx,
+        channel_id,
+        format!("{}を{}個買いました", entry_name(entry).await, quantity),
+        format!("残り {}G", balance),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn entry_name(entry: &ShopEntry) -> String {
+    let name = match entry.kind {
+        ItemKind::Item => ItemConfig::from_file_name(&entry.id).await.map(|i| i.name),
+        ItemKind::Equipment => EquipmentConfig::from_file_name(&entry.id)
+            .await
+            .map(|e| e.name),
+    };
+    name.unwrap_or(entry.id.clone())
+}
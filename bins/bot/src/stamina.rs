@@ -0,0 +1,164 @@
+use crate::play::embed_message;
+use crate::settings::guild_settings;
+use crate::shared::config;
+use battle_machine::{
+    item::{ItemConfig, ItemEffect},
+    mode::PlayMode,
+    stamina::{Stamina, StaminaRule},
+};
+use chrono::Local;
+use serenity::client;
+use serenity::framework::standard::CommandResult;
+use serenity::model::prelude::{ChannelId, GuildId};
+use serenity::model::user::User;
+use setting_config::StaminaConfig;
+use thrpg_database::{
+    repository::{
+        InventoryRepository, RepositoryTransaction, TransactionRepository, UserRepository,
+    },
+    userdata::Model as UserDataModel,
+};
+
+/// Attempts when another command changed the stamina at the same time
+const STAMINA_RETRY: usize = 3;
+
+fn stamina_rule(config: &StaminaConfig) -> StaminaRule {
+    StaminaRule {
+        max: config.max(),
+        regen_minutes: config.regen_minutes(),
+    }
+}
+
+fn stored_stamina(userdata: &UserDataModel) -> Stamina {
+    Stamina {
+        points: userdata.stamina.max(0) as u32,
+        updated_at: userdata.stamina_updated_at,
+    }
+}
+
+/// Take the cost of the mode from the user
+/// Returns false, after telling the user, when the stamina is not enough
+pub async fn consume_stamina<R: UserRepository>(
+    ctx: &client::Context,
+    channel_id: ChannelId,
+    repo: &R,
+    guild_id: Option<GuildId>,
+    user_id: &str,
+    mode: &PlayMode,
+) -> anyhow::Result<bool> {
+    let config = config(ctx).await;
+    let stamina_config = match config.stamina_config() {
+        Some(c) if guild_settings(ctx, guild_id).await?.stamina_enabled => c,
+        _ => return Ok(true),
+    };
+    let cost = stamina_config.cost(mode.kind());
+    if cost == 0 {
+        return Ok(true);
+    }
+    let rule = stamina_rule(stamina_config);
+
+    for _ in 0..STAMINA_RETRY {
+        let userdata = match repo.find_user(user_id).await? {
+            Some(ud) => ud,
+            None => return Ok(true),
+        };
+        let stored = stored_stamina(&userdata);
+        let now = Local::now().naive_local();
+        let after = match stored.spend(cost, now, &rule) {
+            Some(after) => after,
+            None => {
+                let current = stored.at(now, &rule);
+                embed_message(
+                    ctx,
+                    channel_id,
+                    format!("スタミナが足りません ({}/{})", current.points, cost),
+                    format!("全回復: {}", current.full_at(&rule).format("%H:%M")),
+                )
+                .await?;
+                return Ok(false);
+            }
+        };
+        if repo
+            .update_stamina(
+                user_id,
+                (userdata.stamina, userdata.stamina_updated_at),
+                (after.points as i64, after.updated_at),
+            )
+            .await?
+        {
+            return Ok(true);
+        }
+    }
+    Err(anyhow::anyhow!("stamina of {} is busy", user_id))
+}
+
+/// `/stamina`
+/// With an item, use it to refill
+pub async fn stamina<R: TransactionRepository + UserRepository>(
+    ctx: client::Context,
+    channel_id: ChannelId,
+    user: User,
+    item: Option<String>,
+    repo: R,
+) -> CommandResult {
+    let config = config(&ctx).await;
+    let stamina_config = match config.stamina_config() {
+        Some(c) => c,
+        None => {
+            embed_message(&ctx, channel_id, "スタミナは無効です", "").await?;
+            return Ok(());
+        }
+    };
+    let rule = stamina_rule(stamina_config);
+    let userdata = match repo.find_user(&user.id.to_string()).await? {
+        Some(ud) => ud,
+        None => {
+            embed_message(&ctx, channel_id, "まだセーブデータがありません", "").await?;
+            return Ok(());
+        }
+    };
+    let now = Local::now().naive_local();
+    let mut current = stored_stamina(&userdata).at(now, &rule);
+
+    if let Some(item_id) = item {
+        let amount = match ItemConfig::from_file_name(&item_id).await.map(|i| i.effect) {
+            Ok(ItemEffect::RestoreStamina { stamina }) => stamina,
+            _ => {
+                embed_message(&ctx, channel_id, "スタミナを回復するアイテムではありません", "").await?;
+                return Ok(());
+            }
+        };
+        let refilled = current.refill(amount, now, &rule);
+        let txn = repo.begin_transaction().await?;
+        // The item is kept when the stamina changed in between
+        if !txn.use_item(&userdata.user_id, &item_id, 1).await?
+            || !txn
+                .update_stamina(
+                    &userdata.user_id,
+                    (userdata.stamina, userdata.stamina_updated_at),
+                    (refilled.points as i64, refilled.updated_at),
+                )
+                .await?
+        {
+            txn.rollback().await?;
+            embed_message(&ctx, channel_id, "アイテムを使えませんでした", "").await?;
+            return Ok(());
+        }
+        txn.commit().await?;
+        current = refilled;
+    }
+
+    let description = if current.points >= rule.max {
+        "全回復しています".to_string()
+    } else {
+        format!("全回復: {}", current.full_at(&rule).format("%m/%d %H:%M"))
+    };
+    embed_message(
+        &ctx,
+        channel_id,
+        format!("スタミナ {}/{}", current.points, rule.max),
+        description,
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use crate::achievement::achievement_config;
+use crate::equipment::equipped_gear;
+use anyhow::Context;
+use battle_machine::{
+    chara::CharaConfig,
+    equipment::{gear_bonus, stat_bonus},
+};
+use serenity::client;
+use serenity::framework::standard::CommandResult;
+use serenity::model::prelude::ChannelId;
+use serenity::model::user::User;
+use thrpg_database::repository::{ProgressRepository, RosterRepository, UserRepository};
+
+/// `/status`
+/// Base stats, gear bonuses and bond bonuses are shown separately
+pub async fn status<R: UserRepository + RosterRepository + ProgressRepository>(
+    ctx: client::Context,
+    channel_id: ChannelId,
+    user: User,
+    repo: R,
+) -> CommandResult {
+    let userdata = match repo.find_user(&user.id.to_string()).await? {
+        Some(ud) => ud,
+        None => {
+            channel_id
+                .send_message(&ctx.http, |f| f.embed(|e| e.title("まだセーブデータがありません")))
+                .await
+                .context("埋め込みの作成に失敗しました")?;
+            return Ok(());
+        }
+    };
+    let roster = repo.find_or_unlock(&userdata.user_id, &userdata.player).await?;
+    let chara = CharaConfig::from_file_name(&userdata.player).await?;
+    let gear = equipped_gear(&repo, &userdata.user_id, &userdata.player).await?;
+    let base = chara.charabase.with_level(roster.level as i16);
+    let bonus = gear_bonus(&base, &gear);
+    let affinity = roster.affinity.max(0) as u32;
+    let tiers = chara.bond_tiers(affinity);
+    let bond = stat_bonus(&base, tiers.iter().flat_map(|t| t.bonus.iter()));
+
+    let stats = [
+        ("攻撃", base.power, bonus.power, bond.power),
+        ("防御", base.guard, bonus.guard, bond.guard),
+        ("素早さ", base.speed, bonus.speed, bond.speed),
+        ("HP", base.hp, bonus.hp, bond.hp),
+        ("MP", base.mp, bonus.mp, bond.mp),
+    ];
+    let mut bond_lines = vec![match chara.next_bond_tier(affinity) {
+        Some(next) => format!("{} / {} (次: {})", affinity, next.affinity, next.name),
+        None => format!("{}", affinity),
+    }];
+    bond_lines.extend(tiers.iter().map(|t| match &t.special {
+        Some(special) => format!("{} (必殺技: {})", t.name, special.name),
+        None => t.name.clone(),
+    }));
+    let achievements = achievement_config().await?;
+    let achievement_names: Vec<String> = repo
+        .unlocked_achievements(&userdata.user_id)
+        .await?
+        .iter()
+        .map(|a| {
+            achievements
+                .find(&a.achievement_id)
+                .map_or(a.achievement_id.clone(), |found| found.name.clone())
+        })
+        .collect();
+    let gear_names: Vec<String> = gear
+        .iter()
+        .map(|g| format!("{}: {}", g.slot.as_str(), g.name))
+        .collect();
+
+    channel_id
+        .send_message(&ctx.http, |f| {
+            f.embed(|e| {
+                e.title(format!("{}のステータス", user.name))
+                    .description(format!(
+                        "{} Lv.{} (exp {})\n所持金 {}G",
+                        chara.meta.name, roster.level, roster.exp, userdata.gold
+                    ))
+                    .field(
+                        "基本ステータス",
+                        stats
+                            .iter()
+                            .map(|(name, base, _, _)| format!("{} {}", name, base))
+                            .collect::<Vec<String>>()
+                            .join("\n"),
+                        true,
+                    )
+                    .field(
+                        "装備補正",
+                        stats
+                            .iter()
+                            .map(|(name, _, bonus, _)| format!("{} {:+}", name, bonus))
+                            .collect::<Vec<String>>()
+                            .join("\n"),
+                        true,
+                    )
+                    .field(
+                        "絆補正",
+                        stats
+                            .iter()
+                            .map(|(name, _, _, bond)| format!("{} {:+}", name, bond))
+                            .collect::<Vec<String>>()
+                            .join("\n"),
+                        true,
+                    )
+                    .field("絆", bond_lines.join("\n"), false)
+                    .field(
+                        "装備",
+                        if gear_names.is_empty() {
+                            "なし".to_string()
+                        } else {
+                            gear_names.join("\n")
+                        },
+                        false,
+                    )
+                    .field(
+                        "実績",
+                        if achievement_names.is_empty() {
+                            "なし".to_string()
+                        } else {
+                            achievement_names.join("\n")
+                        },
+                        false,
+                    )
+            })
+        })
+        .await
+        .context("埋め込みの作成に失敗しました")?;
+    Ok(())
+}
@@ -0,0 +1,343 @@
+use crate::play::{duel, embed_message, integer_option, string_option};
+use anyhow::Context;
+use battle_machine::tournament::{Bracket, BracketMatch, TournamentFormat};
+use chrono::{Duration, Local};
+use rand::seq::SliceRandom;
+use sea_orm::prelude::Uuid;
+use serenity::client;
+use serenity::framework::standard::CommandResult;
+use serenity::model::prelude::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::prelude::{ChannelId, UserId};
+use thrpg_database::{
+    repository::{Repository, TournamentRepository, TransactionRepository},
+    tournament::{Model as TournamentModel, STATUS_FINISHED, STATUS_REGISTRATION, STATUS_RUNNING},
+};
+
+/// Default time to play a match before it is decided as a no-show
+const DEFAULT_MATCH_TIMEOUT_HOURS: i64 = 24;
+
+/// `/tournament` subcommands
+pub async fn tournament<R: Repository + TransactionRepository + Clone>(
+    ctx: client::Context,
+    command: &ApplicationCommandInteraction,
+    repo: R,
+) -> CommandResult {
+    let channel_id = command.channel_id;
+    let (subcommand, guild_id) = match (command.data.options.first(), command.guild_id) {
+        (Some(s), Some(g)) => (s, g.to_string()),
+        _ => {
+            embed_message(&ctx, channel_id, "トーナメントはサーバー内で使って下さい", "").await?;
+            return Ok(());
+        }
+    };
+    let is_admin = command
+        .member
+        .as_ref()
+        .and_then(|member| member.permissions)
+        .is_some_and(|permissions| permissions.administrator());
+    let user_id = command.user.id.to_string();
+    let now = Local::now().naive_local();
+    let current = repo.current_tournament(&guild_id).await?;
+
+    match (subcommand.name.as_str(), current) {
+        ("create", _) | ("start", _) if !is_admin => {
+            embed_message(&ctx, channel_id, "管理者のみ実行できます", "").await?;
+        }
+        ("create", Some(_)) => {
+            embed_message(&ctx, channel_id, "開催中のトーナメントがあります", "").await?;
+        }
+        ("create", None) => {
+            let name =
+                string_option(&subcommand.options, "name").unwrap_or("THRPG Cup".to_string());
+            let format = TournamentFormat::try_from_value(
+                &string_option(&subcommand.options, "format")
+                    .unwrap_or(TournamentFormat::SingleElimination.as_str().to_string()),
+            )?;
+            let timeout_hours = integer_option(&subcommand.options, "timeout_hours")
+                .unwrap_or(DEFAULT_MATCH_TIMEOUT_HOURS);
+            repo.save_tournament(TournamentModel {
+                tournament_uuid: Uuid::new_v4(),
+                guild_id,
+                name: name.clone(),
+                format: format.as_str().to_string(),
+                status: STATUS_REGISTRATION.to_string(),
+                participants: serde_json::json!([]),
+                bracket: None,
+                match_timeout_minutes: timeout_hours * 60,
+                created_at: now,
+            })
+            .await?;
+            embed_message(
+                &ctx,
+                channel_id,
+                format!("{}の参加受付を始めました `/tournament join`", name),
+                "",
+            )
+            .await?;
+        }
+        ("join", Some(t)) if t.status == STATUS_REGISTRATION => {
+            let joined = repo
+                .update_tournament(
+                    t.tournament_uuid,
+                    Box::new(move |t| {
+                        let mut participants = participants_of(t).ok()?;
+                        if t.status != STATUS_REGISTRATION || participants.contains(&user_id) {
+                            return None;
+                        }
+                        participants.push(user_id);
+                        Some(TournamentModel {
+                            participants: serde_json::to_value(participants).ok()?,
+                            ..t.clone()
+                        })
+                    }),
+                )
+                .await?;
+            match joined {
+                Some(t) => {
+                    let count = participants_of(&t)?.len();
+                    embed_message(&ctx, channel_id, format!("参加しました ({}人目)", count), "").await?;
+                }
+                None => {
+                    embed_message(&ctx, channel_id, "既に参加しているか、参加受付が終わっています", "").await?;
+                }
+            }
+        }
+        ("start", Some(t)) if t.status == STATUS_REGISTRATION => {
+            if participants_of(&t)?.len() < 2 {
+                embed_message(&ctx, channel_id, "参加者が2人以上必要です", "").await?;
+                return Ok(());
+            }
+            let format = TournamentFormat::try_from_value(&t.format)?;
+            let timeout = Duration::minutes(t.match_timeout_minutes);
+            // The participants are read under the lock, so nobody joining meanwhile is left out
+            let started = repo
+                .update_tournament(
+                    t.tournament_uuid,
+                    Box::new(move |t| {
+                        if t.status != STATUS_REGISTRATION {
+                            return None;
+                        }
+                        let mut participants = participants_of(t).ok()?;
+                        participants.shuffle(&mut rand::thread_rng());
+                        let bracket = Bracket::new(format, participants, timeout, now).ok()?;
+                        Some(TournamentModel {
+                            status: STATUS_RUNNING.to_string(),
+                            bracket: Some(serde_json::to_value(&bracket).ok()?),
+                            ..t.clone()
+                        })
+                    }),
+                )
+                .await?;
+            match started {
+                Some(t) => bracket_embed(&ctx, channel_id, &t.name, &bracket_of(&t)?).await?,
+                None => embed_message(&ctx, channel_id, "トーナメントは既に始まっています", "").await?,
+            }
+        }
+        ("bracket", Some(t)) if t.status == STATUS_RUNNING => {
+            let (t, bracket) = expire(&ctx, channel_id, t, &repo).await?;
+            bracket_embed(&ctx, channel_id, &t.name, &bracket).await?;
+        }
+        ("play", Some(t)) if t.status == STATUS_RUNNING => {
+            let (t, bracket) = expire(&ctx, channel_id, t, &repo).await?;
+            let index = match bracket.match_of(&user_id) {
+                Some(i) => i,
+                None => {
+                    embed_message(&ctx, channel_id, "あなたの試合はありません", "").await?;
+                    return Ok(());
+                }
+            };
+            let opponent_id: u64 = bracket.matches()[index]
+                .opponent_of(&user_id)
+                .context("no opponent")?
+                .parse()?;
+            let checked_in = user_id.clone();
+            update_bracket(&repo, t.tournament_uuid, move |bracket| {
+                bracket.check_in(index, &checked_in);
+                true
+            })
+            .await?;
+
+            let opponent = UserId(opponent_id).to_user(&ctx.http).await?;
+            let winner = duel(
+                ctx.clone(),
+                channel_id,
+                command.guild_id,
+                command.user.clone(),
+                opponent,
+                repo.clone(),
+            )
+            .await?;
+
+            if let Some(winner) = winner {
+                let winner = winner.to_string();
+                let now = Local::now().naive_local();
+                // Reported under the lock, so reports of other matches during this battle are kept
+                let reported = update_bracket(&repo, t.tournament_uuid, move |bracket| {
+                    bracket.report(index, &winner, now).unwrap_or(false)
+                })
+                .await?;
+                match reported {
+                    Some((t, bracket)) => announce_champion(&ctx, channel_id, &t, &bracket).await?,
+                    None => {
+                        embed_message(
+                            &ctx,
+                            channel_id,
+                            "この試合は期限切れで既に決着しているため、結果は記録されませんでした",
+                            "",
+                        )
+                        .await?;
+                    }
+                }
+            }
+        }
+        ("bracket", Some(t)) | ("play", Some(t)) => {
+            embed_message(&ctx, channel_id, format!("{}は参加受付中です", t.name), "").await?;
+        }
+        (_, Some(_)) => {
+            embed_message(&ctx, channel_id, "トーナメントは既に始まっています", "").await?;
+        }
+        (_, None) => {
+            embed_message(&ctx, channel_id, "開催中のトーナメントはありません", "").await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Decide the matches whose deadline has passed
+async fn expire<R: TournamentRepository>(
+    ctx: &client::Context,
+    channel_id: ChannelId,
+    t: TournamentModel,
+    repo: &R,
+) -> anyhow::Result<(TournamentModel, Bracket)> {
+    let now = Local::now().naive_local();
+    let expired =
+        update_bracket(repo, t.tournament_uuid, move |bracket| !bracket.expire(now).is_empty())
+            .await?;
+    match expired {
+        Some((t, bracket)) => {
+            announce_champion(ctx, channel_id, &t, &bracket).await?;
+            Ok((t, bracket))
+        }
+        None => {
+            let bracket = bracket_of(&t)?;
+            Ok((t, bracket))
+        }
+    }
+}
+
+/// Change the bracket of a running tournament under its lock
+/// The tournament is finished once the bracket has a champion
+/// `change` returns `false` to leave the tournament as it is
+async fn update_bracket<R, F>(
+    repo: &R,
+    tournament_uuid: Uuid,
+    change: F,
+) -> anyhow::Result<Option<(TournamentModel, Bracket)>>
+where
+    R: TournamentRepository,
+    F: FnOnce(&mut Bracket) -> bool + Send + 'static,
+{
+    let updated = repo
+        .update_tournament(
+            tournament_uuid,
+            Box::new(move |t| {
+                let mut bracket = bracket_of(t).ok()?;
+                if t.status != STATUS_RUNNING || !change(&mut bracket) {
+                    return None;
+                }
+                let status = match bracket.champion() {
+                    Some(_) => STATUS_FINISHED,
+                    None => STATUS_RUNNING,
+                };
+                Some(TournamentModel {
+                    status: status.to_string(),
+                    bracket: Some(serde_json::to_value(&bracket).ok()?),
+                    ..t.clone()
+                })
+            }),
+        )
+        .await?;
+    match updated {
+        Some(t) => {
+            let bracket = bracket_of(&t)?;
+            Ok(Some((t, bracket)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Show the final bracket and the champion when the tournament is finished
+async fn announce_champion(
+    ctx: &client::Context,
+    channel_id: ChannelId,
+    t: &TournamentModel,
+    bracket: &Bracket,
+) -> anyhow::Result<()> {
+    if let Some(champion) = bracket.champion() {
+        bracket_embed(ctx, channel_id, &t.name, bracket).await?;
+        embed_message(ctx, channel_id, format!("{}の優勝者は<@{}>です！", t.name, champion), "").await?;
+    }
+    Ok(())
+}
+
+fn participants_of(t: &TournamentModel) -> anyhow::Result<Vec<String>> {
+    Ok(serde_json::from_value(t.participants.clone())?)
+}
+
+fn bracket_of(t: &TournamentModel) -> anyhow::Result<Bracket> {
+    Ok(serde_json::from_value(t.bracket.clone().context("no bracket")?)?)
+}
+
+async fn bracket_embed(
+    ctx: &client::Context,
+    channel_id: ChannelId,
+    name: &str,
+    bracket: &Bracket,
+) -> anyhow::Result<()> {
+    channel_id
+        .send_message(&ctx.http, |f| {
+            f.embed(|e| {
+                e.title(name).description(match bracket.format() {
+                    TournamentFormat::SingleElimination => "トーナメント",
+                    TournamentFormat::RoundRobin => "総当たり戦",
+                });
+                for round in 0..bracket.rounds() {
+                    let lines: Vec<String> = bracket
+                        .matches()
+                        .iter()
+                        .filter(|m| m.round == round)
+                        .map(match_line)
+                        .collect();
+                    e.field(format!("{}回戦", round + 1), lines.join("\n"), false);
+                }
+                if bracket.format() == TournamentFormat::RoundRobin {
+                    let standings: Vec<String> = bracket
+                        .standings()
+                        .iter()
+                        .map(|(user_id, wins)| format!("<@{}> {}勝", user_id, wins))
+                        .collect();
+                    e.field("順位", standings.join("\n"), false);
+                }
+                e
+            })
+        })
+        .await
+        .context("埋め込みの作成に失敗しました")?;
+    Ok(())
+}
+
+fn match_line(m: &BracketMatch) -> String {
+    let player = |p: &Option<String>| match p {
+        Some(user_id) => format!("<@{}>", user_id),
+        None if m.round == 0 => "不戦勝".to_string(),
+        None => "未定".to_string(),
+    };
+    let result = match (&m.winner, m.finished) {
+        (Some(winner), true) => format!(" → 🏆<@{}>", winner),
+        (None, true) => " → 不成立".to_string(),
+        _ => String::new(),
+    };
+    format!("{} vs {}{}", player(&m.players[0]), player(&m.players[1]), result)
+}
@@ -0,0 +1,198 @@
+use anyhow::Context;
+use battle_machine::{
+    event::ProgressEvent,
+    unlock::{GachaPool, UnlockConfig},
+};
+use chrono::Local;
+use extension::store::ExtensionStore;
+use serenity::client;
+use serenity::framework::standard::CommandResult;
+use serenity::model::prelude::ChannelId;
+use serenity::model::user::User;
+use thrpg_database::{
+    pull_history::Model as PullHistoryModel,
+    repository::{RepositoryTransaction, RosterRepository, TransactionRepository, UserRepository},
+};
+
+/// Unlock settings of `unlock/` and Contents extensions
+pub async fn unlock_config() -> anyhow::Result<UnlockConfig> {
+    let extension_dirs = ExtensionStore::extension_files()
+        .await
+        .map(|store| store.contents_dirs())
+        .unwrap_or_default();
+    UnlockConfig::load_with_extensions("unlock/", extension_dirs).await
+}
+
+/// Unlock the characters whose milestone is reached and announce them
+pub async fn unlock_progress<R: RosterRepository>(
+    ctx: &client::Context,
+    channel_id: ChannelId,
+    repo: &R,
+    user_id: &str,
+    events: &[ProgressEvent<'_>],
+) -> anyhow::Result<()> {
+    let config = unlock_config().await?;
+    for chara in events.iter().flat_map(|event| config.unlocked_by(event)) {
+        if repo.unlock(user_id, chara).await? {
+            channel_id
+                .send_message(&ctx.http, |f| {
+                    f.embed(|e| {
+                        e.title(format!("{}が仲間になった！", chara))
+                            .description("`/chara`で操作するキャラクターを変更できます")
+                    })
+                })
+                .await
+                .context("埋め込みの作成に失敗しました")?;
+        }
+    }
+    Ok(())
+}
+
+/// `/gacha`
+/// Without a pool, show the pools and their rates
+pub async fn gacha<R: TransactionRepository + UserRepository>(
+    ctx: client::Context,
+    channel_id: ChannelId,
+    user: User,
+    pool_id: Option<String>,
+    repo: R,
+) -> CommandResult {
+    let config = unlock_config().await?;
+    let pool = match pool_id.as_ref().and_then(|id| config.pool(id)) {
+        Some(pool) => pool,
+        None => {
+            channel_id
+                .send_message(&ctx.http, |f| {
+                    f.embed(|e| {
+                        e.title("排出率");
+                        for pool in config.pool.iter() {
+                            let rates: Vec<String> = pool
+                                .rates()
+                                .iter()
+                                .map(|(chara, rate)| format!("{} {:.1}%", chara, rate * 100.0))
+                                .collect();
+                            e.field(
+                                format!("{} (`{}`) {}G", pool.name, pool.id, pool.cost),
+                                rates.join("\n"),
+                                false,
+                            );
+                        }
+                        e
+                    })
+                })
+                .await
+                .context("埋め込みの作成に失敗しました")?;
+            return Ok(());
+        }
+    };
+
+    let user_id = user.id.to_string();
+    if repo.find_user(&user_id).await?.is_none() {
+        channel_id
+            .send_message(&ctx.http, |f| f.embed(|e| e.title("まだセーブデータがありません")))
+            .await
+            .context("埋め込みの作成に失敗しました")?;
+        return Ok(());
+    }
+
+    let entry = pool
+        .pull(&mut rand::thread_rng())
+        .context("empty pool")?
+        .clone();
+    let new = match pull(&repo, &user_id, pool, &entry.chara).await? {
+        Some(new) => new,
+        None => {
+            channel_id
+                .send_message(&ctx.http, |f| {
+                    f.embed(|e| e.title(format!("{}Gが足りません", pool.cost)))
+                })
+                .await
+                .context("埋め込みの作成に失敗しました")?;
+            return Ok(());
+        }
+    };
+
+    channel_id
+        .send_message(&ctx.http, |f| {
+            f.embed(|e| {
+                e.title(format!("{}が出た！", entry.chara))
+                    .description(if new {
+                        "新しく仲間になりました"
+                    } else {
+                        "既に仲間になっています"
+                    })
+            })
+        })
+        .await
+        .context("埋め込みの作成に失敗しました")?;
+    Ok(())
+}
+
+/// Pay for the pull and add the character to the roster
+/// Returns whether the character is new, or `None` if the user can't pay
+async fn pull<R: TransactionRepository>(
+    repo: &R,
+    user_id: &str,
+    pool: &GachaPool,
+    chara: &str,
+) -> anyhow::Result<Option<bool>> {
+    let txn = repo.begin_transaction().await?;
+    if !txn.spend_gold(user_id, pool.cost as i64).await? {
+        txn.rollback().await?;
+        return Ok(None);
+    }
+    let new = txn.unlock(user_id, chara).await?;
+    txn.record_pull(PullHistoryModel {
+        id: 0,
+        user_id: user_id.to_string(),
+        pool_id: pool.id.clone(),
+        chara: chara.to_string(),
+        cost: pool.cost as i64,
+        duplicate: !new,
+        pulled_at: Local::now().naive_local(),
+    })
+    .await?;
+    txn.commit().await?;
+    Ok(Some(new))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battle_machine::unlock::PoolEntry;
+    use thrpg_database::repository::{test_user, MemoryRepository};
+    use thrpg_database::userdata::Model as UserDataModel;
+
+    #[tokio::test]
+    async fn pull_costs_gold_and_records_duplicates() {
+        let repo = MemoryRepository::new();
+        repo.insert_user(UserDataModel {
+            gold: 150,
+            ..test_user("user")
+        })
+        .await
+        .unwrap();
+        let pool = GachaPool {
+            id: "standard".to_string(),
+            name: "通常".to_string(),
+            cost: 100,
+            entries: vec![PoolEntry {
+                chara: "marisa".to_string(),
+                weight: 1,
+            }],
+        };
+
+        assert_eq!(pull(&repo, "user", &pool, "marisa").await.unwrap(), Some(true));
+        assert!(repo.find_roster("user", "marisa").await.unwrap().is_some());
+        assert_eq!(repo.find_user("user").await.unwrap().unwrap().gold, 50);
+
+        // Not enough gold, nothing changes
+        assert_eq!(pull(&repo, "user", &pool, "marisa").await.unwrap(), None);
+        assert_eq!(repo.find_user("user").await.unwrap().unwrap().gold, 50);
+
+        repo.add_gold("user", 50).await.unwrap();
+        assert_eq!(pull(&repo, "user", &pool, "marisa").await.unwrap(), Some(false));
+        let duplicates: Vec<bool> = repo.pulls().iter().map(|pull| pull.duplicate).collect();
+        assert_eq!(duplicates, [false, true]);
+    }
+}
@@ -1,10 +1,18 @@
-use axum::{routing::get, Router};
+use axum::{extract::Extension, routing::get, Router};
 
-use webapi_scheme::owner;
+use setting_config::config_parse_toml;
+use thrpg_database::database_connect::connect;
+use webapi_scheme::{owner, user_stats};
 
 #[tokio::main]
 async fn main() {
-    let app = Router::new().route("/", get(owner));
+    let db = connect(config_parse_toml().await.postgresql_config().db_address.as_str())
+        .await
+        .unwrap();
+    let app = Router::new()
+        .route("/", get(owner))
+        .route("/stats/:user_id", get(user_stats))
+        .layer(Extension(db));
 
     let address = std::net::SocketAddr::from(([127, 0, 0, 1], 3000));
 
@@ -0,0 +1 @@
+appear-enemy = {$name} appeared!
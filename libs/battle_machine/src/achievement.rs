@@ -0,0 +1,213 @@
+use crate::chara::LevelupExpType;
+use crate::event::ProgressEvent;
+use crate::rpg_core::StatusCharaType;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Achievements read from `achievement/*.toml` and the `achievement.toml` of Contents extensions
+/// example:
+/// ```toml
+/// [[achievement]]
+/// id = "flawless_remilia"
+/// name = "紅い悪魔も怖くない"
+/// description = "ダメージを受けずにレミリアを倒す"
+/// condition = { DefeatWithoutDamage = { enemy = "レミリア・スカーレット" } }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AchievementConfig {
+    #[serde(default)]
+    pub achievement: Vec<Achievement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Achievement {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub condition: AchievementCondition,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AchievementCondition {
+    /// Defeat enemies `count` times in total
+    WinCount { count: u32 },
+    /// Defeat the enemy, or any enemy, without taking damage
+    DefeatWithoutDamage { enemy: Option<String> },
+    /// A character, with the exp curve if set, reaches the level
+    ReachLevel {
+        level: u32,
+        levelup_exp: Option<LevelupExpType>,
+    },
+}
+
+impl AchievementConfig {
+    /// Read and merge every file
+    pub async fn load(paths: Vec<PathBuf>) -> anyhow::Result<Self> {
+        let mut config = Self::default();
+        for path in paths {
+            let other: Self = thrpg_utils::read_to_toml(path).await?;
+            config.register(other.achievement);
+        }
+        Ok(config)
+    }
+
+    /// Files in `achievement/` and `achievement.toml` in the directories of Contents extensions
+    pub async fn load_with_extensions<P: AsRef<Path>>(
+        dir: P,
+        extension_dirs: Vec<PathBuf>,
+    ) -> anyhow::Result<Self> {
+        let mut paths = thrpg_utils::dir_files(dir.as_ref()).await.unwrap_or_default();
+        paths.extend(
+            extension_dirs
+                .into_iter()
+                .map(|dir| dir.join("achievement.toml"))
+                .filter(|path| path.is_file()),
+        );
+        Self::load(paths).await
+    }
+
+    /// Add achievements, an achievement with the same id is replaced
+    pub fn register(&mut self, achievements: Vec<Achievement>) -> &mut Self {
+        for achievement in achievements {
+            self.achievement.retain(|a| a.id != achievement.id);
+            self.achievement.push(achievement);
+        }
+        self
+    }
+
+    pub fn find(&self, id: &str) -> Option<&Achievement> {
+        self.achievement.iter().find(|a| a.id == id)
+    }
+}
+
+impl AchievementCondition {
+    /// Progress needed to unlock
+    pub fn goal(&self) -> u32 {
+        match self {
+            AchievementCondition::WinCount { count } => *count,
+            _ => 1,
+        }
+    }
+
+    /// Progress is added up over events
+    /// Otherwise the best progress is kept
+    pub fn is_cumulative(&self) -> bool {
+        matches!(self, AchievementCondition::WinCount { .. })
+    }
+
+    /// Progress made by the event
+    pub fn progress(&self, event: &ProgressEvent) -> u32 {
+        match (self, event) {
+            (AchievementCondition::WinCount { .. }, ProgressEvent::EnemyDefeated { .. }) => 1,
+            (
+                AchievementCondition::DefeatWithoutDamage { enemy },
+                ProgressEvent::EnemyDefeated { battle },
+            ) => {
+                let target = enemy
+                    .as_ref()
+                    .is_none_or(|e| e == &battle.enemy().meta.name);
+                (target && battle.damage_taken(StatusCharaType::Player) == 0) as u32
+            }
+            (
+                AchievementCondition::ReachLevel { level, levelup_exp },
+                ProgressEvent::LevelReached {
+                    levelup_exp: l_exp,
+                    level: l,
+                    ..
+                },
+            ) => {
+                let curve = levelup_exp.as_ref().is_none_or(|e| e == *l_exp);
+                (curve && l >= level) as u32
+            }
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::PlayMode;
+    use crate::rpg_core::{tests::chara, BattleData};
+    use uuid::Uuid;
+
+    fn battle(enemy: &str) -> BattleData {
+        BattleData::new(
+            Uuid::new_v4(),
+            chara("reimu", 100),
+            chara(enemy, 100),
+            PlayMode::Simple,
+            chrono::Local::now().naive_local(),
+            0,
+        )
+    }
+
+    fn achievement(id: &str, condition: AchievementCondition) -> Achievement {
+        Achievement {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: String::new(),
+            condition,
+        }
+    }
+
+    #[test]
+    fn wins_add_up_to_the_count() {
+        let condition = AchievementCondition::WinCount { count: 3 };
+        let battle = battle("チルノ");
+        assert_eq!(condition.goal(), 3);
+        assert!(condition.is_cumulative());
+        assert_eq!(condition.progress(&ProgressEvent::EnemyDefeated { battle: &battle }), 1);
+        assert_eq!(condition.progress(&ProgressEvent::ItemUsed { item: "potion" }), 0);
+    }
+
+    #[test]
+    fn flawless_win_takes_no_damage_from_the_enemy() {
+        let condition = AchievementCondition::DefeatWithoutDamage {
+            enemy: Some("レミリア・スカーレット".to_string()),
+        };
+        assert!(!condition.is_cumulative());
+        let flawless = battle("レミリア・スカーレット");
+        let mut damaged = battle("レミリア・スカーレット");
+        damaged.calculate_enemy_damage();
+        let other = battle("チルノ");
+
+        assert_eq!(condition.progress(&ProgressEvent::EnemyDefeated { battle: &flawless }), 1);
+        assert_eq!(condition.progress(&ProgressEvent::EnemyDefeated { battle: &damaged }), 0);
+        assert_eq!(condition.progress(&ProgressEvent::EnemyDefeated { battle: &other }), 0);
+        let any = AchievementCondition::DefeatWithoutDamage { enemy: None };
+        assert_eq!(any.progress(&ProgressEvent::EnemyDefeated { battle: &other }), 1);
+    }
+
+    #[test]
+    fn level_counts_only_on_the_exp_curve() {
+        let condition = AchievementCondition::ReachLevel {
+            level: 10,
+            levelup_exp: Some(LevelupExpType::Normal),
+        };
+        let level = |levelup_exp, level| ProgressEvent::LevelReached {
+            chara: "reimu",
+            levelup_exp,
+            level,
+        };
+        assert_eq!(condition.progress(&level(&LevelupExpType::Normal, 9)), 0);
+        assert_eq!(condition.progress(&level(&LevelupExpType::Normal, 10)), 1);
+        assert_eq!(condition.progress(&level(&LevelupExpType::Early, 10)), 0);
+    }
+
+    #[test]
+    fn registering_an_id_again_replaces_it() {
+        let mut config = AchievementConfig::default();
+        config.register(vec![
+            achievement("wins", AchievementCondition::WinCount { count: 3 }),
+            achievement("flawless", AchievementCondition::DefeatWithoutDamage { enemy: None }),
+        ]);
+        config.register(vec![achievement("wins", AchievementCondition::WinCount { count: 10 })]);
+        assert_eq!(config.achievement.len(), 2);
+        assert_eq!(
+            config.find("wins").unwrap().condition,
+            AchievementCondition::WinCount { count: 10 }
+        );
+        assert!(config.find("missing").is_none());
+    }
+}
@@ -0,0 +1,79 @@
+use crate::chara::CharaConfig;
+use crate::event::BattleEvent;
+use crate::rpg_core::BattleData;
+use serde::{Deserialize, Serialize};
+
+/// Percentage of the exp of a battle the assist gains
+pub const ASSIST_EXP_PERCENT: u32 = 30;
+
+/// Move of a character called as an assist
+/// example in `chara/{name}.toml`:
+/// ```toml
+/// [assist]
+/// name = "夢想封印"
+/// effect = { Strike = { damage = 40 } }
+/// ```
+#[derive(Deserialize, Serialize, PartialEq, PartialOrd, Debug, Clone)]
+pub struct AssistAbility {
+    pub name: String,
+    pub effect: AssistEffect,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+pub enum AssistEffect {
+    Heal { hp: i16 },
+    /// Absorbs damage from the next enemy attacks until it breaks
+    Shield { amount: i16 },
+    Strike { damage: i16 },
+}
+
+/// Owned character joining the player side as an assist
+#[derive(Deserialize, Serialize, PartialEq, PartialOrd, Debug, Clone)]
+pub struct Assist {
+    /// `chara/{chara}.toml`
+    pub chara: String,
+    pub name: String,
+    pub ability: AssistAbility,
+    /// An assist can be called once per battle
+    pub used: bool,
+}
+
+impl Assist {
+    /// `None` if the character has no assist ability
+    pub fn new(chara_id: &str, chara: &CharaConfig) -> Option<Self> {
+        chara.assist.clone().map(|ability| Self {
+            chara: chara_id.to_string(),
+            name: chara.meta.name.clone(),
+            ability,
+            used: false,
+        })
+    }
+
+    /// Exp the assist gains when the player gains `exp`
+    pub fn exp_share(exp: u32) -> u32 {
+        exp * ASSIST_EXP_PERCENT / 100
+    }
+}
+
+impl BattleData {
+    pub fn assist(&self) -> Option<&Assist> {
+        self.assist.as_ref()
+    }
+
+    /// Call the assist on the turn of the player
+    /// Returns `None` without advancing the turn if there is no assist or it was already called
+    pub fn call_assist(&mut self) -> Option<AssistAbility> {
+        let assist = self.assist.as_mut().filter(|a| !a.used)?;
+        assist.used = true;
+        let ability = assist.ability.clone();
+        let chara = assist.chara.clone();
+        match ability.effect {
+            AssistEffect::Heal { hp } => self.player_mut().charabase.hp += hp,
+            AssistEffect::Shield { amount } => self.player_shield += amount,
+            AssistEffect::Strike { damage } => self.enemy_mut().charabase.hp -= damage,
+        }
+        self.push_event(BattleEvent::Assist { chara });
+        self.add_turn();
+        Some(ability)
+    }
+}
@@ -0,0 +1,182 @@
+use crate::chara::{CharaAttack, CharaConfig};
+use crate::equipment::{apply_bonus, StatBonus};
+use crate::event::ProgressEvent;
+use rand::seq::IteratorRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Affinity gained by winning a battle with the character
+pub const AFFINITY_PER_WIN: u32 = 10;
+/// Affinity gained by finishing a story with the character
+pub const AFFINITY_PER_STORY: u32 = 50;
+
+/// Reached by raising the affinity between a user and an owned character
+/// example in `chara/{name}.toml`:
+/// ```toml
+/// [[bond]]
+/// affinity = 100
+/// name = "信頼"
+/// bonus = [{ stat = "Power", percent = 5 }]
+/// quote = "今日も頼りにしてるわよ"
+/// special = { name = "夢想天生", damage = 150, hit_rate = 0.8 }
+/// ```
+#[derive(Deserialize, Serialize, PartialEq, PartialOrd, Debug, Clone)]
+pub struct BondTier {
+    /// Affinity needed for this tier
+    pub affinity: u32,
+    pub name: String,
+    /// Passive bonus, added to the bonuses of lower tiers
+    #[serde(default)]
+    pub bonus: Vec<StatBonus>,
+    /// Said when a battle starts
+    pub quote: Option<String>,
+    /// Special move added to the attacks
+    pub special: Option<CharaAttack>,
+}
+
+/// Affinity the active character gains from the event
+pub fn affinity_gain(event: &ProgressEvent) -> u32 {
+    match event {
+        ProgressEvent::EnemyDefeated { .. } => AFFINITY_PER_WIN,
+        ProgressEvent::StoryCleared { .. } => AFFINITY_PER_STORY,
+        _ => 0,
+    }
+}
+
+impl CharaConfig {
+    /// Tiers reached with the affinity, lowest first
+    pub fn bond_tiers(&self, affinity: u32) -> Vec<&BondTier> {
+        let mut tiers: Vec<&BondTier> = self.bond.iter().filter(|t| t.affinity <= affinity).collect();
+        tiers.sort_by_key(|t| t.affinity);
+        tiers
+    }
+
+    /// Highest tier reached with the affinity
+    pub fn bond_tier(&self, affinity: u32) -> Option<&BondTier> {
+        self.bond_tiers(affinity).pop()
+    }
+
+    /// Lowest tier not reached yet
+    pub fn next_bond_tier(&self, affinity: u32) -> Option<&BondTier> {
+        self.bond
+            .iter()
+            .filter(|t| t.affinity > affinity)
+            .min_by_key(|t| t.affinity)
+    }
+
+    /// Tiers newly reached when the affinity grows from `before` to `after`
+    pub fn bond_reached(&self, before: u32, after: u32) -> Vec<&BondTier> {
+        self.bond_tiers(after)
+            .into_iter()
+            .filter(|t| t.affinity > before)
+            .collect()
+    }
+
+    /// Add the passive bonuses and special moves of the reached tiers
+    pub fn apply_bond(&mut self, affinity: u32) -> &mut Self {
+        let tiers: Vec<BondTier> = self.bond_tiers(affinity).into_iter().cloned().collect();
+        apply_bonus(&mut self.charabase, tiers.iter().flat_map(|t| t.bonus.iter()));
+        self.attack
+            .extend(tiers.into_iter().filter_map(|t| t.special));
+        self
+    }
+
+    /// One of the quotes of the reached tiers
+    pub fn bond_quote<R: Rng>(&self, affinity: u32, rng: &mut R) -> Option<&str> {
+        self.bond_tiers(affinity)
+            .into_iter()
+            .filter_map(|t| t.quote.as_deref())
+            .choose(rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::equipment::Stat;
+    use crate::rpg_core::tests::chara;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn tier(affinity: u32, quote: &str, special: Option<&str>) -> BondTier {
+        BondTier {
+            affinity,
+            name: affinity.to_string(),
+            bonus: vec![StatBonus {
+                stat: Stat::Power,
+                flat: Some(5),
+                percent: None,
+            }],
+            quote: Some(quote.to_string()),
+            special: special.map(|name| CharaAttack {
+                name: name.to_string(),
+                damage: 150,
+                hit_rate: 0.8,
+                abnormal_state: None,
+            }),
+        }
+    }
+
+    fn reimu() -> CharaConfig {
+        let mut reimu = chara("reimu", 100);
+        // Tiers are sorted by their affinity, not the order in the file
+        reimu.bond = vec![
+            tier(300, "最後まで一緒よ", Some("夢想天生")),
+            tier(100, "頼りにしてるわよ", None),
+        ];
+        reimu
+    }
+
+    fn affinities(tiers: Vec<&BondTier>) -> Vec<u32> {
+        tiers.into_iter().map(|t| t.affinity).collect()
+    }
+
+    #[test]
+    fn tiers_are_reached_at_their_affinity() {
+        let reimu = reimu();
+        assert!(reimu.bond_tier(99).is_none());
+        assert_eq!(reimu.bond_tier(100).map(|t| t.affinity), Some(100));
+        assert_eq!(affinities(reimu.bond_tiers(300)), [100, 300]);
+        assert_eq!(reimu.next_bond_tier(100).map(|t| t.affinity), Some(300));
+        assert!(reimu.next_bond_tier(300).is_none());
+    }
+
+    #[test]
+    fn reached_tiers_are_only_the_new_ones() {
+        let reimu = reimu();
+        assert_eq!(affinities(reimu.bond_reached(90, 100)), [100]);
+        assert_eq!(affinities(reimu.bond_reached(90, 300)), [100, 300]);
+        assert!(reimu.bond_reached(100, 290).is_empty());
+    }
+
+    #[test]
+    fn bond_adds_bonuses_and_specials_of_reached_tiers() {
+        let mut reimu = reimu();
+        let power = reimu.charabase.power;
+        reimu.apply_bond(100);
+        assert_eq!(reimu.charabase.power, power + 5);
+        assert_eq!(reimu.attack.len(), 1);
+
+        let mut reimu = self::reimu();
+        reimu.apply_bond(300);
+        assert_eq!(reimu.charabase.power, power + 10);
+        let attacks: Vec<&str> = reimu.attack.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(attacks, ["弾幕", "夢想天生"]);
+    }
+
+    #[test]
+    fn quote_comes_from_a_reached_tier() {
+        let reimu = reimu();
+        let mut rng = StdRng::seed_from_u64(1);
+        assert_eq!(reimu.bond_quote(100, &mut rng), Some("頼りにしてるわよ"));
+        assert_eq!(reimu.bond_quote(0, &mut rng), None);
+        assert!(reimu.bond_quote(300, &mut rng).is_some());
+    }
+
+    #[test]
+    fn wins_and_stories_raise_the_affinity() {
+        let cleared = ProgressEvent::StoryCleared { story: "scarlet" };
+        assert_eq!(affinity_gain(&cleared), AFFINITY_PER_STORY);
+        assert_eq!(affinity_gain(&ProgressEvent::ItemUsed { item: "potion" }), 0);
+    }
+}
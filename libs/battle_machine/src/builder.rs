@@ -1,12 +1,16 @@
 use std::path::{PathBuf, Path};
 
 use crate::{
+    assist::Assist,
     chara::CharaConfig,
-    rpg_core::BattleData,
+    equipment::{apply_gear, gear_immunity, EquipmentConfig},
+    field::{Field, FieldConfig},
+    rpg_core::{BattleData, Controller},
     mode::PlayMode
 };
 use chrono::prelude::{Local, NaiveDateTime};
 use rand::prelude::IteratorRandom;
+use rand::Rng;
 use thrpg_database::playdata::Model;
 use uuid::Uuid;
 
@@ -19,6 +23,15 @@ pub struct BattleBuilder {
     player: Option<CharaConfig>,
     enemy: Option<CharaConfig>,
     elapsed_turns: u32,
+    player_controller: Controller,
+    enemy_controller: Controller,
+    player_equipment: Vec<EquipmentConfig>,
+    enemy_equipment: Vec<EquipmentConfig>,
+    assist: Option<Assist>,
+    player_affinity: u32,
+    field: Option<Field>,
+    /// The characters come from a saved battle and already have everything applied
+    saved: bool,
 }
 
 #[derive(Debug)]
@@ -46,6 +59,14 @@ impl Default for BattleBuilder {
             enemy: None,
             player: None,
             uuid: Uuid::new_v4(),
+            player_controller: Controller::default(),
+            enemy_controller: Controller::default(),
+            player_equipment: Vec::new(),
+            enemy_equipment: Vec::new(),
+            assist: None,
+            player_affinity: 0,
+            field: None,
+            saved: false,
         }
     }
 }
@@ -64,6 +85,14 @@ impl BattleBuilder {
             player,
             enemy,
             elapsed_turns: elapsed_turns.unwrap_or_default(),
+            player_controller: Controller::default(),
+            enemy_controller: Controller::default(),
+            player_equipment: Vec::new(),
+            enemy_equipment: Vec::new(),
+            assist: None,
+            player_affinity: 0,
+            field: None,
+            saved: false,
         }
     }
 
@@ -116,6 +145,56 @@ impl BattleBuilder {
         self
     }
 
+    /// The player side is operated by this user
+    pub fn player_user(&mut self, user_id: u64) -> &mut Self {
+        self.player_controller = Controller::User(user_id);
+        self
+    }
+
+    /// The enemy side is operated by this user
+    /// Use this for user vs user battles
+    pub fn enemy_user(&mut self, user_id: u64) -> &mut Self {
+        self.enemy_controller = Controller::User(user_id);
+        self
+    }
+
+    /// Gear of the player, applied when building
+    pub fn player_equipment(&mut self, gear: Vec<EquipmentConfig>) -> &mut Self {
+        self.player_equipment = gear;
+        self
+    }
+
+    /// Gear of the enemy, applied when building
+    pub fn enemy_equipment(&mut self, gear: Vec<EquipmentConfig>) -> &mut Self {
+        self.enemy_equipment = gear;
+        self
+    }
+
+    /// Owned character of the player called once per battle
+    /// Characters without an assist ability are ignored
+    pub fn assist(&mut self, chara_id: &str, chara: &CharaConfig) -> &mut Self {
+        self.assist = Assist::new(chara_id, chara);
+        self
+    }
+
+    /// Affinity between the user and the player character
+    pub fn player_affinity(&mut self, affinity: u32) -> &mut Self {
+        self.player_affinity = affinity;
+        self
+    }
+
+    /// Field of the battle
+    pub fn field(&mut self, field: Option<Field>) -> &mut Self {
+        self.field = field;
+        self
+    }
+
+    /// Choose the field from the mode and the enemy
+    pub fn choose_field<R: Rng>(&mut self, fields: &FieldConfig, rng: &mut R) -> &mut Self {
+        self.field = fields.choose(&self.mode, self.enemy.as_ref(), rng).cloned();
+        self
+    }
+
     /// Randomly choose the enemy
     pub async fn enemy_random(&mut self, random_options: RandomOption, charas: Vec<CharaConfig>) -> &mut Self {
         let chara = random_options.chara_random(charas).ok();
@@ -126,11 +205,7 @@ impl BattleBuilder {
     pub fn player_status_setting(&mut self, level: i16) -> &mut Self {
         match self.player.clone() {
             Some(mut p) => {
-                p.charabase.power += 2 * level;
-                p.charabase.guard += 2 * level;
-                p.charabase.speed += 2 * level;
-                p.charabase.mp += 2 * level;
-                p.charabase.hp += 2 * level;
+                p.charabase = p.charabase.with_level(level);
                 self.player = Some(p);
                 self
             }
@@ -141,12 +216,8 @@ impl BattleBuilder {
     pub fn enemy_status_setting(&mut self, level: i16) -> &mut Self {
         match self.enemy.clone() {
             Some(mut p) => {
-                p.charabase.power += 2 * level;
-                p.charabase.guard += 2 * level;
-                p.charabase.speed += 2 * level;
-                p.charabase.mp += 2 * level;
-                p.charabase.hp += 2 * level;
-                self.player = Some(p);
+                p.charabase = p.charabase.with_level(level);
+                self.enemy = Some(p);
                 self
             }
             None => self,
@@ -154,15 +225,38 @@ impl BattleBuilder {
     }
 
     /// build BattleData
+    /// Bonds, gear and the field are applied after the level, so percentage bonuses grow with the level
+    /// Characters of a saved battle keep the stats they were saved with, only the gear immunities are set again
     pub fn build(self) -> BattleData {
-        BattleData::new(
+        let mut player = self.player.unwrap();
+        let mut enemy = self.enemy.unwrap();
+        if !self.saved {
+            player.apply_bond(self.player_affinity);
+            apply_gear(&mut player.charabase, &self.player_equipment);
+            apply_gear(&mut enemy.charabase, &self.enemy_equipment);
+            if let Some(field) = &self.field {
+                field.apply(&mut player);
+                field.apply(&mut enemy);
+            }
+        }
+
+        let mut battle = BattleData::new(
             self.uuid,
-            self.player.unwrap(),
-            self.enemy.unwrap(),
+            player,
+            enemy,
             self.mode,
             self.datatime,
             self.elapsed_turns,
-        )
+        );
+        battle
+            .controllers(self.player_controller, self.enemy_controller)
+            .immunities(
+                gear_immunity(&self.player_equipment),
+                gear_immunity(&self.enemy_equipment),
+            );
+        battle.assist = self.assist;
+        battle.field = self.field;
+        battle
     }
 }
 
@@ -208,23 +302,80 @@ impl RandomOption {
     }
 }
 
+/// Resume a battle saved by [TryFrom<&BattleData>](Model)
+/// The characters come back with the hp, level, gear, bond and field they were saved with
 impl TryFrom<Model> for BattleBuilder {
     type Error = anyhow::Error;
     fn try_from(model: Model) -> Result<Self, Self::Error> {
-        let builder = BattleBuilder::new(
+        let mut builder = BattleBuilder::new(
             PlayMode::try_from_value(&model.play_mode)?,
             // base type is CharaConfig
-            Some(
-                CharaConfig::from_file_name_noasync(
-                    &model.player["charabase"]["name"].as_str().unwrap(),
-                )?,
-            ),
+            Some(serde_json::from_value(model.player)?),
             // base type is CharaConfig
-            Some(
-                CharaConfig::from_file_name_noasync(&model.enemy["charabase"]["name"].as_str().unwrap())?,
-            ),
-            Some(model.elapesd_turns),
+            Some(serde_json::from_value(model.enemy)?),
+            Some(model.elapesd_turns as u32),
         );
+        builder.uuid = model.battle_uuid;
+        builder.datatime = model.start_time;
+        builder.saved = true;
         Ok(builder)
     }
 }
+
+/// Row of `playdata` to save the battle
+/// The battle does not know the level of the enemy, `enemy_level` is 1 until it is set
+impl TryFrom<&BattleData> for Model {
+    type Error = anyhow::Error;
+    fn try_from(battle: &BattleData) -> Result<Self, Self::Error> {
+        Ok(Model {
+            battle_uuid: battle.uuid(),
+            player: serde_json::to_value(battle.player())?,
+            enemy: serde_json::to_value(battle.enemy())?,
+            elapesd_turns: battle.elapsed_turns() as i64,
+            start_time: battle.start_time(),
+            play_mode: battle.play_mode().as_str().to_string(),
+            enemy_level: 1,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chara::AbnormalState;
+    use crate::rpg_core::{tests::chara, StatusCharaType};
+
+    fn gear() -> EquipmentConfig {
+        serde_json::from_value(serde_json::json!({
+            "id": "charm",
+            "name": "お守り",
+            "slot": "Charm",
+            "bonus": [{ "stat": "Hp", "flat": 20 }],
+            "immunity": ["Poisoned"],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn saved_battle_resumes_as_it_was() {
+        let mut builder = BattleBuilder::new(
+            PlayMode::Simple,
+            Some(chara("reimu", 100)),
+            Some(chara("marisa", 110)),
+            None,
+        );
+        builder.player_status_setting(5).player_equipment(vec![gear()]);
+        let mut battle = builder.build();
+        battle.calculate_enemy_damage().add_turn();
+        let saved: Model = (&battle).try_into().unwrap();
+
+        let mut resumed: BattleBuilder = saved.try_into().unwrap();
+        resumed.player_equipment(vec![gear()]);
+        let resumed = resumed.build();
+        assert_eq!(resumed.uuid(), battle.uuid());
+        assert_eq!(resumed.elapsed_turns(), battle.elapsed_turns());
+        assert_eq!(resumed.player().charabase, battle.player().charabase);
+        assert_eq!(resumed.enemy().charabase, battle.enemy().charabase);
+        assert!(resumed.is_immune(StatusCharaType::Player, &AbnormalState::Poisoned));
+    }
+}
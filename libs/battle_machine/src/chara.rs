@@ -1,5 +1,8 @@
 use std::path::Path;
 
+use crate::assist::AssistAbility;
+use crate::bond::BondTier;
+use crate::reward::DropEntry;
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Debug, Clone, PartialEq, PartialOrd, Serialize)]
@@ -7,7 +10,19 @@ pub struct CharaConfig {
     pub charabase: CharaBase,
     pub attack: Vec<CharaAttack>,
     pub meta: CharaMeta,
-    pub(crate) inside_info: InsideInfo
+    pub(crate) inside_info: InsideInfo,
+    /// Rewards when this character is defeated
+    #[serde(default)]
+    pub drop: Vec<DropEntry>,
+    /// Move when this character is called as an assist
+    #[serde(default)]
+    pub assist: Option<AssistAbility>,
+    /// Tiers of the affinity with the user
+    #[serde(default)]
+    pub bond: Vec<BondTier>,
+    /// `field` id brought into the battle when this character is the enemy
+    #[serde(default)]
+    pub field: Option<String>,
 }
 
 #[derive(Copy, Debug, Clone, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -136,13 +151,33 @@ pub enum LuckyLevel {
     LuckyThree,
 }
 
-#[derive(Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Debug, Clone)]
+#[derive(Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone)]
 pub enum AbnormalState {
     Slowed,
     Poisoned,
     Unlucky,
 }
 
+impl SpeciesType {
+    /// Same species, ignoring the `description` of `Human` and `Yokai`
+    pub fn same_kind(&self, other: &SpeciesType) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+impl CharaBase {
+    /// Stats grown by the level
+    pub fn with_level(&self, level: i16) -> Self {
+        Self {
+            power: self.power + 2 * level,
+            guard: self.guard + 2 * level,
+            speed: self.speed + 2 * level,
+            hp: self.hp + 2 * level,
+            mp: self.mp + 2 * level,
+        }
+    }
+}
+
 impl CharaConfig {
     pub async fn charas_new<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<Self>> {
         let files = thrpg_utils::dir_files(path.as_ref()).await?;
@@ -0,0 +1,268 @@
+use crate::chara::{AbnormalState, CharaBase};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// `equipment/{id}.toml`
+/// example:
+/// ```toml
+/// id = "hakkero"
+/// name = "ミニ八卦炉"
+/// slot = "Weapon"
+/// bonus = [{ stat = "Power", flat = 15 }, { stat = "Mp", percent = 10 }]
+/// immunity = ["Slowed"]
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct EquipmentConfig {
+    pub id: String,
+    pub name: String,
+    pub slot: EquipmentSlot,
+    #[serde(default)]
+    pub bonus: Vec<StatBonus>,
+    /// Abnormal states that do not affect the wearer
+    #[serde(default)]
+    pub immunity: Vec<AbnormalState>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum EquipmentSlot {
+    Weapon,
+    Charm,
+    Accessory,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Stat {
+    Power,
+    Guard,
+    Speed,
+    Hp,
+    Mp,
+}
+
+/// `flat` is added first, then `percent` of the stat with level is added
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct StatBonus {
+    pub stat: Stat,
+    pub flat: Option<i16>,
+    pub percent: Option<i16>,
+}
+
+impl EquipmentSlot {
+    pub fn try_from_value(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "Weapon" => Ok(Self::Weapon),
+            "Charm" => Ok(Self::Charm),
+            "Accessory" => Ok(Self::Accessory),
+            _ => Err(anyhow::anyhow!(format!("No match {}", value))),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Weapon => "Weapon",
+            Self::Charm => "Charm",
+            Self::Accessory => "Accessory",
+        }
+    }
+}
+
+impl Stat {
+    pub fn get(&self, base: &CharaBase) -> i16 {
+        match self {
+            Stat::Power => base.power,
+            Stat::Guard => base.guard,
+            Stat::Speed => base.speed,
+            Stat::Hp => base.hp,
+            Stat::Mp => base.mp,
+        }
+    }
+
+    pub fn get_mut<'a>(&self, base: &'a mut CharaBase) -> &'a mut i16 {
+        match self {
+            Stat::Power => &mut base.power,
+            Stat::Guard => &mut base.guard,
+            Stat::Speed => &mut base.speed,
+            Stat::Hp => &mut base.hp,
+            Stat::Mp => &mut base.mp,
+        }
+    }
+}
+
+impl EquipmentConfig {
+    /// Create a structure from file names
+    /// `equipment/{file names}.toml`
+    pub async fn from_file_name<T: ToString>(name: T) -> anyhow::Result<Self> {
+        let content =
+            thrpg_utils::read_to_toml(format!("equipment/{}.toml", name.to_string())).await?;
+        Ok(content)
+    }
+
+    pub fn from_file_name_noasync<T: ToString>(name: T) -> anyhow::Result<Self> {
+        let content =
+            thrpg_utils::read_to_toml_noasync(format!("equipment/{}.toml", name.to_string()))?;
+        Ok(content)
+    }
+
+    pub async fn equipments_new<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<Self>> {
+        let files = thrpg_utils::dir_files(path.as_ref()).await?;
+        let mut vec = Vec::new();
+        for file_path in files {
+            vec.push(thrpg_utils::read_to_toml(file_path).await?);
+        }
+        Ok(vec)
+    }
+}
+
+/// Stats added by the gear to `base`
+/// Every field of the result is the bonus only, not the total
+pub fn gear_bonus(base: &CharaBase, gear: &[EquipmentConfig]) -> CharaBase {
+    stat_bonus(base, gear.iter().flat_map(|e| e.bonus.iter()))
+}
+
+/// Stats added by the bonuses to `base`
+/// Every field of the result is the bonus only, not the total
+pub fn stat_bonus<'a, I: IntoIterator<Item = &'a StatBonus>>(base: &CharaBase, bonuses: I) -> CharaBase {
+    let mut bonus = CharaBase {
+        power: 0,
+        guard: 0,
+        speed: 0,
+        hp: 0,
+        mp: 0,
+    };
+    for b in bonuses {
+        let value = b.flat.unwrap_or(0) + b.stat.get(base) * b.percent.unwrap_or(0) / 100;
+        *b.stat.get_mut(&mut bonus) += value;
+    }
+    bonus
+}
+
+/// Add the gear bonus to the stats of the character
+pub fn apply_gear(base: &mut CharaBase, gear: &[EquipmentConfig]) {
+    apply_bonus(base, gear.iter().flat_map(|e| e.bonus.iter()));
+}
+
+/// Add the bonuses to the stats of the character
+pub fn apply_bonus<'a, I: IntoIterator<Item = &'a StatBonus>>(base: &mut CharaBase, bonuses: I) {
+    let bonus = stat_bonus(base, bonuses);
+    base.power += bonus.power;
+    base.guard += bonus.guard;
+    base.speed += bonus.speed;
+    base.hp += bonus.hp;
+    base.mp += bonus.mp;
+}
+
+/// Abnormal states the gear protects from
+pub fn gear_immunity(gear: &[EquipmentConfig]) -> Vec<AbnormalState> {
+    let mut immunity: Vec<AbnormalState> =
+        gear.iter().flat_map(|e| e.immunity.iter().cloned()).collect();
+    immunity.sort();
+    immunity.dedup();
+    immunity
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> CharaBase {
+        CharaBase {
+            power: 100,
+            guard: 80,
+            speed: 50,
+            hp: 200,
+            mp: 40,
+        }
+    }
+
+    fn gear(
+        id: &str,
+        slot: EquipmentSlot,
+        bonus: Vec<StatBonus>,
+        immunity: Vec<AbnormalState>,
+    ) -> EquipmentConfig {
+        EquipmentConfig {
+            id: id.to_string(),
+            name: id.to_string(),
+            slot,
+            bonus,
+            immunity,
+        }
+    }
+
+    fn bonus(stat: Stat, flat: Option<i16>, percent: Option<i16>) -> StatBonus {
+        StatBonus { stat, flat, percent }
+    }
+
+    #[test]
+    fn bonuses_of_every_piece_add_up() {
+        let gear = [
+            gear(
+                "hakkero",
+                EquipmentSlot::Weapon,
+                vec![bonus(Stat::Power, Some(15), None), bonus(Stat::Mp, None, Some(10))],
+                vec![],
+            ),
+            gear(
+                "charm",
+                EquipmentSlot::Charm,
+                vec![bonus(Stat::Power, Some(5), Some(10)), bonus(Stat::Hp, Some(20), None)],
+                vec![],
+            ),
+        ];
+        let expected = CharaBase {
+            power: 30,
+            guard: 0,
+            speed: 0,
+            hp: 20,
+            mp: 4,
+        };
+        assert_eq!(gear_bonus(&base(), &gear), expected);
+
+        let mut stats = base();
+        apply_gear(&mut stats, &gear);
+        assert_eq!(
+            stats,
+            CharaBase {
+                power: 130,
+                guard: 80,
+                speed: 50,
+                hp: 220,
+                mp: 44,
+            }
+        );
+    }
+
+    #[test]
+    fn percent_bonus_is_taken_from_the_stats_before_the_gear() {
+        let gear = [
+            gear("a", EquipmentSlot::Weapon, vec![bonus(Stat::Speed, Some(50), None)], vec![]),
+            gear("b", EquipmentSlot::Charm, vec![bonus(Stat::Speed, None, Some(50))], vec![]),
+        ];
+        assert_eq!(gear_bonus(&base(), &gear).speed, 75);
+    }
+
+    #[test]
+    fn immunities_are_merged_once() {
+        let gear = [
+            gear("a", EquipmentSlot::Charm, vec![], vec![AbnormalState::Poisoned]),
+            gear(
+                "b",
+                EquipmentSlot::Accessory,
+                vec![],
+                vec![AbnormalState::Slowed, AbnormalState::Poisoned],
+            ),
+        ];
+        let mut expected = vec![AbnormalState::Poisoned, AbnormalState::Slowed];
+        expected.sort();
+        assert_eq!(gear_immunity(&gear), expected);
+        assert!(gear_immunity(&[]).is_empty());
+    }
+
+    #[test]
+    fn slots_round_trip_through_their_names() {
+        for slot in [EquipmentSlot::Weapon, EquipmentSlot::Charm, EquipmentSlot::Accessory] {
+            assert_eq!(EquipmentSlot::try_from_value(slot.as_str()).unwrap(), slot);
+        }
+        assert!(EquipmentSlot::try_from_value("Hat").is_err());
+    }
+}
@@ -0,0 +1,33 @@
+use crate::chara::{AbnormalState, LevelupExpType};
+use crate::rpg_core::{BattleData, StatusCharaType};
+use serde::{Deserialize, Serialize};
+
+/// What happened in a battle, in order
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum BattleEvent {
+    Attack { attacker: StatusCharaType, damage: i16 },
+    Guard { side: StatusCharaType },
+    ItemUsed { side: StatusCharaType, item: String },
+    /// The assist of the player was called
+    Assist { chara: String },
+    /// HP changed by the field at the end of the turn of the side
+    Field { side: StatusCharaType, hp: i16 },
+    /// The side got an abnormal state from an attack
+    AbnormalState { side: StatusCharaType, state: AbnormalState },
+}
+
+/// Progress of a user, used by unlocks, achievements, quests and bonds
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProgressEvent<'a> {
+    /// The player side defeated the enemy of the battle
+    EnemyDefeated { battle: &'a BattleData },
+    LevelReached {
+        chara: &'a str,
+        levelup_exp: &'a LevelupExpType,
+        level: u32,
+    },
+    /// The user finished a story, such as an incident, with the active character
+    StoryCleared { story: &'a str },
+    /// The user used an item of `item/{item}.toml` in a battle
+    ItemUsed { item: &'a str },
+}
@@ -0,0 +1,293 @@
+use crate::chara::{CharaConfig, SpeciesType};
+use crate::equipment::{apply_bonus, StatBonus};
+use crate::event::BattleEvent;
+use crate::mode::PlayMode;
+use crate::rpg_core::{BattleData, StatusCharaType};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Fields read from `field/*.toml` and the `field.toml` of Contents extensions
+/// example:
+/// ```toml
+/// [[field]]
+/// id = "scarlet_mist"
+/// name = "紅霧"
+/// description = "紅い霧が日の光を遮っている"
+/// modes = ["Simple", "Raid"]
+/// chance = 0.2
+///
+/// [[field.effect]]
+/// species = ["Vampire"]
+/// bonus = [{ stat = "Power", percent = 20 }]
+///
+/// [[field.effect]]
+/// species = [{ Human = { description = "" } }]
+/// hp_per_turn = -3
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FieldConfig {
+    #[serde(default)]
+    pub field: Vec<Field>,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct Field {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    /// `PlayMode`s or story ids where the field can happen by chance
+    #[serde(default)]
+    pub modes: Vec<String>,
+    /// Probability of the field in those modes
+    #[serde(default)]
+    pub chance: f64,
+    #[serde(default)]
+    pub effect: Vec<FieldEffect>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct FieldEffect {
+    /// Species affected, every character if empty
+    #[serde(default)]
+    pub species: Vec<SpeciesType>,
+    /// Added to the stats when the battle starts
+    #[serde(default)]
+    pub bonus: Vec<StatBonus>,
+    /// Added to the HP at the end of each turn of the character
+    #[serde(default)]
+    pub hp_per_turn: i16,
+}
+
+impl FieldConfig {
+    /// Read and merge every file
+    pub async fn load(paths: Vec<PathBuf>) -> anyhow::Result<Self> {
+        let mut config = Self::default();
+        for path in paths {
+            let other: Self = thrpg_utils::read_to_toml(path).await?;
+            config.register(other.field);
+        }
+        Ok(config)
+    }
+
+    /// Files in `field/` and `field.toml` in the directories of Contents extensions
+    pub async fn load_with_extensions<P: AsRef<Path>>(
+        dir: P,
+        extension_dirs: Vec<PathBuf>,
+    ) -> anyhow::Result<Self> {
+        let mut paths = thrpg_utils::dir_files(dir.as_ref()).await.unwrap_or_default();
+        paths.extend(
+            extension_dirs
+                .into_iter()
+                .map(|dir| dir.join("field.toml"))
+                .filter(|path| path.is_file()),
+        );
+        Self::load(paths).await
+    }
+
+    /// Add fields, a field with the same id is replaced
+    pub fn register(&mut self, fields: Vec<Field>) -> &mut Self {
+        for field in fields {
+            self.field.retain(|f| f.id != field.id);
+            self.field.push(field);
+        }
+        self
+    }
+
+    pub fn find(&self, id: &str) -> Option<&Field> {
+        self.field.iter().find(|f| f.id == id)
+    }
+
+    /// The field of the enemy if it has one, otherwise maybe one of the fields of the mode
+    pub fn choose<R: Rng>(&self, mode: &PlayMode, enemy: Option<&CharaConfig>, rng: &mut R) -> Option<&Field> {
+        if let Some(id) = enemy.and_then(|e| e.field.as_ref()) {
+            return self.find(id);
+        }
+        let candidates: Vec<&Field> = self
+            .field
+            .iter()
+            .filter(|f| {
+                f.modes
+                    .iter()
+                    .any(|m| m == mode.kind() || Some(m.as_str()) == mode.story_id())
+            })
+            .collect();
+        candidates
+            .choose(rng)
+            .copied()
+            .filter(|f| rng.gen_bool(f.chance.clamp(0.0, 1.0)))
+    }
+}
+
+impl Field {
+    /// Effects on the character
+    pub fn effects_on<'a>(&'a self, chara: &'a CharaConfig) -> impl Iterator<Item = &'a FieldEffect> {
+        self.effect.iter().filter(move |e| {
+            e.species.is_empty()
+                || e.species.iter().any(|s| s.same_kind(&chara.meta.species_type))
+        })
+    }
+
+    /// Add the stat bonuses of the field to the character
+    pub fn apply(&self, chara: &mut CharaConfig) {
+        let bonuses: Vec<StatBonus> = self
+            .effects_on(chara)
+            .flat_map(|e| e.bonus.iter().copied())
+            .collect();
+        apply_bonus(&mut chara.charabase, bonuses.iter());
+    }
+}
+
+impl BattleData {
+    pub fn field(&self) -> Option<&Field> {
+        self.field.as_ref()
+    }
+
+    /// HP change of the field at the end of the turn of the side
+    pub(crate) fn field_turn(&mut self, side: StatusCharaType) {
+        let field = match &self.field {
+            Some(field) => field,
+            None => return,
+        };
+        let chara = match side {
+            StatusCharaType::Player => self.player(),
+            StatusCharaType::Enemy => self.enemy(),
+        };
+        let hp: i16 = field.effects_on(chara).map(|e| e.hp_per_turn).sum();
+        if hp == 0 {
+            return;
+        }
+        match side {
+            StatusCharaType::Player => self.player_mut().charabase.hp += hp,
+            StatusCharaType::Enemy => self.enemy_mut().charabase.hp += hp,
+        }
+        self.push_event(BattleEvent::Field { side, hp });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::equipment::Stat;
+    use crate::rpg_core::tests::chara;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use uuid::Uuid;
+
+    fn field(id: &str, modes: &[&str], chance: f64) -> Field {
+        Field {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: String::new(),
+            modes: modes.iter().map(|m| m.to_string()).collect(),
+            chance,
+            effect: vec![
+                FieldEffect {
+                    species: vec![SpeciesType::Vampire],
+                    bonus: vec![StatBonus {
+                        stat: Stat::Power,
+                        flat: Some(20),
+                        percent: None,
+                    }],
+                    hp_per_turn: 0,
+                },
+                FieldEffect {
+                    species: vec![SpeciesType::Human {
+                        description: String::new(),
+                    }],
+                    bonus: vec![],
+                    hp_per_turn: -3,
+                },
+            ],
+        }
+    }
+
+    fn config() -> FieldConfig {
+        let mut config = FieldConfig::default();
+        config.register(vec![
+            field("scarlet_mist", &["Simple"], 1.0),
+            field("bamboo", &["eientei"], 1.0),
+            field("calm", &["Raid"], 0.0),
+        ]);
+        config
+    }
+
+    fn species(name: &str, species: SpeciesType) -> CharaConfig {
+        let mut chara = chara(name, 100);
+        chara.meta.species_type = species;
+        chara
+    }
+
+    #[test]
+    fn enemy_field_comes_first() {
+        let config = config();
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut remilia = species("remilia", SpeciesType::Vampire);
+        remilia.field = Some("bamboo".to_string());
+        let chosen = config.choose(&PlayMode::Raid, Some(&remilia), &mut rng);
+        assert_eq!(chosen.map(|f| f.id.as_str()), Some("bamboo"));
+    }
+
+    #[test]
+    fn fields_happen_in_their_modes_by_chance() {
+        let config = config();
+        let mut rng = StdRng::seed_from_u64(1);
+        let id = |mode: PlayMode, rng: &mut StdRng| {
+            config.choose(&mode, None, rng).map(|f| f.id.clone())
+        };
+        assert_eq!(id(PlayMode::Simple, &mut rng).as_deref(), Some("scarlet_mist"));
+        let story = PlayMode::Story {
+            id: "eientei".to_string(),
+        };
+        assert_eq!(id(story, &mut rng).as_deref(), Some("bamboo"));
+        assert_eq!(id(PlayMode::Raid, &mut rng), None);
+        assert_eq!(id(PlayMode::Duel, &mut rng), None);
+    }
+
+    #[test]
+    fn effects_are_only_on_their_species() {
+        let field = field("scarlet_mist", &[], 1.0);
+        let mut remilia = species("remilia", SpeciesType::Vampire);
+        let mut marisa = species(
+            "marisa",
+            SpeciesType::Human {
+                description: "魔法使い".to_string(),
+            },
+        );
+        let power = remilia.charabase.power;
+        field.apply(&mut remilia);
+        field.apply(&mut marisa);
+        assert_eq!(remilia.charabase.power, power + 20);
+        assert_eq!(marisa.charabase.power, power);
+        // `description` of Human is ignored
+        assert_eq!(field.effects_on(&marisa).count(), 1);
+    }
+
+    #[test]
+    fn field_changes_the_hp_at_the_end_of_the_turn() {
+        let marisa = species(
+            "marisa",
+            SpeciesType::Human {
+                description: String::new(),
+            },
+        );
+        let mut battle = BattleData::new(
+            Uuid::new_v4(),
+            marisa,
+            species("remilia", SpeciesType::Vampire),
+            PlayMode::Simple,
+            chrono::Local::now().naive_local(),
+            0,
+        );
+        battle.field = Some(field("scarlet_mist", &[], 1.0));
+        let hp = battle.player().charabase.hp;
+        let side = battle.turn_side();
+        battle.add_turn();
+        battle.add_turn();
+
+        assert_eq!(side, StatusCharaType::Player);
+        assert_eq!(battle.player().charabase.hp, hp - 3);
+        assert_eq!(battle.events(), &vec![BattleEvent::Field { side, hp: -3 }]);
+    }
+}
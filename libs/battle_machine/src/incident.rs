@@ -0,0 +1,327 @@
+use crate::chara::CharaBase;
+use crate::rpg_core::BattleData;
+use chrono::{Duration, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Incidents (異変) read from `incident/*.toml` and the `incident.toml` of Story and Contents extensions
+/// example:
+/// ```toml
+/// [[incident]]
+/// id = "scarlet_mist"
+/// name = "紅霧異変"
+/// description = "幻想郷を覆う紅い霧の元凶を倒せ"
+/// recover_percent = 50
+///
+/// [[incident.stage]]
+/// name = "霧の湖"
+/// mid_boss = "daiyousei"
+/// boss = "cirno"
+/// level = 5
+/// checkpoint = true
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct IncidentConfig {
+    #[serde(default)]
+    pub incident: Vec<Incident>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Incident {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    /// Percentage of the lost HP and MP recovered between stages
+    #[serde(default = "default_recover_percent")]
+    pub recover_percent: u32,
+    pub stage: Vec<Stage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stage {
+    pub name: String,
+    /// `chara/{mid_boss}.toml`
+    pub mid_boss: String,
+    /// `chara/{boss}.toml`
+    pub boss: String,
+    pub level: i16,
+    /// A defeat after clearing this stage restarts from the next stage
+    #[serde(default)]
+    pub checkpoint: bool,
+    /// `field/` id of the stage, chosen as usual if not set
+    #[serde(default)]
+    pub field: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Encounter {
+    MidBoss,
+    Boss,
+}
+
+/// Progress of a user in an incident, saved between battles
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IncidentRun {
+    pub incident: String,
+    pub stage: usize,
+    pub encounter: Encounter,
+    /// HP carried into the next battle, `None` is full
+    pub hp: Option<i16>,
+    /// MP carried into the next battle, `None` is full
+    pub mp: Option<i16>,
+    /// Stage a defeat restarts from
+    pub checkpoint: usize,
+    /// Completion time counts from here, defeats don't reset it
+    pub started_at: NaiveDateTime,
+    #[serde(default)]
+    pub defeats: u32,
+}
+
+fn default_recover_percent() -> u32 {
+    50
+}
+
+impl IncidentConfig {
+    /// Read and merge every file
+    pub async fn load(paths: Vec<PathBuf>) -> anyhow::Result<Self> {
+        let mut config = Self::default();
+        for path in paths {
+            let other: Self = thrpg_utils::read_to_toml(path).await?;
+            config.register(other.incident);
+        }
+        Ok(config)
+    }
+
+    /// Files in `incident/` and `incident.toml` in the directories of extensions
+    pub async fn load_with_extensions<P: AsRef<Path>>(
+        dir: P,
+        extension_dirs: Vec<PathBuf>,
+    ) -> anyhow::Result<Self> {
+        let mut paths = thrpg_utils::dir_files(dir.as_ref()).await.unwrap_or_default();
+        paths.extend(
+            extension_dirs
+                .into_iter()
+                .map(|dir| dir.join("incident.toml"))
+                .filter(|path| path.is_file()),
+        );
+        Self::load(paths).await
+    }
+
+    /// Add incidents, an incident with the same id is replaced
+    pub fn register(&mut self, incidents: Vec<Incident>) -> &mut Self {
+        for incident in incidents {
+            self.incident.retain(|i| i.id != incident.id);
+            self.incident.push(incident);
+        }
+        self
+    }
+
+    pub fn find(&self, id: &str) -> Option<&Incident> {
+        self.incident.iter().find(|i| i.id == id)
+    }
+}
+
+impl IncidentRun {
+    pub fn new(incident: &Incident, now: NaiveDateTime) -> Self {
+        Self {
+            incident: incident.id.clone(),
+            stage: 0,
+            encounter: Encounter::MidBoss,
+            hp: None,
+            mp: None,
+            checkpoint: 0,
+            started_at: now,
+            defeats: 0,
+        }
+    }
+
+    pub fn stage<'a>(&self, incident: &'a Incident) -> Option<&'a Stage> {
+        incident.stage.get(self.stage)
+    }
+
+    /// `chara/{enemy}.toml` of the next battle
+    pub fn enemy<'a>(&self, incident: &'a Incident) -> Option<&'a str> {
+        self.stage(incident).map(|stage| match self.encounter {
+            Encounter::MidBoss => stage.mid_boss.as_str(),
+            Encounter::Boss => stage.boss.as_str(),
+        })
+    }
+
+    pub fn is_cleared(&self, incident: &Incident) -> bool {
+        self.stage >= incident.stage.len()
+    }
+
+    pub fn elapsed(&self, now: NaiveDateTime) -> Duration {
+        now - self.started_at
+    }
+
+    /// Move to the next battle after a win
+    /// `remaining` is the status at the end of the battle and `max` the status at its start with full HP and MP
+    pub fn win(&mut self, incident: &Incident, remaining: &CharaBase, max: &CharaBase) -> &mut Self {
+        match self.encounter {
+            // The boss comes right after the mid-boss, nothing is recovered
+            Encounter::MidBoss => {
+                self.encounter = Encounter::Boss;
+                self.hp = Some(remaining.hp);
+                self.mp = Some(remaining.mp);
+            }
+            Encounter::Boss => {
+                if self.stage(incident).is_some_and(|s| s.checkpoint) {
+                    self.checkpoint = self.stage + 1;
+                }
+                self.stage += 1;
+                self.encounter = Encounter::MidBoss;
+                let recover = |now: i16, max: i16| {
+                    let lost = (max - now.max(0)) as i32;
+                    (now.max(0) + (lost * incident.recover_percent as i32 / 100) as i16).min(max)
+                };
+                self.hp = Some(recover(remaining.hp, max.hp));
+                self.mp = Some(recover(remaining.mp, max.mp));
+            }
+        }
+        self
+    }
+
+    /// Go back to the last checkpoint with full HP and MP
+    pub fn lose(&mut self) -> &mut Self {
+        self.stage = self.checkpoint;
+        self.encounter = Encounter::MidBoss;
+        self.hp = None;
+        self.mp = None;
+        self.defeats += 1;
+        self
+    }
+}
+
+impl BattleData {
+    /// Start the player with the HP and MP carried over in the run
+    pub fn carry_over(&mut self, run: &IncidentRun) -> &mut Self {
+        let player = &mut self.player_mut().charabase;
+        if let Some(hp) = run.hp {
+            player.hp = hp.min(player.hp);
+        }
+        if let Some(mp) = run.mp {
+            player.mp = mp.min(player.mp);
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::PlayMode;
+    use crate::rpg_core::tests::chara;
+    use uuid::Uuid;
+
+    fn now() -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(2026, 10, 19)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+    }
+
+    fn stage(boss: &str, checkpoint: bool) -> Stage {
+        Stage {
+            name: boss.to_string(),
+            mid_boss: format!("{}_mid", boss),
+            boss: boss.to_string(),
+            level: 5,
+            checkpoint,
+            field: None,
+        }
+    }
+
+    fn incident() -> Incident {
+        Incident {
+            id: "scarlet".to_string(),
+            name: "紅霧異変".to_string(),
+            description: String::new(),
+            recover_percent: 50,
+            stage: vec![stage("cirno", true), stage("remilia", false)],
+        }
+    }
+
+    fn status(hp: i16, mp: i16) -> CharaBase {
+        let mut charabase = chara("reimu", 100).charabase;
+        charabase.hp = hp;
+        charabase.mp = mp;
+        charabase
+    }
+
+    #[test]
+    fn boss_follows_the_mid_boss_without_recovery() {
+        let incident = incident();
+        let mut run = IncidentRun::new(&incident, now());
+        assert_eq!(run.enemy(&incident), Some("cirno_mid"));
+
+        run.win(&incident, &status(40, 70), &status(100, 100));
+        assert_eq!((run.stage, run.encounter), (0, Encounter::Boss));
+        assert_eq!((run.hp, run.mp), (Some(40), Some(70)));
+        assert_eq!(run.enemy(&incident), Some("cirno"));
+    }
+
+    #[test]
+    fn clearing_a_stage_recovers_part_of_the_lost_status() {
+        let incident = incident();
+        let mut run = IncidentRun::new(&incident, now());
+        run.win(&incident, &status(40, 70), &status(100, 100));
+        run.win(&incident, &status(-10, 80), &status(100, 100));
+
+        assert_eq!((run.stage, run.encounter), (1, Encounter::MidBoss));
+        // A knocked out status counts from 0
+        assert_eq!((run.hp, run.mp), (Some(50), Some(90)));
+        assert_eq!(run.checkpoint, 1);
+        assert_eq!(run.enemy(&incident), Some("remilia_mid"));
+    }
+
+    #[test]
+    fn defeat_restarts_from_the_checkpoint() {
+        let incident = incident();
+        let mut run = IncidentRun::new(&incident, now());
+        run.win(&incident, &status(40, 70), &status(100, 100));
+        run.lose();
+        assert_eq!((run.stage, run.encounter, run.defeats), (0, Encounter::MidBoss, 1));
+
+        run.win(&incident, &status(40, 70), &status(100, 100))
+            .win(&incident, &status(40, 70), &status(100, 100))
+            .win(&incident, &status(40, 70), &status(100, 100))
+            .lose();
+        assert_eq!((run.stage, run.encounter, run.defeats), (1, Encounter::MidBoss, 2));
+        assert_eq!((run.hp, run.mp), (None, None));
+        // Defeats don't reset the time
+        assert_eq!(run.elapsed(now() + Duration::minutes(30)), Duration::minutes(30));
+    }
+
+    #[test]
+    fn winning_the_last_boss_clears_the_incident() {
+        let incident = incident();
+        let mut run = IncidentRun::new(&incident, now());
+        for _ in 0..3 {
+            run.win(&incident, &status(100, 100), &status(100, 100));
+        }
+        assert!(!run.is_cleared(&incident));
+        run.win(&incident, &status(100, 100), &status(100, 100));
+        assert!(run.is_cleared(&incident));
+        assert_eq!(run.enemy(&incident), None);
+    }
+
+    #[test]
+    fn carried_status_never_exceeds_the_max() {
+        let mut run = IncidentRun::new(&incident(), now());
+        let mut battle = BattleData::new(
+            Uuid::new_v4(),
+            chara("reimu", 100),
+            chara("cirno", 100),
+            PlayMode::Simple,
+            chrono::Local::now().naive_local(),
+            0,
+        );
+        let max = battle.player().charabase;
+        run.hp = Some(max.hp + 50);
+        run.mp = Some(10);
+        battle.carry_over(&run);
+        assert_eq!(battle.player().charabase.hp, max.hp);
+        assert_eq!(battle.player().charabase.mp, 10);
+    }
+}
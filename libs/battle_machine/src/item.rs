@@ -0,0 +1,129 @@
+use crate::event::BattleEvent;
+use crate::rpg_core::{BattleData, StatusCharaType};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// `item/{id}.toml`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ItemConfig {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub effect: ItemEffect,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ItemEffect {
+    Heal { hp: i16 },
+    RecoverMp { mp: i16 },
+    /// Used outside battles, see [Stamina](crate::stamina::Stamina)
+    RestoreStamina { stamina: u32 },
+}
+
+impl ItemEffect {
+    /// Whether the item can be used in a battle
+    pub fn is_battle(&self) -> bool {
+        !matches!(self, ItemEffect::RestoreStamina { .. })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ItemKind {
+    Item,
+    Equipment,
+}
+
+/// `shop/*.toml`
+/// example:
+/// ```toml
+/// [[entry]]
+/// id = "potion"
+/// kind = "Item"
+/// price = 50
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ShopCatalog {
+    #[serde(default)]
+    pub entry: Vec<ShopEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShopEntry {
+    /// `item/{id}.toml` or the equipment id
+    pub id: String,
+    pub kind: ItemKind,
+    pub price: u32,
+}
+
+impl ItemKind {
+    pub fn try_from_value(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "Item" => Ok(Self::Item),
+            "Equipment" => Ok(Self::Equipment),
+            _ => Err(anyhow::anyhow!(format!("No match {}", value))),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Item => "Item",
+            Self::Equipment => "Equipment",
+        }
+    }
+}
+
+impl ItemConfig {
+    pub async fn items_new<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<Self>> {
+        let files = thrpg_utils::dir_files(path.as_ref()).await?;
+        let mut vec = Vec::new();
+        for file_path in files {
+            vec.push(thrpg_utils::read_to_toml(file_path).await?);
+        }
+        Ok(vec)
+    }
+
+    /// Create a structure from file names
+    /// `item/{file names}.toml`
+    pub async fn from_file_name<T: ToString>(name: T) -> anyhow::Result<Self> {
+        let content = thrpg_utils::read_to_toml(format!("item/{}.toml", name.to_string())).await?;
+        Ok(content)
+    }
+}
+
+impl ShopCatalog {
+    /// Read and merge every file in the directory
+    pub async fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let mut catalog = Self::default();
+        for file_path in thrpg_utils::dir_files(path.as_ref()).await? {
+            let other: Self = thrpg_utils::read_to_toml(file_path).await?;
+            catalog.entry.extend(other.entry);
+        }
+        Ok(catalog)
+    }
+
+    pub fn find(&self, id: &str) -> Option<&ShopEntry> {
+        self.entry.iter().find(|e| e.id == id)
+    }
+}
+
+impl BattleData {
+    /// Use the item on the side
+    /// The turn advances by 1 when this function is called
+    pub fn use_item(&mut self, side: StatusCharaType, item: &ItemConfig) -> &mut Self {
+        let chara = match side {
+            StatusCharaType::Player => self.player_mut(),
+            StatusCharaType::Enemy => self.enemy_mut(),
+        };
+        match item.effect {
+            ItemEffect::Heal { hp } => chara.charabase.hp += hp,
+            ItemEffect::RecoverMp { mp } => chara.charabase.mp += mp,
+            ItemEffect::RestoreStamina { .. } => {}
+        }
+        self.push_event(BattleEvent::ItemUsed {
+            side,
+            item: item.id.clone(),
+        });
+        self.add_turn();
+        self
+    }
+}
@@ -1,4 +1,18 @@
+pub mod achievement;
+pub mod assist;
+pub mod bond;
 pub mod builder;
 pub mod chara;
+pub mod equipment;
+pub mod event;
+pub mod field;
+pub mod incident;
+pub mod item;
 pub mod rpg_core;
+pub mod stamina;
 pub mod mode;
+pub mod quest;
+pub mod rating;
+pub mod reward;
+pub mod tournament;
+pub mod unlock;
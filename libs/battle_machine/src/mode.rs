@@ -4,6 +4,8 @@ use serde::{Deserialize, Serialize};
 pub enum PlayMode {
     Simple,
     Raid,
+    /// User vs user
+    Duel,
     Story { id: String },
 }
 
@@ -18,6 +20,7 @@ impl PlayMode {
         match value {
             "Simple" => Ok(Self::Simple),
             "Raid" => Ok(Self::Raid),
+            "Duel" => Ok(Self::Duel),
             _ => Err(anyhow::anyhow!(format!("No match {}", value))),
         }
     }
@@ -27,6 +30,14 @@ impl PlayMode {
             Self::Story { id: e } => e.as_str(),
             Self::Simple => "Simple",
             Self::Raid => "Raid",
+            Self::Duel => "Duel",
+        }
+    }
+    /// Same as `as_str` but every story is `Story`
+    pub fn kind(&self) -> &str {
+        match self {
+            Self::Story { .. } => "Story",
+            _ => self.as_str(),
         }
     }
     /// get story id
@@ -34,7 +45,62 @@ impl PlayMode {
         match self {
             Self::Simple => None,
             Self::Raid => None,
+            Self::Duel => None,
             Self::Story { id: a } => Some(a),
         }
     }
 }
+
+/// Default difficulty of the guild, set by `/settings`
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Hash, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn try_from_value(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "Easy" => Ok(Self::Easy),
+            "Normal" => Ok(Self::Normal),
+            "Hard" => Ok(Self::Hard),
+            _ => Err(anyhow::anyhow!(format!("No match {}", value))),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Easy => "Easy",
+            Self::Normal => "Normal",
+            Self::Hard => "Hard",
+        }
+    }
+
+    /// Level of a random enemy against a character of `level`, at least 1
+    pub fn enemy_level(&self, level: i64) -> i64 {
+        let percent = match self {
+            Self::Easy => 75,
+            Self::Normal => 100,
+            Self::Hard => 125,
+        };
+        (level * percent / 100).max(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn difficulty_scales_the_enemy_level() {
+        assert_eq!(Difficulty::default().enemy_level(8), 8);
+        assert_eq!(Difficulty::Easy.enemy_level(8), 6);
+        assert_eq!(Difficulty::Hard.enemy_level(8), 10);
+        // A new character never meets an enemy below level 1
+        assert_eq!(Difficulty::Easy.enemy_level(1), 1);
+        assert_eq!(Difficulty::try_from_value("Hard").unwrap(), Difficulty::Hard);
+        assert!(Difficulty::try_from_value("Lunatic").is_err());
+    }
+}
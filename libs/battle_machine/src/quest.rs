@@ -0,0 +1,292 @@
+use crate::chara::SpeciesType;
+use crate::event::ProgressEvent;
+use crate::reward::Reward;
+use chrono::{Datelike, NaiveDate};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Quests read from `quest/*.toml` and the `quest.toml` of Contents extensions
+/// example:
+/// ```toml
+/// daily_count = 3
+/// weekly_count = 1
+///
+/// [[quest]]
+/// id = "fairy_hunt"
+/// name = "妖精退治"
+/// rotation = "Daily"
+/// objective = { DefeatSpecies = { species = "Fairy", count = 5 } }
+/// rewards = [{ Gold = { amount = 100 } }]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QuestConfig {
+    /// Number of daily quests offered each day
+    #[serde(default)]
+    pub daily_count: usize,
+    /// Number of weekly quests offered each week
+    #[serde(default)]
+    pub weekly_count: usize,
+    #[serde(default)]
+    pub quest: Vec<Quest>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quest {
+    pub id: String,
+    pub name: String,
+    pub rotation: QuestRotation,
+    pub objective: QuestObjective,
+    #[serde(default)]
+    pub rewards: Vec<Reward>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum QuestRotation {
+    Daily,
+    Weekly,
+    /// Always offered, can be completed once
+    Permanent,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuestObjective {
+    /// `description` of `Human` and `Yokai` is ignored
+    DefeatSpecies { species: SpeciesType, count: u32 },
+    DefeatEnemy { enemy: String, count: u32 },
+    /// Any item if `item` is not set
+    UseItem { item: Option<String>, count: u32 },
+    /// Finish the story or incident with the id
+    ClearStory { story: String },
+}
+
+impl QuestConfig {
+    /// Read and merge every file
+    pub async fn load(paths: Vec<PathBuf>) -> anyhow::Result<Self> {
+        let mut config = Self::default();
+        for path in paths {
+            let other: Self = thrpg_utils::read_to_toml(path).await?;
+            config.daily_count = config.daily_count.max(other.daily_count);
+            config.weekly_count = config.weekly_count.max(other.weekly_count);
+            config.quest.extend(other.quest);
+        }
+        Ok(config)
+    }
+
+    /// Files in `quest/` and `quest.toml` in the directories of Contents extensions
+    pub async fn load_with_extensions<P: AsRef<Path>>(
+        dir: P,
+        extension_dirs: Vec<PathBuf>,
+    ) -> anyhow::Result<Self> {
+        let mut paths = thrpg_utils::dir_files(dir.as_ref()).await.unwrap_or_default();
+        paths.extend(
+            extension_dirs
+                .into_iter()
+                .map(|dir| dir.join("quest.toml"))
+                .filter(|path| path.is_file()),
+        );
+        Self::load(paths).await
+    }
+
+    /// Quests offered on the day, with the period they belong to
+    /// Everyone gets the same rotation on the same day
+    pub fn active_quests(&self, today: NaiveDate) -> Vec<(&Quest, String)> {
+        let mut active = Vec::new();
+        for (rotation, count) in [
+            (QuestRotation::Daily, self.daily_count),
+            (QuestRotation::Weekly, self.weekly_count),
+        ] {
+            let period = rotation.period(today);
+            let mut pool: Vec<&Quest> = self.quest.iter().filter(|q| q.rotation == rotation).collect();
+            let seed = period.bytes().fold(0u64, |acc, b| acc.wrapping_mul(31).wrapping_add(b as u64));
+            pool.shuffle(&mut StdRng::seed_from_u64(seed));
+            active.extend(pool.into_iter().take(count).map(|q| (q, period.clone())));
+        }
+        let permanent = QuestRotation::Permanent.period(today);
+        active.extend(
+            self.quest
+                .iter()
+                .filter(|q| q.rotation == QuestRotation::Permanent)
+                .map(|q| (q, permanent.clone())),
+        );
+        active
+    }
+}
+
+impl QuestRotation {
+    /// Progress is kept per period
+    pub fn period(&self, today: NaiveDate) -> String {
+        match self {
+            QuestRotation::Daily => today.format("%Y-%m-%d").to_string(),
+            QuestRotation::Weekly => {
+                let week = today.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            QuestRotation::Permanent => "permanent".to_string(),
+        }
+    }
+}
+
+impl QuestObjective {
+    pub fn goal(&self) -> u32 {
+        match self {
+            QuestObjective::DefeatSpecies { count, .. } => *count,
+            QuestObjective::DefeatEnemy { count, .. } => *count,
+            QuestObjective::UseItem { count, .. } => *count,
+            QuestObjective::ClearStory { .. } => 1,
+        }
+    }
+
+    /// Progress made by the event
+    pub fn progress(&self, event: &ProgressEvent) -> u32 {
+        match (self, event) {
+            (QuestObjective::DefeatSpecies { species, .. }, ProgressEvent::EnemyDefeated { battle }) => {
+                species.same_kind(&battle.enemy().meta.species_type) as u32
+            }
+            (QuestObjective::DefeatEnemy { enemy, .. }, ProgressEvent::EnemyDefeated { battle }) => {
+                (enemy == &battle.enemy().meta.name) as u32
+            }
+            (QuestObjective::ClearStory { story }, ProgressEvent::StoryCleared { story: cleared }) => {
+                (story == cleared) as u32
+            }
+            (QuestObjective::UseItem { item, .. }, ProgressEvent::ItemUsed { item: used }) => {
+                item.as_ref().is_none_or(|i| i == used) as u32
+            }
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::PlayMode;
+    use crate::rpg_core::{tests::chara, BattleData};
+    use uuid::Uuid;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn quest(id: &str, rotation: QuestRotation) -> Quest {
+        Quest {
+            id: id.to_string(),
+            name: id.to_string(),
+            rotation,
+            objective: QuestObjective::ClearStory {
+                story: "scarlet".to_string(),
+            },
+            rewards: vec![],
+        }
+    }
+
+    fn config() -> QuestConfig {
+        let mut quests: Vec<Quest> = (0..10)
+            .map(|i| quest(&format!("daily{}", i), QuestRotation::Daily))
+            .collect();
+        quests.extend((0..10).map(|i| quest(&format!("weekly{}", i), QuestRotation::Weekly)));
+        quests.push(quest("permanent", QuestRotation::Permanent));
+        QuestConfig {
+            daily_count: 3,
+            weekly_count: 1,
+            quest: quests,
+        }
+    }
+
+    fn ids(active: &[(&Quest, String)], rotation: QuestRotation) -> Vec<String> {
+        active
+            .iter()
+            .filter(|(q, _)| q.rotation == rotation)
+            .map(|(q, _)| q.id.clone())
+            .collect()
+    }
+
+    #[test]
+    fn daily_period_resets_at_midnight() {
+        assert_eq!(QuestRotation::Daily.period(date(2026, 10, 19)), "2026-10-19");
+        assert_ne!(
+            QuestRotation::Daily.period(date(2026, 10, 19)),
+            QuestRotation::Daily.period(date(2026, 10, 20))
+        );
+    }
+
+    #[test]
+    fn weekly_period_resets_on_monday_of_the_iso_week() {
+        // 2026-10-19 is a Monday
+        let monday = QuestRotation::Weekly.period(date(2026, 10, 19));
+        assert_eq!(monday, "2026-W43");
+        assert_eq!(QuestRotation::Weekly.period(date(2026, 10, 25)), monday);
+        assert_eq!(QuestRotation::Weekly.period(date(2026, 10, 26)), "2026-W44");
+        // The week of new year belongs to the year of its Thursday
+        assert_eq!(QuestRotation::Weekly.period(date(2025, 12, 29)), "2026-W01");
+        assert_eq!(QuestRotation::Permanent.period(date(2026, 10, 19)), "permanent");
+    }
+
+    #[test]
+    fn rotation_is_the_same_for_the_whole_period() {
+        let config = config();
+        let monday = config.active_quests(date(2026, 10, 19));
+        let again = config.active_quests(date(2026, 10, 19));
+        let tuesday = config.active_quests(date(2026, 10, 20));
+
+        assert_eq!(ids(&monday, QuestRotation::Daily).len(), 3);
+        assert_eq!(ids(&monday, QuestRotation::Daily), ids(&again, QuestRotation::Daily));
+        assert_eq!(ids(&monday, QuestRotation::Weekly), ids(&tuesday, QuestRotation::Weekly));
+        assert_eq!(ids(&monday, QuestRotation::Permanent), ["permanent"]);
+        let periods: Vec<&str> = tuesday.iter().map(|(_, period)| period.as_str()).collect();
+        assert_eq!(periods, ["2026-10-20", "2026-10-20", "2026-10-20", "2026-W43", "permanent"]);
+
+        // Some day of the month offers other daily quests
+        let rotations: Vec<Vec<String>> = (1..=28)
+            .map(|day| ids(&config.active_quests(date(2026, 10, day)), QuestRotation::Daily))
+            .collect();
+        assert!(rotations.iter().any(|r| r != &rotations[0]));
+    }
+
+    #[test]
+    fn objectives_count_only_matching_events() {
+        let mut fairy = chara("チルノ", 100);
+        fairy.meta.species_type = SpeciesType::Fairy;
+        let battle = BattleData::new(
+            Uuid::new_v4(),
+            chara("reimu", 100),
+            fairy,
+            PlayMode::Simple,
+            chrono::Local::now().naive_local(),
+            0,
+        );
+        let defeated = ProgressEvent::EnemyDefeated { battle: &battle };
+
+        let species = QuestObjective::DefeatSpecies {
+            species: SpeciesType::Fairy,
+            count: 5,
+        };
+        let witch = QuestObjective::DefeatSpecies {
+            species: SpeciesType::Witch,
+            count: 5,
+        };
+        let enemy = QuestObjective::DefeatEnemy {
+            enemy: "チルノ".to_string(),
+            count: 2,
+        };
+        assert_eq!((species.goal(), species.progress(&defeated)), (5, 1));
+        assert_eq!(witch.progress(&defeated), 0);
+        assert_eq!(enemy.progress(&defeated), 1);
+
+        let any_item = QuestObjective::UseItem { item: None, count: 3 };
+        let potion = QuestObjective::UseItem {
+            item: Some("potion".to_string()),
+            count: 3,
+        };
+        let ether = ProgressEvent::ItemUsed { item: "ether" };
+        assert_eq!((any_item.progress(&ether), potion.progress(&ether)), (1, 0));
+        assert_eq!(potion.progress(&defeated), 0);
+
+        let story = QuestObjective::ClearStory {
+            story: "scarlet".to_string(),
+        };
+        assert_eq!(story.goal(), 1);
+        assert_eq!(story.progress(&ProgressEvent::StoryCleared { story: "scarlet" }), 1);
+        assert_eq!(story.progress(&ProgressEvent::StoryCleared { story: "spring" }), 0);
+    }
+}
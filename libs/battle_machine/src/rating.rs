@@ -0,0 +1,204 @@
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+/// Constants of the Glicko rating calculation
+/// Adjust these to change how fast ratings move
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct RatingConstants {
+    /// Rating of a user who has never played a ranked match
+    pub initial_rating: f64,
+    /// Rating deviation of a user who has never played a ranked match
+    /// This is also the upper limit of the deviation
+    pub initial_deviation: f64,
+    /// Lower limit of the deviation so that ratings never freeze
+    pub min_deviation: f64,
+    /// How much the deviation grows for each rating period without matches
+    pub deviation_growth: f64,
+}
+
+impl Default for RatingConstants {
+    fn default() -> Self {
+        Self {
+            initial_rating: 1500.0,
+            initial_deviation: 350.0,
+            min_deviation: 30.0,
+            deviation_growth: 34.6,
+        }
+    }
+}
+
+/// Rating and rating deviation of a user
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct Rating {
+    pub rating: f64,
+    pub deviation: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum MatchResult {
+    Win,
+    Lose,
+    Draw,
+}
+
+impl MatchResult {
+    pub const fn score(&self) -> f64 {
+        match self {
+            MatchResult::Win => 1.0,
+            MatchResult::Lose => 0.0,
+            MatchResult::Draw => 0.5,
+        }
+    }
+
+    /// The result seen from the opponent
+    pub const fn reverse(&self) -> Self {
+        match self {
+            MatchResult::Win => MatchResult::Lose,
+            MatchResult::Lose => MatchResult::Win,
+            MatchResult::Draw => MatchResult::Draw,
+        }
+    }
+}
+
+impl Rating {
+    pub fn new(rating: f64, deviation: f64) -> Self {
+        Self { rating, deviation }
+    }
+
+    /// Rating of a user who has never played a ranked match
+    pub fn initial(constants: &RatingConstants) -> Self {
+        Self::new(constants.initial_rating, constants.initial_deviation)
+    }
+}
+
+const Q: f64 = std::f64::consts::LN_10 / 400.0;
+
+fn g(deviation: f64) -> f64 {
+    1.0 / (1.0 + 3.0 * Q * Q * deviation * deviation / (PI * PI)).sqrt()
+}
+
+/// Expected score of `player` against `opponent`
+pub fn expected_score(player: &Rating, opponent: &Rating) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-g(opponent.deviation) * (player.rating - opponent.rating) / 400.0))
+}
+
+/// Deviation grown by the rating periods in which the user did not play
+pub fn inactive_deviation(rating: &Rating, idle_periods: u32, constants: &RatingConstants) -> f64 {
+    (rating.deviation.powi(2) + constants.deviation_growth.powi(2) * idle_periods as f64)
+        .sqrt()
+        .min(constants.initial_deviation)
+}
+
+/// Calculate the new rating of `player` after one match against `opponent`
+/// `idle_periods` is the number of rating periods since the last match of `player`
+pub fn update_rating(
+    player: &Rating,
+    opponent: &Rating,
+    result: MatchResult,
+    idle_periods: u32,
+    constants: &RatingConstants,
+) -> Rating {
+    let deviation = inactive_deviation(player, idle_periods, constants);
+    let current = Rating::new(player.rating, deviation);
+    let g_opponent = g(opponent.deviation);
+    let expected = expected_score(&current, opponent);
+    let d_squared = 1.0 / (Q * Q * g_opponent * g_opponent * expected * (1.0 - expected));
+    let denominator = 1.0 / (deviation * deviation) + 1.0 / d_squared;
+
+    Rating {
+        rating: player.rating + Q / denominator * g_opponent * (result.score() - expected),
+        deviation: (1.0 / denominator)
+            .sqrt()
+            .clamp(constants.min_deviation, constants.initial_deviation),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 1e-9;
+
+    #[test]
+    fn expected_score_of_equal_ratings_is_even() {
+        let a = Rating::new(1500.0, 200.0);
+        assert!((expected_score(&a, &a) - 0.5).abs() < EPSILON);
+    }
+
+    #[test]
+    fn expected_scores_add_up_to_one() {
+        let strong = Rating::new(1700.0, 100.0);
+        let weak = Rating::new(1400.0, 100.0);
+        let e = expected_score(&strong, &weak);
+        assert!(e > 0.5);
+        assert!((e + expected_score(&weak, &strong) - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn uncertain_opponent_moves_expectation_towards_even() {
+        let player = Rating::new(1700.0, 100.0);
+        let certain = Rating::new(1500.0, 30.0);
+        let uncertain = Rating::new(1500.0, 350.0);
+        assert!(expected_score(&player, &uncertain) < expected_score(&player, &certain));
+    }
+
+    #[test]
+    fn win_and_loss_are_symmetric() {
+        let constants = RatingConstants::default();
+        let a = Rating::new(1500.0, 200.0);
+        let b = Rating::new(1500.0, 200.0);
+        let win = update_rating(&a, &b, MatchResult::Win, 0, &constants);
+        let lose = update_rating(&b, &a, MatchResult::Lose, 0, &constants);
+        assert!(win.rating > a.rating);
+        assert!(lose.rating < b.rating);
+        assert!((win.rating - a.rating + (lose.rating - b.rating)).abs() < EPSILON);
+        assert!((win.deviation - lose.deviation).abs() < EPSILON);
+    }
+
+    #[test]
+    fn draw_between_equals_keeps_the_rating() {
+        let constants = RatingConstants::default();
+        let a = Rating::new(1500.0, 200.0);
+        let draw = update_rating(&a, &a, MatchResult::Draw, 0, &constants);
+        assert!((draw.rating - a.rating).abs() < EPSILON);
+    }
+
+    #[test]
+    fn draw_against_stronger_opponent_gains() {
+        let constants = RatingConstants::default();
+        let weak = Rating::new(1400.0, 100.0);
+        let strong = Rating::new(1700.0, 100.0);
+        assert!(update_rating(&weak, &strong, MatchResult::Draw, 0, &constants).rating > weak.rating);
+        assert!(update_rating(&strong, &weak, MatchResult::Draw, 0, &constants).rating < strong.rating);
+    }
+
+    #[test]
+    fn deviation_shrinks_with_matches() {
+        let constants = RatingConstants {
+            min_deviation: 100.0,
+            ..RatingConstants::default()
+        };
+        let opponent = Rating::initial(&constants);
+        let mut rating = Rating::initial(&constants);
+        for i in 0..200 {
+            let result = if i % 2 == 0 { MatchResult::Win } else { MatchResult::Lose };
+            let updated = update_rating(&rating, &opponent, result, 0, &constants);
+            assert!(updated.deviation <= rating.deviation);
+            rating = updated;
+        }
+        // Stops at the lower limit
+        assert!((rating.deviation - constants.min_deviation).abs() < EPSILON);
+    }
+
+    #[test]
+    fn inactive_deviation_grows_up_to_the_initial_deviation() {
+        let constants = RatingConstants::default();
+        let rating = Rating::new(1500.0, 50.0);
+        assert!((inactive_deviation(&rating, 0, &constants) - 50.0).abs() < EPSILON);
+        let one = inactive_deviation(&rating, 1, &constants);
+        let ten = inactive_deviation(&rating, 10, &constants);
+        assert!(50.0 < one && one < ten);
+        assert!((one - (50f64.powi(2) + constants.deviation_growth.powi(2)).sqrt()).abs() < EPSILON);
+        assert_eq!(inactive_deviation(&rating, 1000, &constants), constants.initial_deviation);
+    }
+}
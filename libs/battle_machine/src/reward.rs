@@ -0,0 +1,76 @@
+use crate::rpg_core::BattleData;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+/// `[[drop]]` in the chara TOML
+/// example:
+/// ```toml
+/// [[drop]]
+/// probability = 0.3
+/// reward = { Item = { id = "potion", quantity = 1 } }
+/// ```
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct DropEntry {
+    /// 0.0 ~ 1.0
+    pub probability: f32,
+    pub reward: Reward,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Reward {
+    /// `item/{id}.toml`
+    Item { id: String, quantity: u32 },
+    /// `equipment/{id}.toml`
+    Equipment { id: String },
+    Gold { amount: u32 },
+    /// Unlocks `chara/{chara}.toml`
+    UnlockToken { chara: String },
+}
+
+/// Everything earned by defeating an enemy
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct RewardSummary {
+    pub exp: u32,
+    pub level_before: u32,
+    pub level_after: u32,
+    pub gold: u32,
+    pub drops: Vec<Reward>,
+}
+
+impl RewardSummary {
+    pub fn is_level_up(&self) -> bool {
+        self.level_after > self.level_before
+    }
+
+    /// Gold of the battle and gold drops
+    pub fn total_gold(&self) -> u32 {
+        self.gold
+            + self
+                .drops
+                .iter()
+                .map(|d| match d {
+                    Reward::Gold { amount } => *amount,
+                    _ => 0,
+                })
+                .sum::<u32>()
+    }
+}
+
+impl BattleData {
+    /// RNG of this battle
+    /// The same battle at the same turn always rolls the same
+    pub fn rng(&self) -> StdRng {
+        StdRng::seed_from_u64(self.rng_seed().wrapping_add(self.elapsed_turns() as u64))
+    }
+
+    /// Roll the drop table of the enemy
+    pub fn resolve_drops(&self) -> Vec<Reward> {
+        let mut rng = self.rng();
+        self.enemy()
+            .drop
+            .iter()
+            .filter(|entry| rng.gen::<f32>() < entry.probability)
+            .map(|entry| entry.reward.clone())
+            .collect()
+    }
+}
@@ -1,4 +1,7 @@
-use crate::chara::{CharaConfig, LevelupExpType, LuckyLevel, SkillType};
+use crate::assist::Assist;
+use crate::chara::{AbnormalState, CharaConfig, LevelupExpType, LuckyLevel, SkillType};
+use crate::event::BattleEvent;
+use crate::field::Field;
 use crate::mode::PlayMode;
 use anyhow::Context;
 use chrono::prelude::NaiveDateTime;
@@ -17,6 +20,42 @@ pub struct BattleData {
     elapsed_turns: u32,
     start_time: NaiveDateTime,
     is_running: bool,
+    player_controller: Controller,
+    enemy_controller: Controller,
+    #[serde(default)]
+    player_immunity: Vec<AbnormalState>,
+    #[serde(default)]
+    enemy_immunity: Vec<AbnormalState>,
+    #[serde(default)]
+    player_states: Vec<AbnormalState>,
+    #[serde(default)]
+    enemy_states: Vec<AbnormalState>,
+    #[serde(default = "rand::random")]
+    rng_seed: u64,
+    #[serde(default)]
+    events: Vec<BattleEvent>,
+    #[serde(default)]
+    pub(crate) assist: Option<Assist>,
+    /// Damage the player side can still absorb
+    #[serde(default)]
+    pub(crate) player_shield: i16,
+    #[serde(default)]
+    pub(crate) field: Option<Field>,
+    /// Side that acts on even turns, fixed when the battle is built
+    #[serde(default)]
+    first_side: Option<StatusCharaType>,
+}
+
+/// Who operates a side of the battle
+#[derive(
+    Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+pub enum Controller {
+    /// Operated by the bot
+    #[default]
+    Computer,
+    /// Operated by the Discord user with this id
+    User(u64),
 }
 
 impl TryFrom<Model> for CharaConfig {
@@ -55,6 +94,7 @@ impl BattleData {
         start_time: NaiveDateTime,
         elapsed_turns: u32,
     ) -> Self {
+        let first_side = Some(Self::faster_side(&player_data, &enemy_data));
         Self {
             uuid,
             player_data,
@@ -63,11 +103,92 @@ impl BattleData {
             start_time,
             elapsed_turns,
             is_running: false,
+            player_controller: Controller::default(),
+            enemy_controller: Controller::default(),
+            player_immunity: Vec::new(),
+            enemy_immunity: Vec::new(),
+            player_states: Vec::new(),
+            enemy_states: Vec::new(),
+            rng_seed: rand::random(),
+            events: Vec::new(),
+            assist: None,
+            player_shield: 0,
+            field: None,
+            first_side,
+        }
+    }
+
+    /// Set the abnormal states each side is protected from
+    pub fn immunities(
+        &mut self,
+        player: Vec<AbnormalState>,
+        enemy: Vec<AbnormalState>,
+    ) -> &mut Self {
+        self.player_immunity = player;
+        self.enemy_immunity = enemy;
+        self
+    }
+
+    /// The side is not affected by the abnormal state
+    pub fn is_immune(&self, side: StatusCharaType, state: &AbnormalState) -> bool {
+        match side {
+            StatusCharaType::Player => self.player_immunity.contains(state),
+            StatusCharaType::Enemy => self.enemy_immunity.contains(state),
+        }
+    }
+
+    /// Put the abnormal state on the side, unless it is immune
+    /// Returns `false` if the side is immune
+    pub fn apply_abnormal_state(&mut self, side: StatusCharaType, state: AbnormalState) -> bool {
+        if self.is_immune(side, &state) {
+            return false;
         }
+        let states = match side {
+            StatusCharaType::Player => &mut self.player_states,
+            StatusCharaType::Enemy => &mut self.enemy_states,
+        };
+        if !states.contains(&state) {
+            states.push(state.clone());
+            self.events.push(BattleEvent::AbnormalState { side, state });
+        }
+        true
+    }
+
+    /// Abnormal states the side is in
+    pub fn abnormal_states(&self, side: StatusCharaType) -> &Vec<AbnormalState> {
+        match side {
+            StatusCharaType::Player => &self.player_states,
+            StatusCharaType::Enemy => &self.enemy_states,
+        }
+    }
+
+    /// Set who operates each side
+    pub fn controllers(&mut self, player: Controller, enemy: Controller) -> &mut Self {
+        self.player_controller = player;
+        self.enemy_controller = enemy;
+        self
+    }
+
+    /// get player side controller
+    pub fn player_controller(&self) -> Controller {
+        self.player_controller
+    }
+
+    /// get enemy side controller
+    pub fn enemy_controller(&self) -> Controller {
+        self.enemy_controller
+    }
+
+    /// Both sides are operated by users
+    pub fn is_pvp(&self) -> bool {
+        matches!(self.player_controller, Controller::User(_))
+            && matches!(self.enemy_controller, Controller::User(_))
     }
 
     /// Advance the elapsed turn
+    /// The field acts on the side whose turn ends
     pub fn add_turn(&mut self) -> &mut Self {
+        self.field_turn(self.turn_side());
         self.elapsed_turns += 1;
         self
     }
@@ -77,30 +198,45 @@ impl BattleData {
         self
     }
 
-    fn _turn<'a>(player: &'a CharaConfig, enemy: &'a CharaConfig) -> Vec<&'a CharaConfig> {
-        let mut vec: Vec<&CharaConfig> = Vec::new();
+    /// The faster side moves first, the player on a tie
+    fn faster_side(player: &CharaConfig, enemy: &CharaConfig) -> StatusCharaType {
         if player.charabase.speed >= enemy.charabase.speed {
-            vec.push(player);
-            vec.push(enemy);
+            StatusCharaType::Player
         } else {
-            vec.push(enemy);
-            vec.push(player);
-        };
-        vec
+            StatusCharaType::Enemy
+        }
     }
+
     /// Functions tat manipulate turnsh
     pub fn turn(&self) -> &CharaConfig {
-        let turn_info = Self::_turn(&self.player_data, &self.enemy_data);
-        // If it exceeds the length of `vec`, it will return to the first element of the array due
-        // to the` Cycle` type.
-        // Therefore, it is unlikely that it will be `None` unless the contents of` Vec` are empty.
-        let info = turn_info
-            .into_iter()
-            .cycle()
-            .nth(self.elapsed_turns as usize)
-            .unwrap();
+        match self.turn_side() {
+            StatusCharaType::Player => &self.player_data,
+            StatusCharaType::Enemy => &self.enemy_data,
+        }
+    }
+
+    /// The side whose turn it is
+    /// The order is decided by the speed when the battle is built, so buffs during the battle don't change it
+    pub fn turn_side(&self) -> StatusCharaType {
+        // Battles saved before the order was stored use the current speed
+        let first_side = self
+            .first_side
+            .unwrap_or_else(|| Self::faster_side(&self.player_data, &self.enemy_data));
+        let player_turn =
+            self.elapsed_turns.is_multiple_of(2) == (first_side == StatusCharaType::Player);
+        if player_turn {
+            StatusCharaType::Player
+        } else {
+            StatusCharaType::Enemy
+        }
+    }
 
-        info
+    /// The controller of the side whose turn it is
+    pub fn turn_controller(&self) -> Controller {
+        match self.turn_side() {
+            StatusCharaType::Player => self.player_controller,
+            StatusCharaType::Enemy => self.enemy_controller,
+        }
     }
 
     /// get player data
@@ -113,6 +249,46 @@ impl BattleData {
         &self.enemy_data
     }
 
+    pub(crate) fn player_mut(&mut self) -> &mut CharaConfig {
+        &mut self.player_data
+    }
+
+    pub(crate) fn enemy_mut(&mut self) -> &mut CharaConfig {
+        &mut self.enemy_data
+    }
+
+    /// get playmode
+    pub fn play_mode(&self) -> &PlayMode {
+        &self.play_mode
+    }
+
+    /// Everything that happened in this battle
+    pub fn events(&self) -> &Vec<BattleEvent> {
+        &self.events
+    }
+
+    /// Record an event that happened outside of the damage calculation
+    pub fn push_event(&mut self, event: BattleEvent) -> &mut Self {
+        self.events.push(event);
+        self
+    }
+
+    /// Total damage the side received from attacks
+    pub fn damage_taken(&self, side: StatusCharaType) -> i32 {
+        self.events
+            .iter()
+            .map(|event| match event {
+                BattleEvent::Attack { attacker, damage } if *attacker != side => *damage as i32,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// Seed of [rng](Self::rng)
+    pub fn rng_seed(&self) -> u64 {
+        self.rng_seed
+    }
+
     /// get uuid
     pub fn uuid(&self) -> Uuid {
         self.uuid
@@ -122,9 +298,16 @@ impl BattleData {
     /// The turn advances by 1 when this function is called
     pub fn calculate_player_damage(&mut self) -> &mut Self {
         let mut rng = rand::thread_rng();
-        let player_attack = self.player_data.attack.iter().choose(&mut rng).unwrap();
+        let player_attack = self.player_data.attack.iter().choose(&mut rng).unwrap().clone();
         let to_enemy_damage = self.enemy_data.charabase.hp - player_attack.damage as i16;
         self.enemy_data.charabase.hp = to_enemy_damage;
+        self.events.push(BattleEvent::Attack {
+            attacker: StatusCharaType::Player,
+            damage: player_attack.damage as i16,
+        });
+        if let Some(state) = player_attack.abnormal_state {
+            self.apply_abnormal_state(StatusCharaType::Enemy, state);
+        }
         self.add_turn();
         self
     }
@@ -133,9 +316,20 @@ impl BattleData {
     /// The turn advances by 1 when this function is called
     pub fn calculate_enemy_damage(&mut self) -> &mut Self {
         let mut rng = rand::thread_rng();
-        let enemy_attack = self.enemy_data.attack.iter().choose(&mut rng).unwrap();
-        let to_player_damage = self.player_data.charabase.hp - enemy_attack.damage as i16;
+        let enemy_attack = self.enemy_data.attack.iter().choose(&mut rng).unwrap().clone();
+        // The shield of an assist takes the damage first
+        let absorbed = self.player_shield.min(enemy_attack.damage as i16);
+        self.player_shield -= absorbed;
+        let damage = enemy_attack.damage as i16 - absorbed;
+        let to_player_damage = self.player_data.charabase.hp - damage;
         self.player_data.charabase.hp = to_player_damage;
+        self.events.push(BattleEvent::Attack {
+            attacker: StatusCharaType::Enemy,
+            damage,
+        });
+        if let Some(state) = enemy_attack.abnormal_state {
+            self.apply_abnormal_state(StatusCharaType::Player, state);
+        }
         self.add_turn();
         self
     }
@@ -147,6 +341,9 @@ impl BattleData {
         let enemy_attack = self.enemy_data.attack.iter().choose(&mut rng).unwrap();
         self.player_data.charabase.hp +=
             enemy_attack.damage as i16 - self.player_data.charabase.guard;
+        self.events.push(BattleEvent::Guard {
+            side: StatusCharaType::Enemy,
+        });
         self.add_turn();
         self
     }
@@ -158,6 +355,9 @@ impl BattleData {
         let player_attack = self.player_data.attack.iter().choose(&mut rng).unwrap();
         self.enemy_data.charabase.hp +=
             player_attack.damage as i16 - self.enemy_data.charabase.guard;
+        self.events.push(BattleEvent::Guard {
+            side: StatusCharaType::Player,
+        });
         self.add_turn();
         self
     }
@@ -166,6 +366,11 @@ impl BattleData {
         self.elapsed_turns
     }
 
+    /// When the battle was built
+    pub fn start_time(&self) -> NaiveDateTime {
+        self.start_time
+    }
+
     pub fn is_running(&self) -> bool {
         self.is_running
     }
@@ -181,25 +386,32 @@ impl BattleData {
     }
 
     pub async fn result_battle(&mut self) -> &Self {
-        let turn = self.turn();
-        let damage = if turn == &self.player_data {
-            self.calculate_player_damage()
-        } else {
-            self.calculate_enemy_damage()
+        let damage = match self.turn_side() {
+            StatusCharaType::Player => self.calculate_player_damage(),
+            StatusCharaType::Enemy => self.calculate_enemy_damage(),
         };
         damage
     }
 
     pub async fn result_guard(&mut self) -> &Self {
-        let turn = self.turn();
-        let guard = if turn == &self.player_data {
-            self.guard_player_damage()
-        } else {
-            self.guard_enemy_damage()
+        let guard = match self.turn_side() {
+            StatusCharaType::Player => self.guard_player_damage(),
+            StatusCharaType::Enemy => self.guard_enemy_damage(),
         };
         guard
     }
 
+    /// The side that has won, if the battle is decided
+    pub fn winner(&self) -> Option<StatusCharaType> {
+        if self.enemy_data.charabase.hp <= 0 {
+            Some(StatusCharaType::Player)
+        } else if self.player_data.charabase.hp <= 0 {
+            Some(StatusCharaType::Enemy)
+        } else {
+            None
+        }
+    }
+
     /// Amount of exp earned in battle
     ///
     pub fn calculate_exp(&self, enemy_level: u32, player_level: u32) -> u32 {
@@ -231,6 +443,18 @@ impl BattleData {
         base_exp as u32
     }
 
+    /// Amount of gold earned by defeating the enemy
+    pub fn calculate_gold(&self, enemy_level: u32) -> u32 {
+        let base_gold = 10 + enemy_level * 5 + rand::random::<u8>() as u32 % 10;
+        (base_gold as f32
+            * self
+                .player_data
+                .meta
+                .skill_type
+                .lucky_level()
+                .map_or(1.0, |l| l.lucky_number())) as u32
+    }
+
     /// Find the player level from exp
     pub fn calculate_player_level(&self, exp: f64) -> f64 {
         match &self.player_data.meta.levelup_exp {
@@ -298,7 +522,99 @@ impl BattleData {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum StatusCharaType {
     Enemy,
     Player,
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn chara(name: &str, speed: i16) -> CharaConfig {
+        serde_json::from_value(serde_json::json!({
+            "charabase": { "power": 100, "guard": 100, "speed": speed, "hp": 100, "mp": 100 },
+            "attack": [{ "name": "弾幕", "damage": 10, "hit_rate": 1.0, "abnormal_state": null }],
+            "meta": {
+                "name": name,
+                "levelup_exp": "Normal",
+                "species_type": "ShrineMaiden",
+                "get_exp": 100,
+                "skill_type": "Effort",
+            },
+            "inside_info": { "regex": name, "alias": null },
+        }))
+        .unwrap()
+    }
+
+    fn battle() -> BattleData {
+        BattleData::new(
+            Uuid::new_v4(),
+            chara("marisa", 110),
+            chara("reimu", 100),
+            PlayMode::Simple,
+            chrono::Local::now().naive_local(),
+            0,
+        )
+    }
+
+    #[test]
+    fn immune_side_is_not_affected() {
+        let mut battle = battle();
+        battle.immunities(vec![AbnormalState::Poisoned], Vec::new());
+        assert!(!battle.apply_abnormal_state(StatusCharaType::Player, AbnormalState::Poisoned));
+        assert!(battle.abnormal_states(StatusCharaType::Player).is_empty());
+        assert!(battle.apply_abnormal_state(StatusCharaType::Player, AbnormalState::Slowed));
+        assert!(battle.apply_abnormal_state(StatusCharaType::Enemy, AbnormalState::Poisoned));
+        assert_eq!(battle.abnormal_states(StatusCharaType::Player), &vec![AbnormalState::Slowed]);
+        assert_eq!(battle.abnormal_states(StatusCharaType::Enemy), &vec![AbnormalState::Poisoned]);
+    }
+
+    #[test]
+    fn attack_applies_its_abnormal_state() {
+        let mut battle = battle();
+        battle.immunities(Vec::new(), vec![AbnormalState::Unlucky]);
+        battle.player_data.attack[0].abnormal_state = Some(AbnormalState::Slowed);
+        battle.calculate_player_damage();
+        assert_eq!(battle.abnormal_states(StatusCharaType::Enemy), &vec![AbnormalState::Slowed]);
+
+        battle.player_data.attack[0].abnormal_state = Some(AbnormalState::Unlucky);
+        battle.calculate_player_damage();
+        assert_eq!(battle.abnormal_states(StatusCharaType::Enemy), &vec![AbnormalState::Slowed]);
+    }
+
+    #[test]
+    fn faster_side_moves_first() {
+        let mut battle = battle();
+        assert_eq!(battle.turn_side(), StatusCharaType::Player);
+        assert_eq!(battle.turn(), battle.player());
+        battle.add_turn();
+        assert_eq!(battle.turn_side(), StatusCharaType::Enemy);
+        assert_eq!(battle.turn(), battle.enemy());
+    }
+
+    #[test]
+    fn buffs_keep_the_turn_order() {
+        let mut battle = battle();
+        for _ in 0..6 {
+            battle.status_up(StatusCharaType::Enemy);
+        }
+        assert!(battle.enemy().charabase.speed > battle.player().charabase.speed);
+        assert_eq!(battle.turn_side(), StatusCharaType::Player);
+        battle.add_turn();
+        assert_eq!(battle.turn_side(), StatusCharaType::Enemy);
+    }
+
+    #[test]
+    fn saved_battle_without_order_uses_speed() {
+        let mut battle = battle();
+        for _ in 0..6 {
+            battle.status_up(StatusCharaType::Enemy);
+        }
+        let mut json = serde_json::to_value(&battle).unwrap();
+        json.as_object_mut().unwrap().remove("first_side");
+        let loaded: BattleData = serde_json::from_value(json).unwrap();
+        assert_eq!(loaded.turn_side(), StatusCharaType::Enemy);
+    }
+}
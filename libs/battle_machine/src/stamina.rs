@@ -0,0 +1,142 @@
+use chrono::{Duration, NaiveDateTime};
+
+/// Stamina as it was written to the database
+/// Regeneration is computed from `updated_at` when it is read, there is no ticker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stamina {
+    pub points: u32,
+    /// Regeneration counts from here
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaminaRule {
+    pub max: u32,
+    /// Minutes to regenerate 1 point, 0 means always full
+    pub regen_minutes: u32,
+}
+
+impl Stamina {
+    /// Stamina at `now`
+    /// The minutes toward the next point are kept in `updated_at`
+    pub fn at(&self, now: NaiveDateTime, rule: &StaminaRule) -> Self {
+        if self.points >= rule.max {
+            return Self {
+                points: self.points,
+                updated_at: now,
+            };
+        }
+        let elapsed = (now - self.updated_at).num_minutes().max(0) as u32;
+        let gained = elapsed.checked_div(rule.regen_minutes).unwrap_or(rule.max);
+        let points = self.points.saturating_add(gained).min(rule.max);
+        if points >= rule.max {
+            Self { points, updated_at: now }
+        } else {
+            Self {
+                points,
+                updated_at: self.updated_at + Duration::minutes((gained * rule.regen_minutes) as i64),
+            }
+        }
+    }
+
+    /// `None` if the stamina is not enough
+    pub fn spend(&self, cost: u32, now: NaiveDateTime, rule: &StaminaRule) -> Option<Self> {
+        let current = self.at(now, rule);
+        current.points.checked_sub(cost).map(|points| Self {
+            points,
+            updated_at: current.updated_at,
+        })
+    }
+
+    /// Refill up to the max
+    pub fn refill(&self, amount: u32, now: NaiveDateTime, rule: &StaminaRule) -> Self {
+        let current = self.at(now, rule);
+        Self {
+            points: current.points.saturating_add(amount).min(rule.max),
+            updated_at: current.updated_at,
+        }
+    }
+
+    /// When the stamina becomes full without spending
+    pub fn full_at(&self, rule: &StaminaRule) -> NaiveDateTime {
+        let missing = rule.max.saturating_sub(self.points);
+        self.updated_at + Duration::minutes((missing * rule.regen_minutes) as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RULE: StaminaRule = StaminaRule {
+        max: 10,
+        regen_minutes: 5,
+    };
+
+    fn now() -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(2026, 10, 19)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+    }
+
+    fn stamina(points: u32, minutes_ago: i64) -> Stamina {
+        Stamina {
+            points,
+            updated_at: now() - Duration::minutes(minutes_ago),
+        }
+    }
+
+    #[test]
+    fn partial_regen_carries_over_to_the_next_point() {
+        let current = stamina(2, 12).at(now(), &RULE);
+        assert_eq!(current, stamina(4, 2));
+        // The 2 minutes already waited count toward the next point
+        assert_eq!(current.at(now() + Duration::minutes(3), &RULE), stamina(5, -3));
+        // Reading again does not regenerate twice
+        assert_eq!(current.at(now(), &RULE), current);
+    }
+
+    #[test]
+    fn regen_stops_at_the_max() {
+        assert_eq!(stamina(8, 60).at(now(), &RULE), stamina(10, 0));
+        // Time spent full does not count toward the next point
+        let full = stamina(10, 60).at(now(), &RULE);
+        assert_eq!(full, stamina(10, 0));
+        let spent = full.spend(1, now(), &RULE).unwrap();
+        assert_eq!(spent.at(now() + Duration::minutes(4), &RULE).points, 9);
+        // Stamina over the max from a refill item is kept
+        assert_eq!(stamina(12, 60).at(now(), &RULE), stamina(12, 0));
+    }
+
+    #[test]
+    fn zero_regen_minutes_is_always_full() {
+        let rule = StaminaRule {
+            max: 10,
+            regen_minutes: 0,
+        };
+        assert_eq!(stamina(0, 0).at(now(), &rule), stamina(10, 0));
+        assert_eq!(stamina(0, 0).spend(10, now(), &rule), Some(stamina(0, 0)));
+        assert_eq!(stamina(3, 0).full_at(&rule), now());
+    }
+
+    #[test]
+    fn spend_needs_enough_regenerated_stamina() {
+        assert_eq!(stamina(2, 6).spend(4, now(), &RULE), None);
+        assert_eq!(stamina(2, 12).spend(4, now(), &RULE), Some(stamina(0, 2)));
+        assert_eq!(stamina(10, 60).spend(3, now(), &RULE), Some(stamina(7, 0)));
+    }
+
+    #[test]
+    fn refill_is_capped_at_the_max() {
+        assert_eq!(stamina(2, 12).refill(3, now(), &RULE), stamina(7, 2));
+        assert_eq!(stamina(9, 0).refill(5, now(), &RULE), stamina(10, 0));
+    }
+
+    #[test]
+    fn full_at_counts_the_missing_points() {
+        assert_eq!(stamina(7, 0).full_at(&RULE), now() + Duration::minutes(15));
+        assert_eq!(stamina(4, 2).full_at(&RULE), now() + Duration::minutes(28));
+        assert_eq!(stamina(10, 0).full_at(&RULE), now());
+    }
+}
@@ -0,0 +1,466 @@
+use chrono::{Duration, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum TournamentFormat {
+    SingleElimination,
+    RoundRobin,
+}
+
+impl TournamentFormat {
+    pub fn try_from_value(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "SingleElimination" => Ok(Self::SingleElimination),
+            "RoundRobin" => Ok(Self::RoundRobin),
+            _ => Err(anyhow::anyhow!(format!("No match {}", value))),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::SingleElimination => "SingleElimination",
+            Self::RoundRobin => "RoundRobin",
+        }
+    }
+}
+
+/// One match of the bracket
+/// Participants are Discord user ids
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct BracketMatch {
+    pub round: u32,
+    /// `None` is a bye in the first round, or a slot waiting for the previous round
+    pub players: [Option<String>; 2],
+    pub winner: Option<String>,
+    pub finished: bool,
+    /// Participants who came to play this match
+    pub ready: Vec<String>,
+    /// After this, the match is decided by [expire](Bracket::expire)
+    pub deadline: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Bracket {
+    format: TournamentFormat,
+    participants: Vec<String>,
+    matches: Vec<BracketMatch>,
+    match_timeout_minutes: i64,
+}
+
+impl BracketMatch {
+    fn new(round: u32, players: [Option<String>; 2]) -> Self {
+        Self {
+            round,
+            players,
+            winner: None,
+            finished: false,
+            ready: Vec::new(),
+            deadline: None,
+        }
+    }
+
+    /// Both participants are known and the match is not decided
+    pub fn is_open(&self) -> bool {
+        !self.finished && self.players.iter().all(Option::is_some)
+    }
+
+    pub fn has_player(&self, user_id: &str) -> bool {
+        self.players.iter().flatten().any(|p| p == user_id)
+    }
+
+    /// The other participant of this match
+    pub fn opponent_of(&self, user_id: &str) -> Option<&str> {
+        self.players
+            .iter()
+            .flatten()
+            .find(|p| p.as_str() != user_id)
+            .map(|p| p.as_str())
+    }
+}
+
+impl Bracket {
+    /// Make the bracket in the order of `participants`
+    /// Shuffle `participants` before calling this if the seed should be random
+    pub fn new(
+        format: TournamentFormat,
+        participants: Vec<String>,
+        match_timeout: Duration,
+        now: NaiveDateTime,
+    ) -> anyhow::Result<Self> {
+        if participants.len() < 2 {
+            return Err(anyhow::anyhow!("at least 2 participants are required"));
+        }
+        let matches = match format {
+            TournamentFormat::SingleElimination => Self::single_elimination(&participants),
+            TournamentFormat::RoundRobin => Self::round_robin(&participants),
+        };
+        let mut bracket = Self {
+            format,
+            participants,
+            matches,
+            match_timeout_minutes: match_timeout.num_minutes(),
+        };
+        bracket.resolve_byes();
+        bracket.open_deadlines(now);
+        Ok(bracket)
+    }
+
+    /// Pad to a power of two and pair the top seeds with byes
+    /// The order of [seed_order](Self::seed_order) keeps the top seeds apart until the last rounds
+    fn single_elimination(participants: &[String]) -> Vec<BracketMatch> {
+        let size = participants.len().next_power_of_two();
+        let order = Self::seed_order(size);
+
+        let mut matches: Vec<BracketMatch> = order
+            .chunks(2)
+            .map(|pair| {
+                BracketMatch::new(
+                    0,
+                    [participants.get(pair[0]).cloned(), participants.get(pair[1]).cloned()],
+                )
+            })
+            .collect();
+        let mut round = 1;
+        let mut round_size = size / 4;
+        while round_size >= 1 {
+            matches.extend((0..round_size).map(|_| BracketMatch::new(round, [None, None])));
+            round += 1;
+            round_size /= 2;
+        }
+        matches
+    }
+
+    /// Seeds (0 is the top seed) in the slots of the first round, `size` is a power of two
+    /// Each pair of seeds adds up to `size - 1`, and the first and second seeds can only meet in the final,
+    /// e.g. 0 7 3 4 1 6 2 5 for 8
+    fn seed_order(size: usize) -> Vec<usize> {
+        let mut order = vec![0];
+        while order.len() < size {
+            let len = order.len() * 2;
+            order = order.iter().flat_map(|&seed| [seed, len - 1 - seed]).collect();
+        }
+        order
+    }
+
+    /// Circle method: everyone plays everyone once
+    fn round_robin(participants: &[String]) -> Vec<BracketMatch> {
+        let mut circle: Vec<Option<String>> = participants.iter().cloned().map(Some).collect();
+        if circle.len() % 2 == 1 {
+            circle.push(None);
+        }
+        let size = circle.len();
+        let mut matches = Vec::new();
+        for round in 0..size - 1 {
+            for i in 0..size / 2 {
+                let pair = [circle[i].clone(), circle[size - 1 - i].clone()];
+                if pair.iter().all(Option::is_some) {
+                    matches.push(BracketMatch::new(round as u32, pair));
+                }
+            }
+            circle[1..].rotate_right(1);
+        }
+        matches
+    }
+
+    pub fn format(&self) -> TournamentFormat {
+        self.format
+    }
+
+    pub fn participants(&self) -> &Vec<String> {
+        &self.participants
+    }
+
+    pub fn matches(&self) -> &Vec<BracketMatch> {
+        &self.matches
+    }
+
+    pub fn rounds(&self) -> u32 {
+        self.matches.iter().map(|m| m.round + 1).max().unwrap_or(0)
+    }
+
+    /// The open match of this participant
+    pub fn match_of(&self, user_id: &str) -> Option<usize> {
+        self.matches
+            .iter()
+            .position(|m| m.is_open() && m.has_player(user_id))
+    }
+
+    /// The participant came to play, but the match did not start
+    pub fn check_in(&mut self, index: usize, user_id: &str) {
+        if let Some(m) = self.matches.get_mut(index) {
+            if m.has_player(user_id) && !m.ready.iter().any(|r| r == user_id) {
+                m.ready.push(user_id.to_string());
+            }
+        }
+    }
+
+    /// Record the winner of a match and advance the bracket
+    /// Returns `false` without changing anything if the match was already decided,
+    /// e.g. by [expire](Self::expire) while the battle was played
+    pub fn report(&mut self, index: usize, winner: &str, now: NaiveDateTime) -> anyhow::Result<bool> {
+        let m = self
+            .matches
+            .get(index)
+            .ok_or_else(|| anyhow::anyhow!("no match {}", index))?;
+        if !m.has_player(winner) {
+            return Err(anyhow::anyhow!("{} is not in match {}", winner, index));
+        }
+        if !m.is_open() {
+            return Ok(false);
+        }
+        self.finish(index, Some(winner.to_string()));
+        self.open_deadlines(now);
+        Ok(true)
+    }
+
+    /// Decide the open matches whose deadline has passed
+    /// A participant who checked in wins against one who did not show up.
+    /// If nobody showed up, the higher seed advances in single elimination
+    /// and nobody wins in round robin.
+    pub fn expire(&mut self, now: NaiveDateTime) -> Vec<usize> {
+        let expired: Vec<usize> = self
+            .matches
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.is_open() && m.deadline.is_some_and(|d| d <= now))
+            .map(|(i, _)| i)
+            .collect();
+
+        for &index in expired.iter() {
+            let m = &self.matches[index];
+            let winner = match (m.ready.len(), self.format) {
+                (1, _) => Some(m.ready[0].clone()),
+                (_, TournamentFormat::SingleElimination) => m.players[0].clone(),
+                (_, TournamentFormat::RoundRobin) => None,
+            };
+            self.finish(index, winner);
+        }
+        self.open_deadlines(now);
+        expired
+    }
+
+    /// Winner of the tournament once every match is decided
+    pub fn champion(&self) -> Option<String> {
+        if !self.is_finished() {
+            return None;
+        }
+        match self.format {
+            TournamentFormat::SingleElimination => self.matches.last()?.winner.clone(),
+            TournamentFormat::RoundRobin => self
+                .standings()
+                .into_iter()
+                .next()
+                .map(|(user_id, _)| user_id),
+        }
+    }
+
+    /// Participants and their wins, most wins first
+    /// Ties are broken by the wins against the other participants with as many wins,
+    /// then by the seed
+    pub fn standings(&self) -> Vec<(String, u32)> {
+        let wins = |p: &String| {
+            self.matches
+                .iter()
+                .filter(|m| m.winner.as_ref() == Some(p))
+                .count() as u32
+        };
+        let mut standings: Vec<(String, u32, u32)> = self
+            .participants
+            .iter()
+            .map(|p| (p.clone(), wins(p), 0))
+            .collect();
+        for i in 0..standings.len() {
+            let (player, player_wins, _) = &standings[i];
+            let head_to_head = self
+                .matches
+                .iter()
+                .filter(|m| m.winner.as_ref() == Some(player))
+                .filter_map(|m| m.opponent_of(player))
+                .filter(|opponent| {
+                    standings
+                        .iter()
+                        .any(|(p, w, _)| p == opponent && w == player_wins)
+                })
+                .count() as u32;
+            standings[i].2 = head_to_head;
+        }
+        // Stable, so the seed decides what is still tied
+        standings.sort_by(|a, b| b.1.cmp(&a.1).then(b.2.cmp(&a.2)));
+        standings.into_iter().map(|(p, w, _)| (p, w)).collect()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.matches.iter().all(|m| m.finished)
+    }
+
+    fn finish(&mut self, index: usize, winner: Option<String>) {
+        let m = &mut self.matches[index];
+        m.finished = true;
+        m.winner = winner.clone();
+        let round = m.round;
+
+        if self.format == TournamentFormat::SingleElimination {
+            // Position of the match in its round decides the slot in the next round
+            let round_start = self.matches.iter().position(|m| m.round == round).unwrap();
+            let position = index - round_start;
+            if let Some(next_start) = self.matches.iter().position(|m| m.round == round + 1) {
+                self.matches[next_start + position / 2].players[position % 2] = winner;
+            }
+        }
+    }
+
+    /// Decide the first round matches against a bye
+    fn resolve_byes(&mut self) {
+        if self.format != TournamentFormat::SingleElimination {
+            return;
+        }
+        let byes: Vec<(usize, Option<String>)> = self
+            .matches
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.round == 0 && m.players.iter().any(Option::is_none))
+            .map(|(i, m)| (i, m.players.iter().flatten().next().cloned()))
+            .collect();
+        for (index, winner) in byes {
+            self.finish(index, winner);
+        }
+    }
+
+    fn open_deadlines(&mut self, now: NaiveDateTime) {
+        let timeout = Duration::minutes(self.match_timeout_minutes);
+        for m in self.matches.iter_mut() {
+            if m.is_open() && m.deadline.is_none() {
+                m.deadline = Some(now + timeout);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(2026, 10, 19)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+    }
+
+    fn bracket(format: TournamentFormat, count: usize) -> Bracket {
+        let participants = (1..=count).map(|seed| seed.to_string()).collect();
+        Bracket::new(format, participants, Duration::minutes(60), now()).unwrap()
+    }
+
+    fn first_round(bracket: &Bracket) -> Vec<[Option<String>; 2]> {
+        bracket
+            .matches()
+            .iter()
+            .filter(|m| m.round == 0)
+            .map(|m| m.players.clone())
+            .collect()
+    }
+
+    fn pair(a: &str, b: &str) -> [Option<String>; 2] {
+        [Some(a.to_string()), Some(b.to_string())]
+    }
+
+    #[test]
+    fn seeds_follow_the_standard_order() {
+        assert_eq!(Bracket::seed_order(8), [0, 7, 3, 4, 1, 6, 2, 5]);
+        let bracket = bracket(TournamentFormat::SingleElimination, 8);
+        assert_eq!(
+            first_round(&bracket),
+            [pair("1", "8"), pair("4", "5"), pair("2", "7"), pair("3", "6")]
+        );
+    }
+
+    #[test]
+    fn top_seeds_meet_in_the_final() {
+        let mut bracket = bracket(TournamentFormat::SingleElimination, 8);
+        // The better seed wins every match
+        while let Some(index) = bracket.matches().iter().position(|m| m.is_open()) {
+            let winner = bracket.matches()[index]
+                .players
+                .iter()
+                .flatten()
+                .min_by_key(|p| p.parse::<u32>().unwrap())
+                .unwrap()
+                .clone();
+            let round = bracket.matches()[index].round;
+            if round + 1 == bracket.rounds() {
+                assert_eq!(bracket.matches()[index].players, pair("1", "2"));
+            }
+            assert!(bracket.report(index, &winner, now()).unwrap());
+        }
+        assert_eq!(bracket.champion().as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn top_seeds_get_the_byes() {
+        let bracket = bracket(TournamentFormat::SingleElimination, 6);
+        let byes: Vec<&String> = bracket
+            .matches()
+            .iter()
+            .filter(|m| m.round == 0 && m.players.iter().any(Option::is_none))
+            .filter_map(|m| m.winner.as_ref())
+            .collect();
+        assert_eq!(byes, ["1", "2"]);
+    }
+
+    #[test]
+    fn report_after_expire_is_ignored() {
+        let mut bracket = bracket(TournamentFormat::SingleElimination, 2);
+        bracket.check_in(0, "2");
+        assert_eq!(bracket.expire(now() + Duration::minutes(61)), [0]);
+        assert_eq!(bracket.matches()[0].winner.as_deref(), Some("2"));
+
+        assert!(!bracket.report(0, "1", now()).unwrap());
+        assert_eq!(bracket.matches()[0].winner.as_deref(), Some("2"));
+        assert!(bracket.report(0, "3", now()).is_err());
+        assert!(bracket.report(5, "1", now()).is_err());
+    }
+
+    #[test]
+    fn round_robin_ties_are_broken_by_head_to_head() {
+        let mut bracket = bracket(TournamentFormat::RoundRobin, 4);
+        // 2 beats 1 and 3, 1 beats 3 and 4, 3 beats 4, 4 beats 2: 1 and 2 have 2 wins each
+        play_round_robin(
+            &mut bracket,
+            &[
+                ("1", "2", "2"),
+                ("1", "3", "1"),
+                ("1", "4", "1"),
+                ("2", "3", "2"),
+                ("2", "4", "4"),
+                ("3", "4", "3"),
+            ],
+        );
+        // 3 and 4 have 1 win each, and 3 beat 4
+        assert_eq!(bracket.standings(), standings(&[("2", 2), ("1", 2), ("3", 1), ("4", 1)]));
+        assert_eq!(bracket.champion().as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn round_robin_ties_left_are_broken_by_seed() {
+        let mut bracket = bracket(TournamentFormat::RoundRobin, 3);
+        play_round_robin(&mut bracket, &[("1", "2", "1"), ("2", "3", "2"), ("1", "3", "3")]);
+        assert_eq!(bracket.standings(), standings(&[("1", 1), ("2", 1), ("3", 1)]));
+        assert_eq!(bracket.champion().as_deref(), Some("1"));
+    }
+
+    fn play_round_robin(bracket: &mut Bracket, results: &[(&str, &str, &str)]) {
+        for (a, b, winner) in results {
+            let index = bracket
+                .matches()
+                .iter()
+                .position(|m| m.has_player(a) && m.has_player(b))
+                .unwrap();
+            assert!(bracket.report(index, winner, now()).unwrap());
+        }
+    }
+
+    fn standings(expected: &[(&str, u32)]) -> Vec<(String, u32)> {
+        expected.iter().map(|(p, w)| (p.to_string(), *w)).collect()
+    }
+}
@@ -0,0 +1,299 @@
+use crate::event::ProgressEvent;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Character unlock settings
+/// Read from `unlock/*.toml` and the `unlock.toml` of Contents extensions
+/// example:
+/// ```toml
+/// default_unlocked = ["reimu", "marisa"]
+///
+/// [[milestone]]
+/// chara = "sakuya"
+/// condition = { DefeatEnemy = { enemy = "十六夜咲夜", story = "scarlet" } }
+///
+/// [[pool]]
+/// id = "standard"
+/// name = "いつもの賽銭箱"
+/// cost = 300
+/// entries = [{ chara = "sakuya", weight = 10 }, { chara = "marisa", weight = 90 }]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UnlockConfig {
+    #[serde(default)]
+    pub default_unlocked: Vec<String>,
+    #[serde(default)]
+    pub milestone: Vec<Milestone>,
+    #[serde(default)]
+    pub pool: Vec<GachaPool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Milestone {
+    /// `chara/{chara}.toml`
+    pub chara: String,
+    pub condition: MilestoneCondition,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MilestoneCondition {
+    /// Defeat the enemy, only in the story if `story` is set
+    DefeatEnemy {
+        enemy: String,
+        story: Option<String>,
+    },
+    /// Any character, or `chara` if set, reaches the level
+    ReachLevel { level: u32, chara: Option<String> },
+}
+
+/// Random pull paid with gold
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GachaPool {
+    pub id: String,
+    pub name: String,
+    pub cost: u32,
+    pub entries: Vec<PoolEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolEntry {
+    pub chara: String,
+    pub weight: u32,
+}
+
+impl UnlockConfig {
+    /// Read and merge every file
+    pub async fn load(paths: Vec<PathBuf>) -> anyhow::Result<Self> {
+        let mut config = Self::default();
+        for path in paths {
+            config.merge(thrpg_utils::read_to_toml(path).await?);
+        }
+        Ok(config)
+    }
+
+    /// Files in `unlock/` and `unlock.toml` in the directories of Contents extensions
+    pub async fn load_with_extensions<P: AsRef<Path>>(
+        dir: P,
+        extension_dirs: Vec<PathBuf>,
+    ) -> anyhow::Result<Self> {
+        let mut paths = thrpg_utils::dir_files(dir.as_ref()).await.unwrap_or_default();
+        paths.extend(
+            extension_dirs
+                .into_iter()
+                .map(|dir| dir.join("unlock.toml"))
+                .filter(|path| path.is_file()),
+        );
+        Self::load(paths).await
+    }
+
+    pub fn merge(&mut self, other: Self) -> &mut Self {
+        self.default_unlocked.extend(other.default_unlocked);
+        self.milestone.extend(other.milestone);
+        for pool in other.pool {
+            match self.pool.iter_mut().find(|p| p.id == pool.id) {
+                // Extensions can add characters to an existing pool
+                Some(p) => p.entries.extend(pool.entries),
+                None => self.pool.push(pool),
+            }
+        }
+        self
+    }
+
+    pub fn is_default_unlocked(&self, chara: &str) -> bool {
+        self.default_unlocked.iter().any(|c| c == chara)
+    }
+
+    pub fn pool(&self, id: &str) -> Option<&GachaPool> {
+        self.pool.iter().find(|p| p.id == id)
+    }
+
+    /// Characters whose milestone is reached by this event
+    pub fn unlocked_by(&self, event: &ProgressEvent) -> Vec<&str> {
+        self.milestone
+            .iter()
+            .filter(|m| m.condition.is_reached(event))
+            .map(|m| m.chara.as_str())
+            .collect()
+    }
+}
+
+impl MilestoneCondition {
+    pub fn is_reached(&self, event: &ProgressEvent) -> bool {
+        match (self, event) {
+            (
+                MilestoneCondition::DefeatEnemy { enemy, story },
+                ProgressEvent::EnemyDefeated { battle },
+            ) => {
+                enemy == &battle.enemy().meta.name
+                    && story
+                        .as_deref()
+                        .is_none_or(|s| battle.play_mode().story_id() == Some(s))
+            }
+            (
+                MilestoneCondition::ReachLevel { level, chara },
+                ProgressEvent::LevelReached { chara: c, level: l, .. },
+            ) => l >= level && chara.as_deref().is_none_or(|chara| chara == *c),
+            _ => false,
+        }
+    }
+}
+
+impl GachaPool {
+    /// Published rate of each character
+    pub fn rates(&self) -> Vec<(&str, f64)> {
+        let total: u32 = self.entries.iter().map(|e| e.weight).sum();
+        self.entries
+            .iter()
+            .map(|e| (e.chara.as_str(), e.weight as f64 / total.max(1) as f64))
+            .collect()
+    }
+
+    pub fn pull<R: Rng>(&self, rng: &mut R) -> Option<&PoolEntry> {
+        let total: u32 = self.entries.iter().map(|e| e.weight).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut roll = rng.gen_range(0..total);
+        self.entries.iter().find(|e| {
+            if roll < e.weight {
+                true
+            } else {
+                roll -= e.weight;
+                false
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chara::LevelupExpType;
+    use crate::mode::PlayMode;
+    use crate::rpg_core::{tests::chara, BattleData};
+    use rand::{rngs::StdRng, SeedableRng};
+    use uuid::Uuid;
+
+    fn config() -> UnlockConfig {
+        UnlockConfig {
+            default_unlocked: vec!["reimu".to_string()],
+            milestone: vec![
+                Milestone {
+                    chara: "sakuya".to_string(),
+                    condition: MilestoneCondition::DefeatEnemy {
+                        enemy: "十六夜咲夜".to_string(),
+                        story: Some("scarlet".to_string()),
+                    },
+                },
+                Milestone {
+                    chara: "marisa".to_string(),
+                    condition: MilestoneCondition::ReachLevel {
+                        level: 10,
+                        chara: Some("reimu".to_string()),
+                    },
+                },
+            ],
+            pool: vec![GachaPool {
+                id: "standard".to_string(),
+                name: "いつもの賽銭箱".to_string(),
+                cost: 300,
+                entries: vec![
+                    PoolEntry {
+                        chara: "sakuya".to_string(),
+                        weight: 1,
+                    },
+                    PoolEntry {
+                        chara: "marisa".to_string(),
+                        weight: 3,
+                    },
+                ],
+            }],
+        }
+    }
+
+    fn battle(enemy: &str, mode: PlayMode) -> BattleData {
+        BattleData::new(
+            Uuid::new_v4(),
+            chara("reimu", 100),
+            chara(enemy, 100),
+            mode,
+            chrono::Local::now().naive_local(),
+            0,
+        )
+    }
+
+    #[test]
+    fn defeat_milestone_needs_the_enemy_in_the_story() {
+        let config = config();
+        let story = PlayMode::Story {
+            id: "scarlet".to_string(),
+        };
+        let in_story = battle("十六夜咲夜", story.clone());
+        let outside = battle("十六夜咲夜", PlayMode::Simple);
+        let other = battle("紅美鈴", story);
+
+        assert_eq!(config.unlocked_by(&ProgressEvent::EnemyDefeated { battle: &in_story }), ["sakuya"]);
+        assert!(config.unlocked_by(&ProgressEvent::EnemyDefeated { battle: &outside }).is_empty());
+        assert!(config.unlocked_by(&ProgressEvent::EnemyDefeated { battle: &other }).is_empty());
+    }
+
+    #[test]
+    fn level_milestone_needs_the_level_on_the_character() {
+        let config = config();
+        let level = |chara, level| ProgressEvent::LevelReached {
+            chara,
+            levelup_exp: &LevelupExpType::Normal,
+            level,
+        };
+        assert!(config.unlocked_by(&level("reimu", 9)).is_empty());
+        assert_eq!(config.unlocked_by(&level("reimu", 10)), ["marisa"]);
+        assert_eq!(config.unlocked_by(&level("reimu", 12)), ["marisa"]);
+        assert!(config.unlocked_by(&level("sakuya", 10)).is_empty());
+    }
+
+    #[test]
+    fn pulls_follow_the_published_rates() {
+        let config = config();
+        let pool = config.pool("standard").unwrap();
+        assert_eq!(pool.rates(), [("sakuya", 0.25), ("marisa", 0.75)]);
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let sakuya = (0..4000)
+            .filter(|_| pool.pull(&mut rng).unwrap().chara == "sakuya")
+            .count();
+        assert!((800..1200).contains(&sakuya), "{}", sakuya);
+    }
+
+    #[test]
+    fn empty_or_weightless_pool_pulls_nothing() {
+        let mut pool = config().pool[0].clone();
+        pool.entries.iter_mut().for_each(|e| e.weight = 0);
+        assert_eq!(pool.pull(&mut StdRng::seed_from_u64(1)), None);
+        assert_eq!(pool.rates(), [("sakuya", 0.0), ("marisa", 0.0)]);
+        pool.entries.clear();
+        assert_eq!(pool.pull(&mut StdRng::seed_from_u64(1)), None);
+    }
+
+    #[test]
+    fn extensions_add_to_existing_pools() {
+        let mut config = config();
+        config.merge(UnlockConfig {
+            default_unlocked: vec!["marisa".to_string()],
+            milestone: vec![],
+            pool: vec![GachaPool {
+                id: "standard".to_string(),
+                name: String::new(),
+                cost: 0,
+                entries: vec![PoolEntry {
+                    chara: "youmu".to_string(),
+                    weight: 4,
+                }],
+            }],
+        });
+        assert!(config.is_default_unlocked("marisa"));
+        assert_eq!(config.pool.len(), 1);
+        let pool = config.pool("standard").unwrap();
+        assert_eq!((pool.cost, pool.rates()[2]), (300, ("youmu", 0.5)));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::extension_config::ExtensionConfig;
+use crate::extension_config::{ExtensionConfig, Extensiontype};
 use anyhow::Context;
 use serde::Deserialize;
 use std::path::{Path, PathBuf};
@@ -59,4 +59,25 @@ impl ExtensionStore {
             .collect();
         result
     }
+
+    /// Get directories of Contents extensions
+    pub fn contents_dirs(&self) -> Vec<PathBuf> {
+        self.dirs_of(&Extensiontype::Contents)
+    }
+
+    /// Get directories of Story extensions
+    pub fn story_dirs(&self) -> Vec<PathBuf> {
+        self.dirs_of(&Extensiontype::Story)
+    }
+
+    fn dirs_of(&self, extension_type: &Extensiontype) -> Vec<PathBuf> {
+        self.extensions
+            .iter()
+            .filter(|p| {
+                ExtensionConfig::parse(p.join("manifest.toml"))
+                    .is_ok_and(|c| c.extension_type() == extension_type)
+            })
+            .cloned()
+            .collect()
+    }
 }
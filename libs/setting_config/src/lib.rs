@@ -1,10 +1,17 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 /// Default server address 
 /// check: [address_random](address_random())
 pub const NULL_ADDRESS: &str = "null.address";
 
+/// Stamina of a new user and the cap when `max` is not set
+pub const DEFAULT_STAMINA_MAX: u32 = 100;
+
+/// Days a deleted account can be restored when `restore_days` is not set
+pub const DEFAULT_RESTORE_DAYS: u32 = 30;
+
 /// THRPG.toml params
 /// example:
 /// ```toml
@@ -12,12 +19,22 @@ pub const NULL_ADDRESS: &str = "null.address";
 /// prefix="th!" # your bot prefix
 /// manager_id= #your userid
 /// language="Japanese" # used language in your bot 
+/// restore_days=30 # days /restore can bring back an account after /delete
 ///
 /// [postgresql_config]
 /// db_address="postgres://postgres@localhost/thrpg" # postgresql server address
 ///
 /// [redis_config]
-/// db_address="" # redis server address
+/// db_address="" # redis server address, live battles are kept in the bot process when empty
+///
+/// [stamina_config] # battles are not limited without this table
+/// max=100
+/// regen_minutes=5 # minutes to regenerate 1 point
+/// disabled_guilds=[] # guilds where battles cost no stamina
+///
+/// [stamina_config.cost] # by PlayMode, "Story" is every story
+/// Simple=10
+/// Raid=30
 /// ```
 #[derive(Deserialize, Serialize, Clone)]
 pub struct Config {
@@ -31,6 +48,8 @@ pub struct Config {
     timeout_duration: Option<u64>,
     authority_flags: Option<u32>,
     authority_strict: Option<bool>,
+    stamina_config: Option<StaminaConfig>,
+    restore_days: Option<u32>,
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -43,6 +62,16 @@ pub struct PostgresqlConfig {
     pub db_address: String,
 }
 
+#[derive(Deserialize, Serialize, Clone)]
+pub struct StaminaConfig {
+    max: Option<u32>,
+    regen_minutes: Option<u32>,
+    #[serde(default)]
+    cost: HashMap<String, u32>,
+    #[serde(default)]
+    disabled_guilds: Vec<u64>,
+}
+
 pub async fn config_parse_toml() -> Config {
     // Setting file is "THRPG.toml"
     let path = PathBuf::from("THRPG.toml");
@@ -73,6 +102,10 @@ impl Config {
         &self.postgresql_config
     }
 
+    pub fn stamina_config(&self) -> Option<&StaminaConfig> {
+        self.stamina_config.as_ref()
+    }
+
     pub fn manager_id(&self) -> u64 {
         self.manager_id
     }
@@ -81,6 +114,10 @@ impl Config {
         self.prefix.as_ref().map(|string| string as &str)
     }
 
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
     pub fn check_server_address(&self) -> anyhow::Result<String> {
         if let Some(url) = &self.server_address {
             let url = url::Url::parse(&url).map_err(|e| anyhow::anyhow!(e))?;
@@ -106,4 +143,29 @@ impl Config {
             false
         }
     }
+
+    /// Days until a deleted account is purged
+    pub fn restore_days(&self) -> u32 {
+        self.restore_days.unwrap_or(DEFAULT_RESTORE_DAYS)
+    }
+}
+
+impl StaminaConfig {
+    pub fn max(&self) -> u32 {
+        self.max.unwrap_or(DEFAULT_STAMINA_MAX)
+    }
+
+    pub fn regen_minutes(&self) -> u32 {
+        self.regen_minutes.unwrap_or(5)
+    }
+
+    /// Modes not in the table are free
+    pub fn cost(&self, mode: &str) -> u32 {
+        self.cost.get(mode).copied().unwrap_or(0)
+    }
+
+    /// Battles outside guilds always use stamina
+    pub fn is_enabled(&self, guild_id: Option<u64>) -> bool {
+        guild_id.is_none_or(|id| !self.disabled_guilds.contains(&id))
+    }
 }
@@ -12,6 +12,7 @@ use rust_embed::RustEmbed;
 use thrpg_utils::dir_files;
 use tokio::fs::read_to_string;
 pub use unic_langid::LanguageIdentifier;
+use unic_langid::langid;
 
 #[deprecated]
 static LANGUAGE_LOADER: Lazy<FluentLanguageLoader> = Lazy::new(|| {
@@ -46,6 +47,40 @@ pub fn appear_enemy<T: Into<String>>(name: T) -> String {
     tfl!("appear-enemy", name = name.into())
 }
 
+/// Every language in `i18n/`, see [language_loader]
+static LANGUAGES: Lazy<FluentLanguageLoader> = Lazy::new(|| {
+    let loader = fluent_language_loader!();
+    let mut languages = loader.available_languages(&Localizations).unwrap();
+    // The first language loaded is the current one
+    languages.sort_by_key(|language| language != loader.fallback_language());
+    loader
+        .load_languages(&Localizations, &languages.iter().collect::<Vec<_>>())
+        .unwrap();
+    loader
+});
+
+/// Messages in `language`, a name of `/settings` like "English" or an identifier like "en-US"
+/// Messages missing in the language and unknown languages use the fallback language
+pub fn language_loader(language: Option<&str>) -> FluentLanguageLoader {
+    let identifier = language
+        .and_then(language_identifier)
+        .unwrap_or_else(|| LANGUAGES.fallback_language().clone());
+    LANGUAGES.select_languages(&[identifier])
+}
+
+fn language_identifier(language: &str) -> Option<LanguageIdentifier> {
+    match language {
+        "Japanese" => Some(langid!("ja-JP")),
+        "English" => Some(langid!("en-US")),
+        other => other.parse().ok(),
+    }
+}
+
+pub fn appear_enemy_in<T: Into<String>>(language: Option<&str>, name: T) -> String {
+    let loader = language_loader(language);
+    i18n_embed_fl::fl!(loader, "appear-enemy", name = name.into())
+}
+
 pub async fn load_fluent_resource<P:AsRef<Path>>(path: P) -> anyhow::Result<Vec<FluentResource>> {
     let mut vec = Vec::new();
     for resource_path in dir_files(path.as_ref()).await? {
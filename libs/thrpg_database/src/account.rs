@@ -0,0 +1,238 @@
+//! Everything stored about one user, for `/export`, `/delete` and `/restore`
+use crate::rating::RATING_KEY;
+use crate::redis_connect::AsyncConnection;
+use crate::session::session_key;
+use crate::{
+    achievement, battle_history, duel, equipment, incident_clear, incident_run, inventory, playdata,
+    pull_history, quest, rating, rating_history, roster, userdata,
+};
+use chrono::{Local, NaiveDateTime};
+use sea_orm::{
+    sea_query::Expr, ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait, QueryFilter,
+    TransactionTrait, Value,
+};
+use serde_json::json;
+
+/// Rows of every table about the user, by table name
+pub async fn export_user<C: ConnectionTrait>(db: &C, user_id: &str) -> Result<serde_json::Value, DbErr> {
+    let user = userdata::Entity::find_by_id(user_id.to_string()).one(db).await?;
+    let playdata = match user.as_ref().and_then(|user| user.battle_uuid) {
+        Some(battle_uuid) => playdata::Entity::find_by_id(battle_uuid).into_json().all(db).await?,
+        None => Vec::new(),
+    };
+    Ok(json!({
+        "user_id": user_id,
+        "exported_at": Local::now().naive_local(),
+        "userdata": userdata::Entity::find_by_id(user_id.to_string()).into_json().one(db).await?,
+        "playdata": playdata,
+        "roster": roster::Entity::find()
+            .filter(roster::Column::UserId.eq(user_id))
+            .into_json()
+            .all(db)
+            .await?,
+        "inventory": inventory::Entity::find()
+            .filter(inventory::Column::UserId.eq(user_id))
+            .into_json()
+            .all(db)
+            .await?,
+        "equipment": equipment::Entity::find()
+            .filter(equipment::Column::UserId.eq(user_id))
+            .into_json()
+            .all(db)
+            .await?,
+        "achievement": achievement::Entity::find()
+            .filter(achievement::Column::UserId.eq(user_id))
+            .into_json()
+            .all(db)
+            .await?,
+        "quest": quest::Entity::find()
+            .filter(quest::Column::UserId.eq(user_id))
+            .into_json()
+            .all(db)
+            .await?,
+        "incident_run": incident_run::Entity::find()
+            .filter(incident_run::Column::UserId.eq(user_id))
+            .into_json()
+            .all(db)
+            .await?,
+        "incident_clear": incident_clear::Entity::find()
+            .filter(incident_clear::Column::UserId.eq(user_id))
+            .into_json()
+            .all(db)
+            .await?,
+        "pull_history": pull_history::Entity::find()
+            .filter(pull_history::Column::UserId.eq(user_id))
+            .into_json()
+            .all(db)
+            .await?,
+        "duel": duel::Entity::find()
+            .filter(involved_in_duel(user_id))
+            .into_json()
+            .all(db)
+            .await?,
+        "rating": rating::Entity::find_by_id(user_id.to_string()).into_json().one(db).await?,
+        "rating_history": rating_history::Entity::find()
+            .filter(rating_history::Column::UserId.eq(user_id))
+            .into_json()
+            .all(db)
+            .await?,
+        "battle_history": battle_history::Entity::find()
+            .filter(battle_history::Column::UserId.eq(user_id))
+            .into_json()
+            .all(db)
+            .await?,
+    }))
+}
+
+/// Delete every row about the user in one transaction
+/// Duels are gone for the opponent too, and the battle history of the opponent forgets the user
+/// Returns `false` if nothing was stored about the user
+pub async fn delete_user_data<C: ConnectionTrait + TransactionTrait>(
+    db: &C,
+    user_id: &str,
+) -> Result<bool, DbErr> {
+    let txn = db.begin().await?;
+    let mut deleted = 0;
+    if let Some(battle_uuid) = userdata::Entity::find_by_id(user_id.to_string())
+        .one(&txn)
+        .await?
+        .and_then(|user| user.battle_uuid)
+    {
+        deleted += playdata::Entity::delete_by_id(battle_uuid).exec(&txn).await?.rows_affected;
+    }
+    deleted += roster::Entity::delete_many()
+        .filter(roster::Column::UserId.eq(user_id))
+        .exec(&txn)
+        .await?
+        .rows_affected;
+    deleted += inventory::Entity::delete_many()
+        .filter(inventory::Column::UserId.eq(user_id))
+        .exec(&txn)
+        .await?
+        .rows_affected;
+    deleted += equipment::Entity::delete_many()
+        .filter(equipment::Column::UserId.eq(user_id))
+        .exec(&txn)
+        .await?
+        .rows_affected;
+    deleted += achievement::Entity::delete_many()
+        .filter(achievement::Column::UserId.eq(user_id))
+        .exec(&txn)
+        .await?
+        .rows_affected;
+    deleted += quest::Entity::delete_many()
+        .filter(quest::Column::UserId.eq(user_id))
+        .exec(&txn)
+        .await?
+        .rows_affected;
+    deleted += incident_run::Entity::delete_many()
+        .filter(incident_run::Column::UserId.eq(user_id))
+        .exec(&txn)
+        .await?
+        .rows_affected;
+    deleted += incident_clear::Entity::delete_many()
+        .filter(incident_clear::Column::UserId.eq(user_id))
+        .exec(&txn)
+        .await?
+        .rows_affected;
+    deleted += pull_history::Entity::delete_many()
+        .filter(pull_history::Column::UserId.eq(user_id))
+        .exec(&txn)
+        .await?
+        .rows_affected;
+    deleted += duel::Entity::delete_many()
+        .filter(involved_in_duel(user_id))
+        .exec(&txn)
+        .await?
+        .rows_affected;
+    deleted += rating::Entity::delete_by_id(user_id.to_string())
+        .exec(&txn)
+        .await?
+        .rows_affected;
+    deleted += rating_history::Entity::delete_many()
+        .filter(rating_history::Column::UserId.eq(user_id))
+        .exec(&txn)
+        .await?
+        .rows_affected;
+    deleted += battle_history::Entity::delete_many()
+        .filter(battle_history::Column::UserId.eq(user_id))
+        .exec(&txn)
+        .await?
+        .rows_affected;
+    battle_history::Entity::update_many()
+        .col_expr(battle_history::Column::OpponentId, Expr::value(Value::String(None)))
+        .filter(battle_history::Column::OpponentId.eq(user_id))
+        .exec(&txn)
+        .await?;
+    deleted += userdata::Entity::delete_by_id(user_id.to_string())
+        .exec(&txn)
+        .await?
+        .rows_affected;
+    txn.commit().await?;
+    Ok(deleted > 0)
+}
+
+/// Mark the account as pending deletion, nothing is deleted until [purge_deleted]
+/// Returns `false` if the user has no save data or it is already pending
+pub async fn mark_deleted<C: ConnectionTrait>(
+    db: &C,
+    user_id: &str,
+    now: NaiveDateTime,
+) -> Result<bool, DbErr> {
+    let result = userdata::Entity::update_many()
+        .col_expr(userdata::Column::DeletedAt, Expr::value(now))
+        .filter(userdata::Column::UserId.eq(user_id))
+        .filter(userdata::Column::DeletedAt.is_null())
+        .exec(db)
+        .await?;
+    Ok(result.rows_affected == 1)
+}
+
+/// Undo [mark_deleted], returns `false` if the account is not pending deletion
+pub async fn restore_user<C: ConnectionTrait>(db: &C, user_id: &str) -> Result<bool, DbErr> {
+    let result = userdata::Entity::update_many()
+        .col_expr(userdata::Column::DeletedAt, Expr::value(Value::ChronoDateTime(None)))
+        .filter(userdata::Column::UserId.eq(user_id))
+        .filter(userdata::Column::DeletedAt.is_not_null())
+        .exec(db)
+        .await?;
+    Ok(result.rows_affected == 1)
+}
+
+/// Delete every account marked before `before` with [delete_user_data]
+/// Returns the ids of the purged users
+pub async fn purge_deleted<C: ConnectionTrait + TransactionTrait>(
+    db: &C,
+    before: NaiveDateTime,
+) -> Result<Vec<String>, DbErr> {
+    let user_ids: Vec<String> = userdata::Entity::find()
+        .filter(userdata::Column::DeletedAt.lte(before))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|user| user.user_id)
+        .collect();
+    for user_id in &user_ids {
+        delete_user_data(db, user_id).await?;
+    }
+    Ok(user_ids)
+}
+
+/// Remove the user from the rating ranking and drop the live battle, all or nothing
+pub async fn delete_user_cache(connect: &mut AsyncConnection, user_id: &str) -> anyhow::Result<()> {
+    let _: () = redis::pipe()
+        .atomic()
+        .zrem(RATING_KEY, user_id)
+        .ignore()
+        .del(session_key(user_id))
+        .ignore()
+        .query_async(connect)
+        .await?;
+    Ok(())
+}
+
+fn involved_in_duel(user_id: &str) -> Condition {
+    Condition::any()
+        .add(duel::Column::WinnerId.eq(user_id))
+        .add(duel::Column::LoserId.eq(user_id))
+}
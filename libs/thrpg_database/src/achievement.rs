@@ -0,0 +1,35 @@
+use chrono::NaiveDateTime;
+use sea_orm::{entity::prelude::*, ConnectionTrait, DeriveEntityModel};
+
+/// Progress of an achievement of a user
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "achievement")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub user_id: String,
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub achievement_id: String,
+    pub progress: i64,
+    /// `None` until the achievement is unlocked
+    pub unlocked_at: Option<NaiveDateTime>,
+}
+
+#[derive(Clone, Copy, Debug, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        panic!("No RelationDef")
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// Unlocked achievements of the user
+pub async fn unlocked<C: ConnectionTrait>(db: &C, user_id: &str) -> Result<Vec<Model>, DbErr> {
+    Entity::find()
+        .filter(Column::UserId.eq(user_id))
+        .filter(Column::UnlockedAt.is_not_null())
+        .all(db)
+        .await
+}
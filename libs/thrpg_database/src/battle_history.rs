@@ -0,0 +1,100 @@
+use chrono::NaiveDateTime;
+use sea_orm::{entity::prelude::*, DeriveEntityModel};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// A finished battle seen from one user, a duel has a row for each of the two users
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "battle_history")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    pub battle_uuid: Uuid,
+    pub user_id: String,
+    /// `PlayMode::kind` of the battle, `Story` for every story
+    pub mode: String,
+    /// `chara/{chara}.toml`
+    pub chara: String,
+    pub chara_level: i64,
+    /// The other user of a duel, `None` against the computer
+    pub opponent_id: Option<String>,
+    /// Display name of the opposing character
+    pub opponent_name: String,
+    pub opponent_level: i64,
+    pub won: bool,
+    pub turns: i64,
+    pub damage_dealt: i64,
+    pub damage_taken: i64,
+    pub started_at: NaiveDateTime,
+    pub finished_at: NaiveDateTime,
+}
+
+#[derive(Clone, Copy, Debug, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        panic!("No RelationDef")
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// Numbers of `/stats` and `GET {url}/stats/:user_id`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct BattleStats {
+    pub battles: u64,
+    pub wins: u64,
+    pub losses: u64,
+    /// 0.0 to 1.0, 0.0 without any battle
+    pub win_rate: f64,
+    /// Character used in the most battles
+    pub favorite_chara: Option<String>,
+    pub longest_win_streak: u64,
+    /// Wins since the last loss
+    pub current_win_streak: u64,
+    pub damage_dealt: i64,
+    pub damage_taken: i64,
+    pub average_turns: f64,
+    pub average_seconds: f64,
+    /// Battles of each mode
+    pub modes: HashMap<String, u64>,
+}
+
+impl BattleStats {
+    /// `history` must be oldest first for the streaks
+    pub fn from_history(history: &[Model]) -> Self {
+        let mut stats = BattleStats::default();
+        let mut charas: HashMap<&str, u64> = HashMap::new();
+        let mut turns = 0;
+        let mut seconds = 0;
+        for battle in history {
+            stats.battles += 1;
+            if battle.won {
+                stats.wins += 1;
+                stats.current_win_streak += 1;
+                stats.longest_win_streak = stats.longest_win_streak.max(stats.current_win_streak);
+            } else {
+                stats.losses += 1;
+                stats.current_win_streak = 0;
+            }
+            stats.damage_dealt += battle.damage_dealt;
+            stats.damage_taken += battle.damage_taken;
+            turns += battle.turns;
+            seconds += (battle.finished_at - battle.started_at).num_seconds().max(0);
+            *charas.entry(&battle.chara).or_default() += 1;
+            *stats.modes.entry(battle.mode.clone()).or_default() += 1;
+        }
+        if stats.battles > 0 {
+            stats.win_rate = stats.wins as f64 / stats.battles as f64;
+            stats.average_turns = turns as f64 / stats.battles as f64;
+            stats.average_seconds = seconds as f64 / stats.battles as f64;
+        }
+        // Ties go to the character whose name comes first, so the result is stable
+        stats.favorite_chara = charas
+            .into_iter()
+            .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(chara, _)| chara.to_string());
+        stats
+    }
+}
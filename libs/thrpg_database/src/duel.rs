@@ -0,0 +1,30 @@
+use chrono::NaiveDateTime;
+use sea_orm::{entity::prelude::*, DeriveEntityModel};
+use uuid::Uuid;
+
+/// Result of a user vs user battle
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "duel")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub battle_uuid: Uuid,
+    pub winner_id: String,
+    pub loser_id: String,
+    pub winner_chara: String,
+    pub loser_chara: String,
+    pub elapsed_turns: i64,
+    /// The loser timed out instead of being defeated
+    pub forfeit: bool,
+    pub finished_at: NaiveDateTime,
+}
+
+#[derive(Clone, Copy, Debug, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        panic!("No RelationDef")
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,53 @@
+use sea_orm::{entity::prelude::*, ConnectionTrait, DeriveEntityModel};
+
+/// Gear worn by an owned character, one row per slot
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "equipment")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub user_id: String,
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub chara: String,
+    /// `Weapon`, `Charm` or `Accessory`
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub slot: String,
+    /// `equipment/{equipment_id}.toml`
+    pub equipment_id: String,
+}
+
+#[derive(Clone, Copy, Debug, EnumIter)]
+pub enum Relation {
+    Roster,
+}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        match self {
+            &Self::Roster => Entity::belongs_to(crate::roster::Entity)
+                .from((Column::UserId, Column::Chara))
+                .to((crate::roster::Column::UserId, crate::roster::Column::Chara))
+                .into(),
+        }
+    }
+}
+
+impl Related<crate::roster::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Roster.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// Gear ids of the character
+pub async fn equipped<C: ConnectionTrait>(
+    db: &C,
+    user_id: &str,
+    chara: &str,
+) -> Result<Vec<Model>, DbErr> {
+    Entity::find()
+        .filter(Column::UserId.eq(user_id))
+        .filter(Column::Chara.eq(chara))
+        .all(db)
+        .await
+}
@@ -0,0 +1,136 @@
+use chrono::NaiveDateTime;
+use sea_orm::{entity::prelude::*, sea_query::OnConflict, ActiveValue, ConnectionTrait, DeriveEntityModel};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+/// Settings changed by `/settings` in a guild
+/// `None` falls back to `THRPG.toml`
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "guild_settings")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub guild_id: String,
+    pub prefix: Option<String>,
+    pub language: Option<String>,
+    /// JSON array of channel ids where battles can start, every channel when `None`
+    pub battle_channels: Option<Json>,
+    pub stamina_enabled: Option<bool>,
+    pub difficulty: Option<String>,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Clone, Copy, Debug, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        panic!("No RelationDef")
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    /// Settings of a guild where nothing is changed yet
+    pub fn empty(guild_id: &str, now: NaiveDateTime) -> Self {
+        Self {
+            guild_id: guild_id.to_string(),
+            prefix: None,
+            language: None,
+            battle_channels: None,
+            stamina_enabled: None,
+            difficulty: None,
+            updated_at: now,
+        }
+    }
+
+    /// Ids in `battle_channels`, `None` when battles can start anywhere
+    pub fn battle_channel_ids(&self) -> Option<Vec<u64>> {
+        self.battle_channels
+            .as_ref()
+            .and_then(|channels| serde_json::from_value(channels.clone()).ok())
+    }
+
+    /// An empty list is stored as `None`, so removing the last channel opens every channel again
+    pub fn set_battle_channel_ids(&mut self, ids: Vec<u64>) {
+        self.battle_channels = match ids.is_empty() {
+            true => None,
+            false => Some(serde_json::json!(ids)),
+        };
+    }
+}
+
+pub async fn find<C: ConnectionTrait>(db: &C, guild_id: &str) -> Result<Option<Model>, DbErr> {
+    Entity::find_by_id(guild_id.to_string()).one(db).await
+}
+
+/// Insert the settings, or overwrite the ones of the guild
+pub async fn save<C: ConnectionTrait>(db: &C, settings: Model) -> Result<(), DbErr> {
+    Entity::insert(ActiveModel {
+        guild_id: ActiveValue::Set(settings.guild_id),
+        prefix: ActiveValue::Set(settings.prefix),
+        language: ActiveValue::Set(settings.language),
+        battle_channels: ActiveValue::Set(settings.battle_channels),
+        stamina_enabled: ActiveValue::Set(settings.stamina_enabled),
+        difficulty: ActiveValue::Set(settings.difficulty),
+        updated_at: ActiveValue::Set(settings.updated_at),
+    })
+    .on_conflict(
+        OnConflict::column(Column::GuildId)
+            .update_columns([
+                Column::Prefix,
+                Column::Language,
+                Column::BattleChannels,
+                Column::StaminaEnabled,
+                Column::Difficulty,
+                Column::UpdatedAt,
+            ])
+            .to_owned(),
+    )
+    .exec(db)
+    .await?;
+    Ok(())
+}
+
+/// Back to `THRPG.toml` for every setting
+pub async fn reset<C: ConnectionTrait>(db: &C, guild_id: &str) -> Result<(), DbErr> {
+    Entity::delete_by_id(guild_id.to_string()).exec(db).await?;
+    Ok(())
+}
+
+/// Settings read in the last `ttl`, so a command doesn't query them every time
+/// Changes made by another process are seen once the entry expires
+#[derive(Debug)]
+pub struct GuildSettingsCache {
+    ttl: Duration,
+    entries: RwLock<HashMap<String, (Option<Model>, Instant)>>,
+}
+
+impl GuildSettingsCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub async fn get<C: ConnectionTrait>(&self, db: &C, guild_id: &str) -> Result<Option<Model>, DbErr> {
+        if let Some((settings, read_at)) = self.entries.read().unwrap().get(guild_id) {
+            if read_at.elapsed() < self.ttl {
+                return Ok(settings.clone());
+            }
+        }
+        let settings = find(db, guild_id).await?;
+        self.entries
+            .write()
+            .unwrap()
+            .insert(guild_id.to_string(), (settings.clone(), Instant::now()));
+        Ok(settings)
+    }
+
+    /// Forget the guild after changing its settings
+    pub fn invalidate(&self, guild_id: &str) {
+        self.entries.write().unwrap().remove(guild_id);
+    }
+}
@@ -0,0 +1,51 @@
+use chrono::NaiveDateTime;
+use sea_orm::{entity::prelude::*, ConnectionTrait, DeriveEntityModel, QueryOrder};
+
+/// Completion time of an incident, a user can have many
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "incident_clear")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    pub user_id: String,
+    pub incident_id: String,
+    pub chara: String,
+    pub elapsed_seconds: i64,
+    pub defeats: i64,
+    pub cleared_at: NaiveDateTime,
+}
+
+#[derive(Clone, Copy, Debug, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        panic!("No RelationDef")
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// Fastest clear of each user, fastest first
+pub async fn best_times<C: ConnectionTrait>(
+    db: &C,
+    incident_id: &str,
+    limit: usize,
+) -> Result<Vec<Model>, DbErr> {
+    let clears = Entity::find()
+        .filter(Column::IncidentId.eq(incident_id))
+        .order_by_asc(Column::ElapsedSeconds)
+        .order_by_asc(Column::ClearedAt)
+        .all(db)
+        .await?;
+    let mut best: Vec<Model> = Vec::new();
+    for clear in clears {
+        if best.len() >= limit {
+            break;
+        }
+        if !best.iter().any(|b| b.user_id == clear.user_id) {
+            best.push(clear);
+        }
+    }
+    Ok(best)
+}
@@ -0,0 +1,50 @@
+use chrono::NaiveDateTime;
+use sea_orm::{entity::prelude::*, sea_query::OnConflict, ActiveValue, ConnectionTrait, DeriveEntityModel};
+
+/// Incident in progress, kept until it is cleared
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "incident_run")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub user_id: String,
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub incident_id: String,
+    /// Stage, carried HP and MP and the checkpoint
+    pub run: serde_json::Value,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Clone, Copy, Debug, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        panic!("No RelationDef")
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// Insert or overwrite the run of the user
+pub async fn save_run<C: ConnectionTrait>(
+    db: &C,
+    user_id: &str,
+    incident_id: &str,
+    run: serde_json::Value,
+    now: NaiveDateTime,
+) -> Result<(), DbErr> {
+    Entity::insert(ActiveModel {
+        user_id: ActiveValue::Set(user_id.to_string()),
+        incident_id: ActiveValue::Set(incident_id.to_string()),
+        run: ActiveValue::Set(run),
+        updated_at: ActiveValue::Set(now),
+    })
+    .on_conflict(
+        OnConflict::columns([Column::UserId, Column::IncidentId])
+            .update_columns([Column::Run, Column::UpdatedAt])
+            .to_owned(),
+    )
+    .exec(db)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use sea_orm::{
+    entity::prelude::*,
+    sea_query::{Expr, OnConflict},
+    ActiveValue, ConnectionTrait, DeriveEntityModel,
+};
+
+/// Items and equipment owned by a user
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "inventory")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub user_id: String,
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub item_id: String,
+    /// `Item` or `Equipment`
+    pub kind: String,
+    pub quantity: i64,
+}
+
+#[derive(Clone, Copy, Debug, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        panic!("No RelationDef")
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// Add items to the inventory of the user
+pub async fn add_item<C: ConnectionTrait>(
+    db: &C,
+    user_id: &str,
+    item_id: &str,
+    kind: &str,
+    quantity: i64,
+) -> Result<(), DbErr> {
+    Entity::insert(ActiveModel {
+        user_id: ActiveValue::Set(user_id.to_string()),
+        item_id: ActiveValue::Set(item_id.to_string()),
+        kind: ActiveValue::Set(kind.to_string()),
+        quantity: ActiveValue::Set(quantity),
+    })
+    .on_conflict(
+        OnConflict::columns([Column::UserId, Column::ItemId])
+            .update_expr((Column::Quantity, Expr::col((Entity, Column::Quantity)).add(quantity)))
+            .to_owned(),
+    )
+    .exec(db)
+    .await?;
+    Ok(())
+}
+
+/// Take items from the inventory of the user only if the user has enough
+pub async fn use_item<C: ConnectionTrait>(
+    db: &C,
+    user_id: &str,
+    item_id: &str,
+    quantity: i64,
+) -> Result<bool, DbErr> {
+    let result = Entity::update_many()
+        .col_expr(Column::Quantity, Expr::col(Column::Quantity).sub(quantity))
+        .filter(Column::UserId.eq(user_id))
+        .filter(Column::ItemId.eq(item_id))
+        .filter(Column::Quantity.gte(quantity))
+        .exec(db)
+        .await?;
+    Ok(result.rows_affected == 1)
+}
+
+/// Owned entries of the kind
+pub async fn owned<C: ConnectionTrait>(db: &C, user_id: &str, kind: &str) -> Result<Vec<Model>, DbErr> {
+    Entity::find()
+        .filter(Column::UserId.eq(user_id))
+        .filter(Column::Kind.eq(kind))
+        .filter(Column::Quantity.gt(0))
+        .all(db)
+        .await
+}
@@ -1,5 +1,23 @@
+pub mod account;
+pub mod achievement;
+pub mod battle_history;
+pub mod duel;
+pub mod equipment;
+pub mod guild_settings;
+pub mod incident_clear;
+pub mod incident_run;
+pub mod inventory;
+pub mod migration;
 pub mod playdata;
+pub mod pull_history;
+pub mod quest;
+pub mod rating;
+pub mod rating_history;
+pub mod repository;
+pub mod roster;
 pub mod score;
+pub mod session;
+pub mod tournament;
 pub mod userdata;
 
 pub mod redis_connect {
@@ -14,14 +32,55 @@ pub mod redis_connect {
         let connect = client.get_tokio_connection().await?;
         Ok(connect)
     }
+
+    /// A connection that can be cloned and used by many tasks at once
+    pub type MultiplexedConnection = redis::aio::MultiplexedConnection;
+
+    pub async fn connect_multiplexed(
+        url: impl Into<String>,
+    ) -> redis::RedisResult<MultiplexedConnection> {
+        let client = redis::Client::open(url.into())?;
+        client.get_multiplexed_tokio_connection().await
+    }
+}
+
+pub mod database_connect {
+    use sea_orm::{ConnectOptions, Database, DatabaseConnection, DbErr};
+    use std::time::Duration;
+
+    /// The backend is chosen by the url
+    /// url format:
+    /// "postgres://<username>:<password>@<hostname>:<port>/<database>"
+    /// "sqlite://<path>?mode=rwc" for a file, created if it does not exist
+    /// "sqlite::memory:" for a database that is gone when the bot stops
+    pub async fn connect(url: impl Into<String>) -> Result<DatabaseConnection, DbErr> {
+        let url = url.into();
+        let mut options = ConnectOptions::new(url.clone());
+        if is_in_memory(&url) {
+            // An in-memory database lives only as long as its connection, so the pool keeps one forever
+            let forever = Duration::from_secs(60 * 60 * 24 * 365 * 100);
+            options
+                .min_connections(1)
+                .max_connections(1)
+                .idle_timeout(forever)
+                .max_lifetime(forever);
+        }
+        Database::connect(options).await
+    }
+
+    /// The database is gone with its last connection, a new connection opens an empty one
+    pub fn is_in_memory(url: &str) -> bool {
+        url.starts_with("sqlite:") && (url.contains(":memory:") || url.contains("mode=memory"))
+    }
 }
 
 pub mod postgres_connect {
     /// url format:
     /// "postgres://<username>:<password>@<hostname>:<port>/<database>"
+    #[deprecated(note = "use database_connect::connect, it takes SQLite urls too")]
     pub async fn connect(
         url: impl Into<String>,
     ) -> Result<sea_orm::DatabaseConnection, sea_orm::DbErr> {
-        sea_orm::Database::connect(url.into().to_owned()).await
+        super::database_connect::connect(url).await
     }
 }
@@ -0,0 +1,475 @@
+// The idens are named after the tables and columns, like `rating.rating`
+#![allow(clippy::enum_variant_names)]
+
+use sea_orm_migration::prelude::*;
+
+/// Every table of the old `sqls/init.sql`, in the shape of the entities
+/// Tables that already exist are left as they are, `m20261019_000002_match_entities` fixes them
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Userdata::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Userdata::UserId).text().not_null().primary_key())
+                    .col(ColumnDef::new(Userdata::Player).text().not_null())
+                    .col(ColumnDef::new(Userdata::Assist).text())
+                    .col(ColumnDef::new(Userdata::BattleUuid).uuid())
+                    .col(ColumnDef::new(Userdata::Gold).big_integer().not_null().default(0i64))
+                    .col(ColumnDef::new(Userdata::Stamina).big_integer().not_null().default(100i64))
+                    .col(
+                        ColumnDef::new(Userdata::StaminaUpdatedAt)
+                            .date_time()
+                            .not_null()
+                            .extra("DEFAULT CURRENT_TIMESTAMP".to_owned()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Roster::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Roster::UserId).text().not_null())
+                    .col(ColumnDef::new(Roster::Chara).text().not_null())
+                    .col(ColumnDef::new(Roster::Level).big_integer().not_null())
+                    .col(ColumnDef::new(Roster::Exp).big_integer().not_null())
+                    .col(ColumnDef::new(Roster::Affinity).big_integer().not_null().default(0i64))
+                    .col(ColumnDef::new(Roster::UnlockedAt).date_time().not_null())
+                    .primary_key(Index::create().col(Roster::UserId).col(Roster::Chara))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Roster::Table, Roster::UserId)
+                            .to(Userdata::Table, Userdata::UserId)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Playdata::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Playdata::BattleUuid).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(Playdata::Player).json().not_null())
+                    .col(ColumnDef::new(Playdata::Enemy).json().not_null())
+                    .col(ColumnDef::new(Playdata::ElapesdTurns).big_integer().not_null())
+                    .col(ColumnDef::new(Playdata::StartTime).date_time().not_null())
+                    .col(ColumnDef::new(Playdata::PlayMode).text().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Duel::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Duel::BattleUuid).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(Duel::WinnerId).text().not_null())
+                    .col(ColumnDef::new(Duel::LoserId).text().not_null())
+                    .col(ColumnDef::new(Duel::WinnerChara).text().not_null())
+                    .col(ColumnDef::new(Duel::LoserChara).text().not_null())
+                    .col(ColumnDef::new(Duel::ElapsedTurns).big_integer().not_null())
+                    .col(ColumnDef::new(Duel::Forfeit).boolean().not_null())
+                    .col(ColumnDef::new(Duel::FinishedAt).date_time().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Rating::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Rating::UserId).text().not_null().primary_key())
+                    .col(ColumnDef::new(Rating::Rating).double().not_null())
+                    .col(ColumnDef::new(Rating::Deviation).double().not_null())
+                    .col(ColumnDef::new(Rating::Matches).big_integer().not_null())
+                    .col(ColumnDef::new(Rating::LastMatch).date_time().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(RatingHistory::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(RatingHistory::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(RatingHistory::UserId).text().not_null())
+                    .col(ColumnDef::new(RatingHistory::BattleUuid).uuid().not_null())
+                    .col(ColumnDef::new(RatingHistory::Rating).double().not_null())
+                    .col(ColumnDef::new(RatingHistory::Deviation).double().not_null())
+                    .col(ColumnDef::new(RatingHistory::RecordedAt).date_time().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Tournament::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Tournament::TournamentUuid).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(Tournament::GuildId).text().not_null())
+                    .col(ColumnDef::new(Tournament::Name).text().not_null())
+                    .col(ColumnDef::new(Tournament::Format).text().not_null())
+                    .col(ColumnDef::new(Tournament::Status).text().not_null())
+                    .col(ColumnDef::new(Tournament::Participants).json().not_null())
+                    .col(ColumnDef::new(Tournament::Bracket).json())
+                    .col(ColumnDef::new(Tournament::MatchTimeoutMinutes).big_integer().not_null())
+                    .col(ColumnDef::new(Tournament::CreatedAt).date_time().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(PullHistory::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PullHistory::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(PullHistory::UserId).text().not_null())
+                    .col(ColumnDef::new(PullHistory::PoolId).text().not_null())
+                    .col(ColumnDef::new(PullHistory::Chara).text().not_null())
+                    .col(ColumnDef::new(PullHistory::Cost).big_integer().not_null())
+                    .col(ColumnDef::new(PullHistory::Duplicate).boolean().not_null())
+                    .col(ColumnDef::new(PullHistory::PulledAt).date_time().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Inventory::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Inventory::UserId).text().not_null())
+                    .col(ColumnDef::new(Inventory::ItemId).text().not_null())
+                    .col(ColumnDef::new(Inventory::Kind).text().not_null())
+                    .col(
+                        ColumnDef::new(Inventory::Quantity)
+                            .big_integer()
+                            .not_null()
+                            .extra("CHECK (quantity >= 0)".to_owned()),
+                    )
+                    .primary_key(Index::create().col(Inventory::UserId).col(Inventory::ItemId))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Equipment::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Equipment::UserId).text().not_null())
+                    .col(ColumnDef::new(Equipment::Chara).text().not_null())
+                    .col(ColumnDef::new(Equipment::Slot).text().not_null())
+                    .col(ColumnDef::new(Equipment::EquipmentId).text().not_null())
+                    .primary_key(
+                        Index::create()
+                            .col(Equipment::UserId)
+                            .col(Equipment::Chara)
+                            .col(Equipment::Slot),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Equipment::Table, (Equipment::UserId, Equipment::Chara))
+                            .to(Roster::Table, (Roster::UserId, Roster::Chara))
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Achievement::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Achievement::UserId).text().not_null())
+                    .col(ColumnDef::new(Achievement::AchievementId).text().not_null())
+                    .col(ColumnDef::new(Achievement::Progress).big_integer().not_null())
+                    .col(ColumnDef::new(Achievement::UnlockedAt).date_time())
+                    .primary_key(
+                        Index::create()
+                            .col(Achievement::UserId)
+                            .col(Achievement::AchievementId),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(QuestProgress::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(QuestProgress::UserId).text().not_null())
+                    .col(ColumnDef::new(QuestProgress::QuestId).text().not_null())
+                    .col(ColumnDef::new(QuestProgress::Period).text().not_null())
+                    .col(ColumnDef::new(QuestProgress::Progress).big_integer().not_null())
+                    .col(ColumnDef::new(QuestProgress::CompletedAt).date_time())
+                    .primary_key(
+                        Index::create()
+                            .col(QuestProgress::UserId)
+                            .col(QuestProgress::QuestId)
+                            .col(QuestProgress::Period),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(IncidentRun::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(IncidentRun::UserId).text().not_null())
+                    .col(ColumnDef::new(IncidentRun::IncidentId).text().not_null())
+                    .col(ColumnDef::new(IncidentRun::Run).json().not_null())
+                    .col(ColumnDef::new(IncidentRun::UpdatedAt).date_time().not_null())
+                    .primary_key(
+                        Index::create()
+                            .col(IncidentRun::UserId)
+                            .col(IncidentRun::IncidentId),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(IncidentClear::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(IncidentClear::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(IncidentClear::UserId).text().not_null())
+                    .col(ColumnDef::new(IncidentClear::IncidentId).text().not_null())
+                    .col(ColumnDef::new(IncidentClear::Chara).text().not_null())
+                    .col(ColumnDef::new(IncidentClear::ElapsedSeconds).big_integer().not_null())
+                    .col(ColumnDef::new(IncidentClear::Defeats).big_integer().not_null())
+                    .col(ColumnDef::new(IncidentClear::ClearedAt).date_time().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("incident_clear_time")
+                    .table(IncidentClear::Table)
+                    .col(IncidentClear::IncidentId)
+                    .col(IncidentClear::ElapsedSeconds)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Children first because of the foreign keys
+        for table in [
+            IncidentClear::Table.into_iden(),
+            IncidentRun::Table.into_iden(),
+            QuestProgress::Table.into_iden(),
+            Achievement::Table.into_iden(),
+            Equipment::Table.into_iden(),
+            Inventory::Table.into_iden(),
+            PullHistory::Table.into_iden(),
+            Tournament::Table.into_iden(),
+            RatingHistory::Table.into_iden(),
+            Rating::Table.into_iden(),
+            Duel::Table.into_iden(),
+            Playdata::Table.into_iden(),
+            Roster::Table.into_iden(),
+            Userdata::Table.into_iden(),
+        ] {
+            manager
+                .drop_table(Table::drop().table(table).if_exists().to_owned())
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum Userdata {
+    Table,
+    UserId,
+    Player,
+    Assist,
+    BattleUuid,
+    Gold,
+    Stamina,
+    StaminaUpdatedAt,
+}
+
+#[derive(Iden)]
+enum Roster {
+    Table,
+    UserId,
+    Chara,
+    Level,
+    Exp,
+    Affinity,
+    UnlockedAt,
+}
+
+#[derive(Iden)]
+enum Playdata {
+    Table,
+    BattleUuid,
+    Player,
+    Enemy,
+    ElapesdTurns,
+    StartTime,
+    PlayMode,
+}
+
+#[derive(Iden)]
+enum Duel {
+    Table,
+    BattleUuid,
+    WinnerId,
+    LoserId,
+    WinnerChara,
+    LoserChara,
+    ElapsedTurns,
+    Forfeit,
+    FinishedAt,
+}
+
+#[derive(Iden)]
+enum Rating {
+    Table,
+    UserId,
+    Rating,
+    Deviation,
+    Matches,
+    LastMatch,
+}
+
+#[derive(Iden)]
+enum RatingHistory {
+    Table,
+    Id,
+    UserId,
+    BattleUuid,
+    Rating,
+    Deviation,
+    RecordedAt,
+}
+
+#[derive(Iden)]
+enum Tournament {
+    Table,
+    TournamentUuid,
+    GuildId,
+    Name,
+    Format,
+    Status,
+    Participants,
+    Bracket,
+    MatchTimeoutMinutes,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+enum PullHistory {
+    Table,
+    Id,
+    UserId,
+    PoolId,
+    Chara,
+    Cost,
+    Duplicate,
+    PulledAt,
+}
+
+#[derive(Iden)]
+enum Inventory {
+    Table,
+    UserId,
+    ItemId,
+    Kind,
+    Quantity,
+}
+
+#[derive(Iden)]
+enum Equipment {
+    Table,
+    UserId,
+    Chara,
+    Slot,
+    EquipmentId,
+}
+
+#[derive(Iden)]
+enum Achievement {
+    Table,
+    UserId,
+    AchievementId,
+    Progress,
+    UnlockedAt,
+}
+
+#[derive(Iden)]
+enum QuestProgress {
+    Table,
+    UserId,
+    QuestId,
+    Period,
+    Progress,
+    CompletedAt,
+}
+
+#[derive(Iden)]
+enum IncidentRun {
+    Table,
+    UserId,
+    IncidentId,
+    Run,
+    UpdatedAt,
+}
+
+#[derive(Iden)]
+enum IncidentClear {
+    Table,
+    Id,
+    UserId,
+    IncidentId,
+    Chara,
+    ElapsedSeconds,
+    Defeats,
+    ClearedAt,
+}
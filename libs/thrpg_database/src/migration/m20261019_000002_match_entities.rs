@@ -0,0 +1,191 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, DbBackend, Statement, TransactionTrait};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Databases made by the old `sqls/init.sql` and `sqls/migrations/*.sql` differ from the entities
+/// Brings them to the shape of `m20261019_000001_create_tables`, does nothing on databases created by it
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Columns added by `assist.sql`, `shop.sql`, `stamina.sql` and `bond.sql`
+const ADD_COLUMNS: [&str; 5] = [
+    "ALTER TABLE userdata ADD COLUMN IF NOT EXISTS assist text",
+    "ALTER TABLE userdata ADD COLUMN IF NOT EXISTS gold bigint NOT NULL DEFAULT 0",
+    "ALTER TABLE userdata ADD COLUMN IF NOT EXISTS stamina bigint NOT NULL DEFAULT 100",
+    "ALTER TABLE userdata ADD COLUMN IF NOT EXISTS stamina_updated_at timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP",
+    "ALTER TABLE roster ADD COLUMN IF NOT EXISTS affinity bigint NOT NULL DEFAULT 0",
+];
+
+/// Files of the characters, relative to where `bot migrate up` runs
+const CHARA_DIR: &str = "chara";
+
+/// `roster.sql`, the level and exp of `userdata` become the roster entry of the current character
+/// `userdata.player` holds a file id of [CHARA_DIR] by then
+const MOVE_LEVEL: [&str; 2] = [
+    "INSERT INTO roster (user_id, chara, level, exp, affinity, unlocked_at)
+        SELECT user_id, player, COALESCE(level, 1), COALESCE(exp, 1), 0, CURRENT_TIMESTAMP
+        FROM userdata
+        WHERE player IS NOT NULL
+        ON CONFLICT (user_id, chara) DO NOTHING",
+    "ALTER TABLE userdata DROP COLUMN level, DROP COLUMN exp",
+];
+
+const MATCH_PLAYDATA: [&str; 3] = [
+    "ALTER TABLE playdata DROP COLUMN IF EXISTS start_turn",
+    // Saved battles keep their characters, the missing parts start over
+    "UPDATE playdata SET
+        elapesd_turns = COALESCE(elapesd_turns, 0),
+        start_time = COALESCE(start_time, CURRENT_TIMESTAMP),
+        play_mode = COALESCE(play_mode, 'Simple')
+        WHERE elapesd_turns IS NULL OR start_time IS NULL OR play_mode IS NULL",
+    "ALTER TABLE playdata
+        ALTER COLUMN player SET NOT NULL,
+        ALTER COLUMN enemy SET NOT NULL,
+        ALTER COLUMN elapesd_turns SET NOT NULL,
+        ALTER COLUMN start_time SET NOT NULL,
+        ALTER COLUMN play_mode SET NOT NULL",
+];
+
+/// Rows that can't be filled in, they are reported so that they are fixed by hand
+const UNFIXABLE: [(&str, &str); 2] = [
+    ("userdata without a character", "SELECT user_id AS id FROM userdata WHERE player IS NULL"),
+    (
+        "playdata without a character",
+        "SELECT CAST(battle_uuid AS text) AS id FROM playdata WHERE player IS NULL OR enemy IS NULL",
+    ),
+];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Only PostgreSQL databases were made by `init.sql`
+        if manager.get_database_backend() != DbBackend::Postgres {
+            return Ok(());
+        }
+        let db = manager.get_connection();
+        let legacy = has_column(db, "userdata", "level").await?;
+        let mut report = unfixable_rows(db).await?;
+        // The old bot saved the name of the character instead of its file id
+        let mut renames = Vec::new();
+        if legacy {
+            let ids = chara_ids(Path::new(CHARA_DIR))?;
+            for (user_id, player) in legacy_players(db).await? {
+                match ids.get(&player.to_lowercase()) {
+                    Some(id) if *id != player => renames.push((player, id.clone())),
+                    Some(_) => {}
+                    None => report.push(format!(
+                        "userdata with a character not in {}/: {} ({})",
+                        CHARA_DIR, user_id, player
+                    )),
+                }
+            }
+        }
+        if !report.is_empty() {
+            return Err(DbErr::Migration(format!(
+                "fix or remove these rows, then run `bot migrate up` again\n{}",
+                report.join("\n")
+            )));
+        }
+
+        let txn = db.begin().await?;
+        for sql in ADD_COLUMNS {
+            execute(&txn, sql).await?;
+        }
+        if legacy {
+            renames.sort();
+            renames.dedup();
+            for (name, id) in renames {
+                txn.execute(Statement::from_sql_and_values(
+                    DbBackend::Postgres,
+                    "UPDATE userdata SET player = $1 WHERE player = $2",
+                    vec![id.into(), name.into()],
+                ))
+                .await?;
+            }
+            for sql in MOVE_LEVEL {
+                execute(&txn, sql).await?;
+            }
+        }
+        for sql in MATCH_PLAYDATA {
+            execute(&txn, sql).await?;
+        }
+        execute(&txn, "ALTER TABLE userdata ALTER COLUMN player SET NOT NULL").await?;
+        txn.commit().await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        match manager.get_database_backend() {
+            // `up` changed nothing
+            DbBackend::MySql | DbBackend::Sqlite => Ok(()),
+            // The level and exp were moved into `roster`, they can't be told apart from the ones earned later
+            DbBackend::Postgres => Err(DbErr::Migration(
+                "m20261019_000002_match_entities can't be reverted, restore a backup instead".to_owned(),
+            )),
+        }
+    }
+}
+
+async fn execute<C: ConnectionTrait>(db: &C, sql: &str) -> Result<(), DbErr> {
+    db.execute(Statement::from_string(DbBackend::Postgres, sql.to_owned()))
+        .await?;
+    Ok(())
+}
+
+/// File ids of the characters keyed by the lowercase file id and the lowercase `charabase.name`
+fn chara_ids(dir: &Path) -> Result<HashMap<String, String>, DbErr> {
+    let error = |e: &dyn std::fmt::Display| DbErr::Migration(format!("{}: {}", dir.display(), e));
+    let mut ids = HashMap::new();
+    for entry in std::fs::read_dir(dir).map_err(|e| error(&e))? {
+        let path = entry.map_err(|e| error(&e))?.path();
+        let id = match (path.extension(), path.file_stem().and_then(|s| s.to_str())) {
+            (Some(ext), Some(id)) if ext == "toml" => id.to_string(),
+            _ => continue,
+        };
+        let content = std::fs::read_to_string(&path).map_err(|e| error(&e))?;
+        let chara: toml::Value = toml::from_str(&content).map_err(|e| error(&e))?;
+        if let Some(name) = chara.get("charabase").and_then(|c| c.get("name")).and_then(|n| n.as_str()) {
+            ids.insert(name.to_lowercase(), id.clone());
+        }
+        ids.insert(id.to_lowercase(), id);
+    }
+    Ok(ids)
+}
+
+async fn legacy_players<C: ConnectionTrait>(db: &C) -> Result<Vec<(String, String)>, DbErr> {
+    let rows = db
+        .query_all(Statement::from_string(
+            DbBackend::Postgres,
+            "SELECT user_id, player FROM userdata WHERE player IS NOT NULL ORDER BY user_id".to_owned(),
+        ))
+        .await?;
+    rows.iter()
+        .map(|row| Ok((row.try_get("", "user_id")?, row.try_get("", "player")?)))
+        .collect()
+}
+
+async fn unfixable_rows<C: ConnectionTrait>(db: &C) -> Result<Vec<String>, DbErr> {
+    let mut report = Vec::new();
+    for (what, sql) in UNFIXABLE {
+        let rows = db
+            .query_all(Statement::from_string(DbBackend::Postgres, sql.to_owned()))
+            .await?;
+        for row in rows {
+            let id: String = row.try_get("", "id")?;
+            report.push(format!("{}: {}", what, id));
+        }
+    }
+    Ok(report)
+}
+
+async fn has_column<C: ConnectionTrait>(db: &C, table: &str, column: &str) -> Result<bool, DbErr> {
+    let row = db
+        .query_one(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            "SELECT 1 AS found FROM information_schema.columns
+                WHERE table_schema = current_schema() AND table_name = $1 AND column_name = $2",
+            vec![table.into(), column.into()],
+        ))
+        .await?;
+    Ok(row.is_some())
+}
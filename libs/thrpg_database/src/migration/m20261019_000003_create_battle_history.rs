@@ -0,0 +1,79 @@
+use sea_orm_migration::prelude::*;
+
+/// Every finished battle, see [battle_history](crate::battle_history)
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(BattleHistory::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(BattleHistory::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(BattleHistory::BattleUuid).uuid().not_null())
+                    .col(ColumnDef::new(BattleHistory::UserId).text().not_null())
+                    .col(ColumnDef::new(BattleHistory::Mode).text().not_null())
+                    .col(ColumnDef::new(BattleHistory::Chara).text().not_null())
+                    .col(ColumnDef::new(BattleHistory::CharaLevel).big_integer().not_null())
+                    .col(ColumnDef::new(BattleHistory::OpponentId).text())
+                    .col(ColumnDef::new(BattleHistory::OpponentName).text().not_null())
+                    .col(ColumnDef::new(BattleHistory::OpponentLevel).big_integer().not_null())
+                    .col(ColumnDef::new(BattleHistory::Won).boolean().not_null())
+                    .col(ColumnDef::new(BattleHistory::Turns).big_integer().not_null())
+                    .col(ColumnDef::new(BattleHistory::DamageDealt).big_integer().not_null())
+                    .col(ColumnDef::new(BattleHistory::DamageTaken).big_integer().not_null())
+                    .col(ColumnDef::new(BattleHistory::StartedAt).date_time().not_null())
+                    .col(ColumnDef::new(BattleHistory::FinishedAt).date_time().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // `/stats` reads the history of one user in order
+        manager
+            .create_index(
+                Index::create()
+                    .name("battle_history_user")
+                    .table(BattleHistory::Table)
+                    .col(BattleHistory::UserId)
+                    .col(BattleHistory::FinishedAt)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(BattleHistory::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum BattleHistory {
+    Table,
+    Id,
+    BattleUuid,
+    UserId,
+    Mode,
+    Chara,
+    CharaLevel,
+    OpponentId,
+    OpponentName,
+    OpponentLevel,
+    Won,
+    Turns,
+    DamageDealt,
+    DamageTaken,
+    StartedAt,
+    FinishedAt,
+}
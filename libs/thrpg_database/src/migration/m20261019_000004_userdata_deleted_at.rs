@@ -0,0 +1,36 @@
+use sea_orm_migration::prelude::*;
+
+/// `userdata.deleted_at` for accounts waiting to be purged after `/delete`
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Userdata::Table)
+                    .add_column(ColumnDef::new(Userdata::DeletedAt).date_time().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Userdata::Table)
+                    .drop_column(Userdata::DeletedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Userdata {
+    Table,
+    DeletedAt,
+}
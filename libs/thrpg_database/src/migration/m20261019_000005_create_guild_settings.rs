@@ -0,0 +1,44 @@
+use sea_orm_migration::prelude::*;
+
+/// Per guild settings of `/settings`, see [guild_settings](crate::guild_settings)
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(GuildSettings::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(GuildSettings::GuildId).text().not_null().primary_key())
+                    .col(ColumnDef::new(GuildSettings::Prefix).text())
+                    .col(ColumnDef::new(GuildSettings::Language).text())
+                    .col(ColumnDef::new(GuildSettings::BattleChannels).json())
+                    .col(ColumnDef::new(GuildSettings::StaminaEnabled).boolean())
+                    .col(ColumnDef::new(GuildSettings::Difficulty).text())
+                    .col(ColumnDef::new(GuildSettings::UpdatedAt).date_time().not_null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(GuildSettings::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum GuildSettings {
+    Table,
+    GuildId,
+    Prefix,
+    Language,
+    BattleChannels,
+    StaminaEnabled,
+    Difficulty,
+    UpdatedAt,
+}
@@ -0,0 +1,51 @@
+// The idens are named after the table and columns, like `score.score`
+#![allow(clippy::enum_variant_names)]
+use sea_orm_migration::prelude::*;
+
+/// Leaderboards when Redis is not configured, see [score](crate::score)
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Score::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Score::Board).text().not_null())
+                    .col(ColumnDef::new(Score::Member).text().not_null())
+                    .col(ColumnDef::new(Score::Score).big_integer().not_null())
+                    .col(ColumnDef::new(Score::UpdatedAt).date_time().not_null())
+                    .primary_key(Index::create().col(Score::Board).col(Score::Member))
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("score_board_score")
+                    .table(Score::Table)
+                    .col(Score::Board)
+                    .col(Score::Score)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Score::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Score {
+    Table,
+    Board,
+    Member,
+    Score,
+    UpdatedAt,
+}
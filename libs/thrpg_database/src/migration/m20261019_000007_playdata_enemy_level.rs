@@ -0,0 +1,42 @@
+use sea_orm_migration::prelude::*;
+
+/// `playdata.enemy_level` so a resumed battle pays and records the level the enemy was built with
+/// Battles saved before always had an enemy of level 1
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Playdata::Table)
+                    .add_column(
+                        ColumnDef::new(Playdata::EnemyLevel)
+                            .big_integer()
+                            .not_null()
+                            .default(1),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Playdata::Table)
+                    .drop_column(Playdata::EnemyLevel)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Playdata {
+    Table,
+    EnemyLevel,
+}
@@ -0,0 +1,183 @@
+//! Versioned schema of the database
+//! Add a new `mYYYYMMDD_NNNNNN_name` module for every change and append it to [Migrator::migrations]
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{
+    ConnectionTrait, DatabaseConnection, DbBackend, EntityTrait, Schema, Statement,
+};
+use sea_orm_migration::seaql_migrations;
+
+mod m20261019_000001_create_tables;
+mod m20261019_000002_match_entities;
+mod m20261019_000003_create_battle_history;
+mod m20261019_000004_userdata_deleted_at;
+mod m20261019_000005_create_guild_settings;
+mod m20261019_000006_create_score;
+mod m20261019_000007_playdata_enemy_level;
+
+pub use sea_orm_migration::MigratorTrait;
+
+pub struct Migrator;
+
+#[async_trait::async_trait]
+impl MigratorTrait for Migrator {
+    fn migrations() -> Vec<Box<dyn MigrationTrait>> {
+        vec![
+            Box::new(m20261019_000001_create_tables::Migration),
+            Box::new(m20261019_000002_match_entities::Migration),
+            Box::new(m20261019_000003_create_battle_history::Migration),
+            Box::new(m20261019_000004_userdata_deleted_at::Migration),
+            Box::new(m20261019_000005_create_guild_settings::Migration),
+            Box::new(m20261019_000006_create_score::Migration),
+            Box::new(m20261019_000007_playdata_enemy_level::Migration),
+        ]
+    }
+}
+
+/// Names of the migrations and whether they are applied, in order
+pub async fn migration_status(db: &DatabaseConnection) -> Result<Vec<(String, bool)>, DbErr> {
+    Migrator::install(db).await?;
+    let applied: Vec<String> = seaql_migrations::Entity::find()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|model| model.version)
+        .collect();
+    Ok(Migrator::migrations()
+        .iter()
+        .map(|migration| {
+            let name = migration.name().to_string();
+            let done = applied.contains(&name);
+            (name, done)
+        })
+        .collect())
+}
+
+/// Differences between the entities and the tables of the database, empty if they match
+/// Column types are only checked on PostgreSQL, SQLite accepts any value in any column
+pub async fn check_schema(db: &DatabaseConnection) -> Result<Vec<String>, DbErr> {
+    if db.get_database_backend() == DbBackend::MySql {
+        return Ok(Vec::new());
+    }
+    let mut mismatches = Vec::new();
+    mismatches.extend(check_entity(db, crate::achievement::Entity).await?);
+    mismatches.extend(check_entity(db, crate::battle_history::Entity).await?);
+    mismatches.extend(check_entity(db, crate::duel::Entity).await?);
+    mismatches.extend(check_entity(db, crate::equipment::Entity).await?);
+    mismatches.extend(check_entity(db, crate::guild_settings::Entity).await?);
+    mismatches.extend(check_entity(db, crate::score::Entity).await?);
+    mismatches.extend(check_entity(db, crate::incident_clear::Entity).await?);
+    mismatches.extend(check_entity(db, crate::incident_run::Entity).await?);
+    mismatches.extend(check_entity(db, crate::inventory::Entity).await?);
+    mismatches.extend(check_entity(db, crate::playdata::Entity).await?);
+    mismatches.extend(check_entity(db, crate::pull_history::Entity).await?);
+    mismatches.extend(check_entity(db, crate::quest::Entity).await?);
+    mismatches.extend(check_entity(db, crate::rating::Entity).await?);
+    mismatches.extend(check_entity(db, crate::rating_history::Entity).await?);
+    mismatches.extend(check_entity(db, crate::roster::Entity).await?);
+    mismatches.extend(check_entity(db, crate::tournament::Entity).await?);
+    mismatches.extend(check_entity(db, crate::userdata::Entity).await?);
+    Ok(mismatches)
+}
+
+async fn check_entity<E: EntityTrait>(db: &DatabaseConnection, entity: E) -> Result<Vec<String>, DbErr> {
+    let table = entity.table_name().to_string();
+    let expected = Schema::new(db.get_database_backend()).create_table_from_entity(entity);
+    let actual = table_columns(db, &table).await?;
+    if actual.is_empty() {
+        return Ok(vec![format!("{}: the table does not exist", table)]);
+    }
+
+    let mut mismatches = Vec::new();
+    for column in expected.get_columns() {
+        let name = column.get_column_name();
+        let nullable = !column
+            .get_column_spec()
+            .iter()
+            .any(|spec| matches!(spec, ColumnSpec::NotNull));
+        let (_, data_type, actual_nullable) = match actual.iter().find(|(n, ..)| n == &name) {
+            Some(found) => found,
+            None => {
+                mismatches.push(format!("{}.{}: the column does not exist", table, name));
+                continue;
+            }
+        };
+        let column_type = column.get_column_type();
+        if let Some(data_type) = data_type {
+            let readable = match column_type {
+                Some(t) => postgres_types(t).contains(&data_type.as_str()),
+                None => false,
+            };
+            if !readable {
+                mismatches.push(format!(
+                    "{}.{}: {} in the database but {:?} in the entity",
+                    table, name, data_type, column_type
+                ));
+            }
+        }
+        if nullable != *actual_nullable {
+            mismatches.push(format!(
+                "{}.{}: nullable is {} in the database but {} in the entity",
+                table, name, actual_nullable, nullable
+            ));
+        }
+    }
+    for (name, ..) in &actual {
+        if !expected.get_columns().iter().any(|c| &c.get_column_name() == name) {
+            mismatches.push(format!("{}.{}: the column is not in the entity", table, name));
+        }
+    }
+    Ok(mismatches)
+}
+
+/// Name, type and nullability of every column of the table, the type is `None` on SQLite
+async fn table_columns(
+    db: &DatabaseConnection,
+    table: &str,
+) -> Result<Vec<(String, Option<String>, bool)>, DbErr> {
+    let backend = db.get_database_backend();
+    let sql = match backend {
+        DbBackend::Sqlite => {
+            r#"SELECT name AS column_name, NULL AS data_type, "notnull" = 0 AS nullable
+                FROM pragma_table_info(?)"#
+        }
+        _ => {
+            "SELECT column_name, data_type, is_nullable = 'YES' AS nullable
+                FROM information_schema.columns
+                WHERE table_schema = current_schema() AND table_name = $1"
+        }
+    };
+    db.query_all(Statement::from_sql_and_values(backend, sql, vec![table.into()]))
+        .await?
+        .iter()
+        .map(|row| {
+            Ok((
+                row.try_get("", "column_name")?,
+                row.try_get("", "data_type")?,
+                row.try_get("", "nullable")?,
+            ))
+        })
+        .collect()
+}
+
+/// `data_type`s of `information_schema.columns` that the entity can read
+fn postgres_types(column_type: &ColumnType) -> &'static [&'static str] {
+    match column_type {
+        ColumnType::Char(_) | ColumnType::String(_) | ColumnType::Text => {
+            &["text", "character varying", "character"]
+        }
+        ColumnType::SmallInteger(_) => &["smallint"],
+        ColumnType::Integer(_) => &["integer"],
+        ColumnType::BigInteger(_) => &["bigint"],
+        ColumnType::Float(_) => &["real"],
+        ColumnType::Double(_) => &["double precision"],
+        ColumnType::Boolean => &["boolean"],
+        ColumnType::Uuid => &["uuid"],
+        ColumnType::Json => &["json"],
+        ColumnType::JsonBinary => &["jsonb"],
+        ColumnType::Date => &["date"],
+        ColumnType::DateTime(_) | ColumnType::Timestamp(_) => &["timestamp without time zone"],
+        ColumnType::TimestampWithTimeZone(_) => &["timestamp with time zone"],
+        // PostgreSQL has no unsigned integers
+        _ => &[],
+    }
+}
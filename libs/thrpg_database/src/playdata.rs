@@ -5,31 +5,23 @@ use uuid::Uuid;
 #[derive(Debug, Clone, PartialEq, DeriveEntityModel)]
 #[sea_orm(table_name = "playdata")]
 pub struct Model {
-    #[sea_orm(primary_key)]
+    #[sea_orm(primary_key, auto_increment = false)]
     pub battle_uuid: Uuid,
     pub player: serde_json::Value,
     pub enemy: serde_json::Value,
-    pub elapesd_turns: u32,
+    pub elapesd_turns: i64,
     pub start_time: NaiveDateTime,
     pub play_mode: String,
+    /// Level the enemy was built with
+    pub enemy_level: i64,
 }
 
 #[derive(Clone, Copy, Debug, EnumIter)]
-pub enum Relation {
-    PlayData,
-}
+pub enum Relation {}
 
 impl RelationTrait for Relation {
     fn def(&self) -> RelationDef {
-        match self {
-            &Self::PlayData => Entity::has_many(Entity).into(),
-        }
-    }
-}
-
-impl Related<Entity> for Entity {
-    fn to() -> RelationDef {
-        Relation::PlayData.def()
+        panic!("No RelationDef")
     }
 }
 
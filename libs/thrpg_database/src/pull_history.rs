@@ -0,0 +1,28 @@
+use chrono::NaiveDateTime;
+use sea_orm::{entity::prelude::*, DeriveEntityModel};
+
+/// Log of random pulls for auditing
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "pull_history")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    pub user_id: String,
+    pub pool_id: String,
+    pub chara: String,
+    pub cost: i64,
+    /// The user already owned the character
+    pub duplicate: bool,
+    pub pulled_at: NaiveDateTime,
+}
+
+#[derive(Clone, Copy, Debug, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        panic!("No RelationDef")
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
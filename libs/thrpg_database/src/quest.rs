@@ -0,0 +1,42 @@
+use chrono::NaiveDateTime;
+use sea_orm::{entity::prelude::*, ConnectionTrait, DeriveEntityModel};
+
+/// Progress of a quest of a user in a rotation period
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "quest_progress")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub user_id: String,
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub quest_id: String,
+    /// `2022-08-01`, `2022-W31` or `permanent`
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub period: String,
+    pub progress: i64,
+    /// `None` until the quest is completed
+    pub completed_at: Option<NaiveDateTime>,
+}
+
+#[derive(Clone, Copy, Debug, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        panic!("No RelationDef")
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// Progress of the user in the periods
+pub async fn progress_in<C: ConnectionTrait>(
+    db: &C,
+    user_id: &str,
+    periods: Vec<String>,
+) -> Result<Vec<Model>, DbErr> {
+    Entity::find()
+        .filter(Column::UserId.eq(user_id))
+        .filter(Column::Period.is_in(periods))
+        .all(db)
+        .await
+}
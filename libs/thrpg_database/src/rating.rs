@@ -0,0 +1,49 @@
+use chrono::NaiveDateTime;
+use redis::{aio::ConnectionLike, AsyncCommands};
+use sea_orm::{entity::prelude::*, DeriveEntityModel};
+
+/// Redis sorted set for the top-N of ratings
+pub const RATING_KEY: &str = "thrpg:rating";
+
+/// Current PvP rating of a user
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "rating")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub user_id: String,
+    pub rating: f64,
+    pub deviation: f64,
+    pub matches: i64,
+    pub last_match: NaiveDateTime,
+}
+
+#[derive(Clone, Copy, Debug, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        panic!("No RelationDef")
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+pub async fn rating_cache<C: ConnectionLike + Send>(
+    connect: &mut C,
+    user_id: &str,
+    rating: f64,
+) -> anyhow::Result<()> {
+    let _: () = connect.zadd(RATING_KEY, user_id, rating).await?;
+    Ok(())
+}
+
+/// Users with the highest rating, best first
+pub async fn rating_top<C: ConnectionLike + Send>(
+    connect: &mut C,
+    count: isize,
+) -> anyhow::Result<Vec<(String, f64)>> {
+    let top: Vec<(String, f64)> = connect
+        .zrevrange_withscores(RATING_KEY, 0, count - 1)
+        .await?;
+    Ok(top)
+}
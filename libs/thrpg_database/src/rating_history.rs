@@ -0,0 +1,27 @@
+use chrono::NaiveDateTime;
+use sea_orm::{entity::prelude::*, DeriveEntityModel};
+use uuid::Uuid;
+
+/// Rating of a user after each ranked match
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "rating_history")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    pub user_id: String,
+    pub battle_uuid: Uuid,
+    pub rating: f64,
+    pub deviation: f64,
+    pub recorded_at: NaiveDateTime,
+}
+
+#[derive(Clone, Copy, Debug, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        panic!("No RelationDef")
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
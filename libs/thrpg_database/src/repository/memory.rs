@@ -0,0 +1,588 @@
+use super::{
+    BattleRepository, InventoryRepository, ProgressRepository, RatingUpdate, RepositoryTransaction,
+    RosterRepository, ScoreRepository, TournamentRepository, TournamentUpdate,
+    TransactionRepository, UserRepository,
+};
+use crate::tournament::STATUS_FINISHED;
+use crate::{
+    achievement, battle_history, duel, equipment, incident_clear, incident_run, inventory,
+    playdata, pull_history, quest, rating, rating_history, roster, tournament, userdata,
+};
+use async_trait::async_trait;
+use chrono::{Local, NaiveDateTime};
+use sea_orm::DbErr;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use uuid::Uuid;
+
+/// Repositories kept in memory, for tests of the game logic without any database
+/// Clones share the same data
+#[derive(Debug, Default, Clone)]
+pub struct MemoryRepository {
+    tables: Arc<Mutex<Tables>>,
+    /// Tables when the transaction began, put back by [RepositoryTransaction::rollback]
+    before: Option<Tables>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Tables {
+    users: HashMap<String, userdata::Model>,
+    battles: HashMap<Uuid, playdata::Model>,
+    duels: Vec<duel::Model>,
+    history: Vec<battle_history::Model>,
+    ratings: HashMap<String, rating::Model>,
+    rating_history: Vec<rating_history::Model>,
+    roster: HashMap<(String, String), roster::Model>,
+    equipment: HashMap<(String, String, String), equipment::Model>,
+    pulls: Vec<pull_history::Model>,
+    inventory: HashMap<(String, String), inventory::Model>,
+    achievements: HashMap<(String, String), achievement::Model>,
+    quests: HashMap<(String, String, String), quest::Model>,
+    runs: HashMap<(String, String), incident_run::Model>,
+    clears: Vec<incident_clear::Model>,
+    tournaments: HashMap<Uuid, tournament::Model>,
+}
+
+/// Save data of a new user playing `reimu` for tests, change the rest with `..test_user(id)`
+pub fn test_user(user_id: &str) -> userdata::Model {
+    userdata::Model {
+        user_id: user_id.to_string(),
+        player: "reimu".to_string(),
+        assist: None,
+        battle_uuid: None,
+        gold: 0,
+        stamina: 0,
+        stamina_updated_at: Local::now().naive_local(),
+        deleted_at: None,
+    }
+}
+
+impl MemoryRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Recorded duels, oldest first
+    pub fn duels(&self) -> Vec<duel::Model> {
+        self.tables().duels.clone()
+    }
+
+    /// Rating history of every user, oldest first
+    pub fn rating_history(&self) -> Vec<rating_history::Model> {
+        self.tables().rating_history.clone()
+    }
+
+    /// Gacha pulls of every user, oldest first
+    pub fn pulls(&self) -> Vec<pull_history::Model> {
+        self.tables().pulls.clone()
+    }
+
+    fn tables(&self) -> MutexGuard<'_, Tables> {
+        self.tables.lock().unwrap()
+    }
+
+    fn update_user<F: FnOnce(&mut userdata::Model)>(
+        &self,
+        user_id: &str,
+        f: F,
+    ) -> Result<(), DbErr> {
+        match self.tables().users.get_mut(user_id) {
+            Some(user) => {
+                f(user);
+                Ok(())
+            }
+            None => Err(DbErr::RecordNotFound(format!("userdata {}", user_id))),
+        }
+    }
+}
+
+fn key(user_id: &str, id: &str) -> (String, String) {
+    (user_id.to_string(), id.to_string())
+}
+
+#[async_trait]
+impl UserRepository for MemoryRepository {
+    async fn find_user(&self, user_id: &str) -> Result<Option<userdata::Model>, DbErr> {
+        Ok(self.tables().users.get(user_id).cloned())
+    }
+
+    async fn insert_user(&self, user: userdata::Model) -> Result<userdata::Model, DbErr> {
+        let mut tables = self.tables();
+        if tables.users.contains_key(&user.user_id) {
+            return Err(DbErr::Exec(format!(
+                "userdata {} already exists",
+                user.user_id
+            )));
+        }
+        tables.users.insert(user.user_id.clone(), user.clone());
+        Ok(user)
+    }
+
+    async fn set_player(&self, user_id: &str, player: &str) -> Result<(), DbErr> {
+        self.update_user(user_id, |user| user.player = player.to_string())
+    }
+
+    async fn set_assist(&self, user_id: &str, assist: Option<String>) -> Result<(), DbErr> {
+        self.update_user(user_id, |user| user.assist = assist)
+    }
+
+    async fn set_battle(&self, user_id: &str, battle_uuid: Option<Uuid>) -> Result<(), DbErr> {
+        self.update_user(user_id, |user| user.battle_uuid = battle_uuid)
+    }
+
+    async fn delete_user(&self, user_id: &str) -> Result<bool, DbErr> {
+        Ok(self.tables().users.remove(user_id).is_some())
+    }
+
+    async fn spend_gold(&self, user_id: &str, amount: i64) -> Result<bool, DbErr> {
+        Ok(match self.tables().users.get_mut(user_id) {
+            Some(user) if user.gold >= amount => {
+                user.gold -= amount;
+                true
+            }
+            _ => false,
+        })
+    }
+
+    async fn add_gold(&self, user_id: &str, amount: i64) -> Result<(), DbErr> {
+        if let Some(user) = self.tables().users.get_mut(user_id) {
+            user.gold += amount;
+        }
+        Ok(())
+    }
+
+    async fn update_stamina(
+        &self,
+        user_id: &str,
+        before: (i64, NaiveDateTime),
+        after: (i64, NaiveDateTime),
+    ) -> Result<bool, DbErr> {
+        Ok(match self.tables().users.get_mut(user_id) {
+            Some(user) if (user.stamina, user.stamina_updated_at) == before => {
+                user.stamina = after.0;
+                user.stamina_updated_at = after.1;
+                true
+            }
+            _ => false,
+        })
+    }
+}
+
+#[async_trait]
+impl BattleRepository for MemoryRepository {
+    async fn find_battle(&self, battle_uuid: Uuid) -> Result<Option<playdata::Model>, DbErr> {
+        Ok(self.tables().battles.get(&battle_uuid).cloned())
+    }
+
+    async fn save_battle(&self, battle: playdata::Model) -> Result<(), DbErr> {
+        self.tables().battles.insert(battle.battle_uuid, battle);
+        Ok(())
+    }
+
+    async fn record_duel(&self, duel: duel::Model) -> Result<(), DbErr> {
+        let mut tables = self.tables();
+        if tables
+            .duels
+            .iter()
+            .any(|d| d.battle_uuid == duel.battle_uuid)
+        {
+            return Err(DbErr::Exec(format!(
+                "duel {} already exists",
+                duel.battle_uuid
+            )));
+        }
+        tables.duels.push(duel);
+        Ok(())
+    }
+
+    async fn record_history(&self, entries: Vec<battle_history::Model>) -> Result<(), DbErr> {
+        let history = &mut self.tables().history;
+        for entry in entries {
+            let id = history.len() as i64 + 1;
+            history.push(battle_history::Model { id, ..entry });
+        }
+        Ok(())
+    }
+
+    async fn history_of(&self, user_id: &str) -> Result<Vec<battle_history::Model>, DbErr> {
+        let mut history: Vec<battle_history::Model> = self
+            .tables()
+            .history
+            .iter()
+            .filter(|entry| entry.user_id == user_id)
+            .cloned()
+            .collect();
+        history.sort_by_key(|entry| (entry.finished_at, entry.id));
+        Ok(history)
+    }
+}
+
+#[async_trait]
+impl ScoreRepository for MemoryRepository {
+    async fn find_rating(&self, user_id: &str) -> Result<Option<rating::Model>, DbErr> {
+        Ok(self.tables().ratings.get(user_id).cloned())
+    }
+
+    async fn update_ratings(
+        &self,
+        battle_uuid: Uuid,
+        unrated: &[rating::Model],
+        update: RatingUpdate,
+    ) -> Result<Vec<rating::Model>, DbErr> {
+        let now = Local::now().naive_local();
+        // Held until the new ratings are stored, like the row locks of the database
+        let mut tables = self.tables();
+        let current = unrated
+            .iter()
+            .map(|model| tables.ratings.get(&model.user_id).unwrap_or(model).clone())
+            .collect();
+        let ratings = update(current);
+        for model in &ratings {
+            tables.ratings.insert(model.user_id.clone(), model.clone());
+            let id = tables.rating_history.len() as i64 + 1;
+            tables.rating_history.push(rating_history::Model {
+                id,
+                user_id: model.user_id.clone(),
+                battle_uuid,
+                rating: model.rating,
+                deviation: model.deviation,
+                recorded_at: now,
+            });
+        }
+        Ok(ratings)
+    }
+
+    async fn top_ratings(&self, count: u64) -> Result<Vec<rating::Model>, DbErr> {
+        let mut top: Vec<rating::Model> = self.tables().ratings.values().cloned().collect();
+        top.sort_by(|a, b| {
+            b.rating
+                .total_cmp(&a.rating)
+                .then_with(|| a.user_id.cmp(&b.user_id))
+        });
+        top.truncate(count as usize);
+        Ok(top)
+    }
+}
+
+#[async_trait]
+impl RosterRepository for MemoryRepository {
+    async fn find_roster(
+        &self,
+        user_id: &str,
+        chara: &str,
+    ) -> Result<Option<roster::Model>, DbErr> {
+        Ok(self.tables().roster.get(&key(user_id, chara)).cloned())
+    }
+
+    async fn find_or_unlock(&self, user_id: &str, chara: &str) -> Result<roster::Model, DbErr> {
+        let model = self
+            .tables()
+            .roster
+            .entry(key(user_id, chara))
+            .or_insert_with(|| roster::Model {
+                user_id: user_id.to_string(),
+                chara: chara.to_string(),
+                level: 1,
+                exp: 1,
+                affinity: 0,
+                unlocked_at: Local::now().naive_local(),
+            })
+            .clone();
+        Ok(model)
+    }
+
+    async fn unlock(&self, user_id: &str, chara: &str) -> Result<bool, DbErr> {
+        if self.find_roster(user_id, chara).await?.is_some() {
+            return Ok(false);
+        }
+        self.find_or_unlock(user_id, chara).await?;
+        Ok(true)
+    }
+
+    async fn set_level(
+        &self,
+        user_id: &str,
+        chara: &str,
+        level: i64,
+        exp: i64,
+    ) -> Result<(), DbErr> {
+        if let Some(model) = self.tables().roster.get_mut(&key(user_id, chara)) {
+            model.level = level;
+            model.exp = exp;
+        }
+        Ok(())
+    }
+
+    async fn add_affinity(&self, user_id: &str, chara: &str, amount: i64) -> Result<(), DbErr> {
+        if let Some(model) = self.tables().roster.get_mut(&key(user_id, chara)) {
+            model.affinity += amount;
+        }
+        Ok(())
+    }
+
+    async fn equipped(&self, user_id: &str, chara: &str) -> Result<Vec<equipment::Model>, DbErr> {
+        let mut gear: Vec<equipment::Model> = self
+            .tables()
+            .equipment
+            .values()
+            .filter(|gear| gear.user_id == user_id && gear.chara == chara)
+            .cloned()
+            .collect();
+        gear.sort_by(|a, b| a.slot.cmp(&b.slot));
+        Ok(gear)
+    }
+
+    async fn equip(&self, gear: equipment::Model) -> Result<Option<String>, DbErr> {
+        let slot = (gear.user_id.clone(), gear.chara.clone(), gear.slot.clone());
+        Ok(self
+            .tables()
+            .equipment
+            .insert(slot, gear)
+            .map(|current| current.equipment_id))
+    }
+
+    async fn record_pull(&self, pull: pull_history::Model) -> Result<(), DbErr> {
+        let pulls = &mut self.tables().pulls;
+        let id = pulls.len() as i64 + 1;
+        pulls.push(pull_history::Model { id, ..pull });
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl InventoryRepository for MemoryRepository {
+    async fn add_item(
+        &self,
+        user_id: &str,
+        item_id: &str,
+        kind: &str,
+        quantity: i64,
+    ) -> Result<(), DbErr> {
+        self.tables()
+            .inventory
+            .entry(key(user_id, item_id))
+            .or_insert_with(|| inventory::Model {
+                user_id: user_id.to_string(),
+                item_id: item_id.to_string(),
+                kind: kind.to_string(),
+                quantity: 0,
+            })
+            .quantity += quantity;
+        Ok(())
+    }
+
+    async fn use_item(&self, user_id: &str, item_id: &str, quantity: i64) -> Result<bool, DbErr> {
+        Ok(
+            match self.tables().inventory.get_mut(&key(user_id, item_id)) {
+                Some(entry) if entry.quantity >= quantity => {
+                    entry.quantity -= quantity;
+                    true
+                }
+                _ => false,
+            },
+        )
+    }
+
+    async fn owned(&self, user_id: &str, kind: &str) -> Result<Vec<inventory::Model>, DbErr> {
+        let mut owned: Vec<inventory::Model> = self
+            .tables()
+            .inventory
+            .values()
+            .filter(|entry| entry.user_id == user_id && entry.kind == kind && entry.quantity > 0)
+            .cloned()
+            .collect();
+        owned.sort_by(|a, b| a.item_id.cmp(&b.item_id));
+        Ok(owned)
+    }
+}
+
+#[async_trait]
+impl ProgressRepository for MemoryRepository {
+    async fn find_achievement(
+        &self,
+        user_id: &str,
+        achievement_id: &str,
+    ) -> Result<Option<achievement::Model>, DbErr> {
+        Ok(self
+            .tables()
+            .achievements
+            .get(&key(user_id, achievement_id))
+            .cloned())
+    }
+
+    async fn save_achievement(&self, achievement: achievement::Model) -> Result<(), DbErr> {
+        let id = key(&achievement.user_id, &achievement.achievement_id);
+        self.tables().achievements.insert(id, achievement);
+        Ok(())
+    }
+
+    async fn unlocked_achievements(&self, user_id: &str) -> Result<Vec<achievement::Model>, DbErr> {
+        let mut unlocked: Vec<achievement::Model> = self
+            .tables()
+            .achievements
+            .values()
+            .filter(|a| a.user_id == user_id && a.unlocked_at.is_some())
+            .cloned()
+            .collect();
+        unlocked.sort_by(|a, b| a.achievement_id.cmp(&b.achievement_id));
+        Ok(unlocked)
+    }
+
+    async fn find_quest(
+        &self,
+        user_id: &str,
+        quest_id: &str,
+        period: &str,
+    ) -> Result<Option<quest::Model>, DbErr> {
+        let id = (
+            user_id.to_string(),
+            quest_id.to_string(),
+            period.to_string(),
+        );
+        Ok(self.tables().quests.get(&id).cloned())
+    }
+
+    async fn save_quest(&self, quest: quest::Model) -> Result<(), DbErr> {
+        let id = (
+            quest.user_id.clone(),
+            quest.quest_id.clone(),
+            quest.period.clone(),
+        );
+        self.tables().quests.insert(id, quest);
+        Ok(())
+    }
+
+    async fn quests_in(
+        &self,
+        user_id: &str,
+        periods: Vec<String>,
+    ) -> Result<Vec<quest::Model>, DbErr> {
+        let mut quests: Vec<quest::Model> = self
+            .tables()
+            .quests
+            .values()
+            .filter(|q| q.user_id == user_id && periods.contains(&q.period))
+            .cloned()
+            .collect();
+        quests.sort_by(|a, b| (&a.quest_id, &a.period).cmp(&(&b.quest_id, &b.period)));
+        Ok(quests)
+    }
+
+    async fn find_run(
+        &self,
+        user_id: &str,
+        incident_id: &str,
+    ) -> Result<Option<incident_run::Model>, DbErr> {
+        Ok(self.tables().runs.get(&key(user_id, incident_id)).cloned())
+    }
+
+    async fn save_run(&self, run: incident_run::Model) -> Result<(), DbErr> {
+        let id = key(&run.user_id, &run.incident_id);
+        self.tables().runs.insert(id, run);
+        Ok(())
+    }
+
+    async fn delete_run(&self, user_id: &str, incident_id: &str) -> Result<(), DbErr> {
+        self.tables().runs.remove(&key(user_id, incident_id));
+        Ok(())
+    }
+
+    async fn record_clear(&self, clear: incident_clear::Model) -> Result<(), DbErr> {
+        let clears = &mut self.tables().clears;
+        let id = clears.len() as i64 + 1;
+        clears.push(incident_clear::Model { id, ..clear });
+        Ok(())
+    }
+
+    async fn best_times(
+        &self,
+        incident_id: &str,
+        limit: usize,
+    ) -> Result<Vec<incident_clear::Model>, DbErr> {
+        let mut clears: Vec<incident_clear::Model> = self
+            .tables()
+            .clears
+            .iter()
+            .filter(|clear| clear.incident_id == incident_id)
+            .cloned()
+            .collect();
+        clears.sort_by_key(|clear| (clear.elapsed_seconds, clear.cleared_at));
+        let mut best: Vec<incident_clear::Model> = Vec::new();
+        for clear in clears {
+            if best.len() >= limit {
+                break;
+            }
+            if !best.iter().any(|b| b.user_id == clear.user_id) {
+                best.push(clear);
+            }
+        }
+        Ok(best)
+    }
+}
+
+#[async_trait]
+impl TournamentRepository for MemoryRepository {
+    async fn find_tournament(
+        &self,
+        tournament_uuid: Uuid,
+    ) -> Result<Option<tournament::Model>, DbErr> {
+        Ok(self.tables().tournaments.get(&tournament_uuid).cloned())
+    }
+
+    async fn current_tournament(&self, guild_id: &str) -> Result<Option<tournament::Model>, DbErr> {
+        Ok(self
+            .tables()
+            .tournaments
+            .values()
+            .find(|t| t.guild_id == guild_id && t.status != STATUS_FINISHED)
+            .cloned())
+    }
+
+    async fn save_tournament(&self, tournament: tournament::Model) -> Result<(), DbErr> {
+        self.tables()
+            .tournaments
+            .insert(tournament.tournament_uuid, tournament);
+        Ok(())
+    }
+
+    async fn update_tournament(
+        &self,
+        tournament_uuid: Uuid,
+        update: TournamentUpdate,
+    ) -> Result<Option<tournament::Model>, DbErr> {
+        // Held until the tournament is stored, like the row lock of the database
+        let mut tables = self.tables();
+        let updated = tables.tournaments.get(&tournament_uuid).and_then(update);
+        if let Some(updated) = &updated {
+            tables.tournaments.insert(tournament_uuid, updated.clone());
+        }
+        Ok(updated)
+    }
+}
+
+/// Writes go to the shared tables at once, a rollback puts back the tables of [TransactionRepository::begin_transaction]
+#[async_trait]
+impl TransactionRepository for MemoryRepository {
+    type Transaction = MemoryRepository;
+
+    async fn begin_transaction(&self) -> Result<MemoryRepository, DbErr> {
+        Ok(MemoryRepository {
+            tables: self.tables.clone(),
+            before: Some(self.tables().clone()),
+        })
+    }
+}
+
+#[async_trait]
+impl RepositoryTransaction for MemoryRepository {
+    async fn commit(self) -> Result<(), DbErr> {
+        Ok(())
+    }
+
+    async fn rollback(self) -> Result<(), DbErr> {
+        if let Some(before) = self.before {
+            *self.tables.lock().unwrap() = before;
+        }
+        Ok(())
+    }
+}
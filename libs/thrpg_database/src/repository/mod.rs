@@ -0,0 +1,813 @@
+//! Storage of users, battles, scores and game progress behind traits
+//! Every SeaORM connection or transaction implements them, [MemoryRepository] keeps everything in memory
+mod memory;
+
+pub use memory::{test_user, MemoryRepository};
+
+use crate::{
+    achievement, battle_history, duel, equipment, incident_clear, incident_run, inventory,
+    playdata, pull_history, quest, rating, rating_history, roster, tournament, userdata,
+};
+use async_trait::async_trait;
+use chrono::{Local, NaiveDateTime};
+use sea_orm::{
+    sea_query::{Expr, OnConflict},
+    ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait, DatabaseTransaction, DbErr,
+    EntityTrait, ModelTrait, QueryFilter, QueryOrder, QuerySelect, TransactionTrait, Value,
+};
+use uuid::Uuid;
+
+/// Save data of the users, see [userdata]
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn find_user(&self, user_id: &str) -> Result<Option<userdata::Model>, DbErr>;
+    async fn insert_user(&self, user: userdata::Model) -> Result<userdata::Model, DbErr>;
+    /// Change the active character
+    async fn set_player(&self, user_id: &str, player: &str) -> Result<(), DbErr>;
+    async fn set_assist(&self, user_id: &str, assist: Option<String>) -> Result<(), DbErr>;
+    /// Remember the battle to resume, see [BattleRepository]
+    async fn set_battle(&self, user_id: &str, battle_uuid: Option<Uuid>) -> Result<(), DbErr>;
+    /// Returns `false` if the user has no save data
+    async fn delete_user(&self, user_id: &str) -> Result<bool, DbErr>;
+    /// Take gold from the user only if the balance is enough
+    async fn spend_gold(&self, user_id: &str, amount: i64) -> Result<bool, DbErr>;
+    async fn add_gold(&self, user_id: &str, amount: i64) -> Result<(), DbErr>;
+    /// Write the stamina only if nobody changed it since `before` was read
+    async fn update_stamina(
+        &self,
+        user_id: &str,
+        before: (i64, NaiveDateTime),
+        after: (i64, NaiveDateTime),
+    ) -> Result<bool, DbErr>;
+}
+
+/// Saved battles, the results of duels and the history of finished battles
+#[async_trait]
+pub trait BattleRepository: Send + Sync {
+    async fn find_battle(&self, battle_uuid: Uuid) -> Result<Option<playdata::Model>, DbErr>;
+    /// Insert the battle, or overwrite it if it is already saved
+    async fn save_battle(&self, battle: playdata::Model) -> Result<(), DbErr>;
+    async fn record_duel(&self, duel: duel::Model) -> Result<(), DbErr>;
+    /// The `id` of each entry is ignored and assigned on insert
+    async fn record_history(&self, entries: Vec<battle_history::Model>) -> Result<(), DbErr>;
+    /// Finished battles of the user, oldest first
+    async fn history_of(&self, user_id: &str) -> Result<Vec<battle_history::Model>, DbErr>;
+}
+
+/// Calculates the new ratings from the current ones, in the same order
+pub type RatingUpdate = Box<dyn FnOnce(Vec<rating::Model>) -> Vec<rating::Model> + Send>;
+
+/// PvP ratings of the users
+#[async_trait]
+pub trait ScoreRepository: Send + Sync {
+    async fn find_rating(&self, user_id: &str) -> Result<Option<rating::Model>, DbErr>;
+    /// Read the ratings of the users, then write the ones `update` returns together with their history, all or nothing
+    /// `unrated` is the rating of each user, used when the user has none yet
+    /// The ratings stay locked until they are written, so concurrent matches of a user are never lost
+    async fn update_ratings(
+        &self,
+        battle_uuid: Uuid,
+        unrated: &[rating::Model],
+        update: RatingUpdate,
+    ) -> Result<Vec<rating::Model>, DbErr>;
+    /// Users with the highest rating, best first
+    async fn top_ratings(&self, count: u64) -> Result<Vec<rating::Model>, DbErr>;
+}
+
+/// Characters owned by the users and their gear, see [roster] and [equipment]
+#[async_trait]
+pub trait RosterRepository: Send + Sync {
+    async fn find_roster(&self, user_id: &str, chara: &str)
+        -> Result<Option<roster::Model>, DbErr>;
+    /// Get the character of the user, adding it to the roster at level 1 if the user does not own it
+    async fn find_or_unlock(&self, user_id: &str, chara: &str) -> Result<roster::Model, DbErr>;
+    /// Returns `false` if the user already owns the character
+    async fn unlock(&self, user_id: &str, chara: &str) -> Result<bool, DbErr>;
+    async fn set_level(
+        &self,
+        user_id: &str,
+        chara: &str,
+        level: i64,
+        exp: i64,
+    ) -> Result<(), DbErr>;
+    async fn add_affinity(&self, user_id: &str, chara: &str, amount: i64) -> Result<(), DbErr>;
+    async fn equipped(&self, user_id: &str, chara: &str) -> Result<Vec<equipment::Model>, DbErr>;
+    /// Put the gear in its slot, returns the id of the gear that was there
+    async fn equip(&self, gear: equipment::Model) -> Result<Option<String>, DbErr>;
+    /// The `id` is ignored and assigned on insert
+    async fn record_pull(&self, pull: pull_history::Model) -> Result<(), DbErr>;
+}
+
+/// Items and gear owned by the users, see [inventory]
+#[async_trait]
+pub trait InventoryRepository: Send + Sync {
+    async fn add_item(
+        &self,
+        user_id: &str,
+        item_id: &str,
+        kind: &str,
+        quantity: i64,
+    ) -> Result<(), DbErr>;
+    /// Take items from the user only if the user has enough
+    async fn use_item(&self, user_id: &str, item_id: &str, quantity: i64) -> Result<bool, DbErr>;
+    /// Entries of the kind with at least one item
+    async fn owned(&self, user_id: &str, kind: &str) -> Result<Vec<inventory::Model>, DbErr>;
+}
+
+/// Achievements, quests and incidents of the users
+#[async_trait]
+pub trait ProgressRepository: Send + Sync {
+    async fn find_achievement(
+        &self,
+        user_id: &str,
+        achievement_id: &str,
+    ) -> Result<Option<achievement::Model>, DbErr>;
+    /// Insert the progress, or overwrite it if it is already saved
+    async fn save_achievement(&self, achievement: achievement::Model) -> Result<(), DbErr>;
+    async fn unlocked_achievements(&self, user_id: &str) -> Result<Vec<achievement::Model>, DbErr>;
+    async fn find_quest(
+        &self,
+        user_id: &str,
+        quest_id: &str,
+        period: &str,
+    ) -> Result<Option<quest::Model>, DbErr>;
+    /// Insert the progress, or overwrite it if it is already saved
+    async fn save_quest(&self, quest: quest::Model) -> Result<(), DbErr>;
+    /// Progress of the user in the periods
+    async fn quests_in(
+        &self,
+        user_id: &str,
+        periods: Vec<String>,
+    ) -> Result<Vec<quest::Model>, DbErr>;
+    async fn find_run(
+        &self,
+        user_id: &str,
+        incident_id: &str,
+    ) -> Result<Option<incident_run::Model>, DbErr>;
+    /// Insert the run, or overwrite it if it is already saved
+    async fn save_run(&self, run: incident_run::Model) -> Result<(), DbErr>;
+    async fn delete_run(&self, user_id: &str, incident_id: &str) -> Result<(), DbErr>;
+    /// The `id` is ignored and assigned on insert
+    async fn record_clear(&self, clear: incident_clear::Model) -> Result<(), DbErr>;
+    /// Fastest clear of each user, fastest first
+    async fn best_times(
+        &self,
+        incident_id: &str,
+        limit: usize,
+    ) -> Result<Vec<incident_clear::Model>, DbErr>;
+}
+
+/// Changes the tournament, `None` leaves it as it is
+pub type TournamentUpdate = Box<dyn FnOnce(&tournament::Model) -> Option<tournament::Model> + Send>;
+
+/// Tournaments of the guilds, see [tournament]
+#[async_trait]
+pub trait TournamentRepository: Send + Sync {
+    async fn find_tournament(
+        &self,
+        tournament_uuid: Uuid,
+    ) -> Result<Option<tournament::Model>, DbErr>;
+    /// The tournament of the guild that is not finished yet
+    async fn current_tournament(&self, guild_id: &str) -> Result<Option<tournament::Model>, DbErr>;
+    /// Insert the tournament, or overwrite it if it is already saved
+    async fn save_tournament(&self, tournament: tournament::Model) -> Result<(), DbErr>;
+    /// Read the tournament, then write the one `update` returns, all or nothing
+    /// The tournament stays locked until it is written, so concurrent joins and reports are never lost
+    /// Returns the written tournament, `None` if it does not exist or `update` left it as it is
+    async fn update_tournament(
+        &self,
+        tournament_uuid: Uuid,
+        update: TournamentUpdate,
+    ) -> Result<Option<tournament::Model>, DbErr>;
+}
+
+/// Every repository, for handlers that need several of them
+pub trait Repository:
+    UserRepository
+    + BattleRepository
+    + ScoreRepository
+    + RosterRepository
+    + InventoryRepository
+    + ProgressRepository
+    + TournamentRepository
+{
+}
+
+impl<R> Repository for R where
+    R: UserRepository
+        + BattleRepository
+        + ScoreRepository
+        + RosterRepository
+        + InventoryRepository
+        + ProgressRepository
+        + TournamentRepository
+{
+}
+
+/// Repositories that can write several changes all or nothing
+#[async_trait]
+pub trait TransactionRepository: Send + Sync {
+    type Transaction: Repository + RepositoryTransaction;
+    async fn begin_transaction(&self) -> Result<Self::Transaction, DbErr>;
+}
+
+/// Nothing written through the transaction is kept until [RepositoryTransaction::commit]
+#[async_trait]
+pub trait RepositoryTransaction: Send + Sync {
+    async fn commit(self) -> Result<(), DbErr>;
+    async fn rollback(self) -> Result<(), DbErr>;
+}
+
+#[async_trait]
+impl<C: ConnectionTrait + Send> UserRepository for C {
+    async fn find_user(&self, user_id: &str) -> Result<Option<userdata::Model>, DbErr> {
+        userdata::Entity::find_by_id(user_id.to_string())
+            .one(self)
+            .await
+    }
+
+    async fn insert_user(&self, user: userdata::Model) -> Result<userdata::Model, DbErr> {
+        userdata::ActiveModel {
+            user_id: ActiveValue::Set(user.user_id),
+            player: ActiveValue::Set(user.player),
+            assist: ActiveValue::Set(user.assist),
+            battle_uuid: ActiveValue::Set(user.battle_uuid),
+            gold: ActiveValue::Set(user.gold),
+            stamina: ActiveValue::Set(user.stamina),
+            stamina_updated_at: ActiveValue::Set(user.stamina_updated_at),
+            deleted_at: ActiveValue::Set(user.deleted_at),
+        }
+        .insert(self)
+        .await
+    }
+
+    async fn set_player(&self, user_id: &str, player: &str) -> Result<(), DbErr> {
+        set_userdata_column(self, user_id, userdata::Column::Player, player.into()).await
+    }
+
+    async fn set_assist(&self, user_id: &str, assist: Option<String>) -> Result<(), DbErr> {
+        set_userdata_column(self, user_id, userdata::Column::Assist, assist.into()).await
+    }
+
+    async fn set_battle(&self, user_id: &str, battle_uuid: Option<Uuid>) -> Result<(), DbErr> {
+        set_userdata_column(
+            self,
+            user_id,
+            userdata::Column::BattleUuid,
+            battle_uuid.into(),
+        )
+        .await
+    }
+
+    async fn delete_user(&self, user_id: &str) -> Result<bool, DbErr> {
+        let result = userdata::Entity::delete_by_id(user_id.to_string())
+            .exec(self)
+            .await?;
+        Ok(result.rows_affected == 1)
+    }
+
+    async fn spend_gold(&self, user_id: &str, amount: i64) -> Result<bool, DbErr> {
+        userdata::spend_gold(self, user_id, amount).await
+    }
+
+    async fn add_gold(&self, user_id: &str, amount: i64) -> Result<(), DbErr> {
+        userdata::add_gold(self, user_id, amount).await
+    }
+
+    async fn update_stamina(
+        &self,
+        user_id: &str,
+        before: (i64, NaiveDateTime),
+        after: (i64, NaiveDateTime),
+    ) -> Result<bool, DbErr> {
+        userdata::update_stamina(self, user_id, before, after).await
+    }
+}
+
+/// Only the column is written, so it never overwrites gold or stamina changed meanwhile
+async fn set_userdata_column<C: ConnectionTrait>(
+    db: &C,
+    user_id: &str,
+    column: userdata::Column,
+    value: Value,
+) -> Result<(), DbErr> {
+    let result = userdata::Entity::update_many()
+        .col_expr(column, Expr::value(value))
+        .filter(userdata::Column::UserId.eq(user_id))
+        .exec(db)
+        .await?;
+    match result.rows_affected {
+        0 => Err(DbErr::RecordNotFound(format!("userdata {}", user_id))),
+        _ => Ok(()),
+    }
+}
+
+#[async_trait]
+impl<C: ConnectionTrait + Send> BattleRepository for C {
+    async fn find_battle(&self, battle_uuid: Uuid) -> Result<Option<playdata::Model>, DbErr> {
+        playdata::Entity::find_by_id(battle_uuid).one(self).await
+    }
+
+    async fn save_battle(&self, battle: playdata::Model) -> Result<(), DbErr> {
+        playdata::Entity::insert(playdata::ActiveModel {
+            battle_uuid: ActiveValue::Set(battle.battle_uuid),
+            player: ActiveValue::Set(battle.player),
+            enemy: ActiveValue::Set(battle.enemy),
+            elapesd_turns: ActiveValue::Set(battle.elapesd_turns),
+            start_time: ActiveValue::Set(battle.start_time),
+            play_mode: ActiveValue::Set(battle.play_mode),
+            enemy_level: ActiveValue::Set(battle.enemy_level),
+        })
+        .on_conflict(
+            OnConflict::column(playdata::Column::BattleUuid)
+                .update_columns([
+                    playdata::Column::Player,
+                    playdata::Column::Enemy,
+                    playdata::Column::ElapesdTurns,
+                    playdata::Column::StartTime,
+                    playdata::Column::PlayMode,
+                    playdata::Column::EnemyLevel,
+                ])
+                .to_owned(),
+        )
+        .exec(self)
+        .await?;
+        Ok(())
+    }
+
+    async fn record_duel(&self, duel: duel::Model) -> Result<(), DbErr> {
+        duel::ActiveModel {
+            battle_uuid: ActiveValue::Set(duel.battle_uuid),
+            winner_id: ActiveValue::Set(duel.winner_id),
+            loser_id: ActiveValue::Set(duel.loser_id),
+            winner_chara: ActiveValue::Set(duel.winner_chara),
+            loser_chara: ActiveValue::Set(duel.loser_chara),
+            elapsed_turns: ActiveValue::Set(duel.elapsed_turns),
+            forfeit: ActiveValue::Set(duel.forfeit),
+            finished_at: ActiveValue::Set(duel.finished_at),
+        }
+        .insert(self)
+        .await?;
+        Ok(())
+    }
+
+    async fn record_history(&self, entries: Vec<battle_history::Model>) -> Result<(), DbErr> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        battle_history::Entity::insert_many(entries.into_iter().map(|entry| {
+            battle_history::ActiveModel {
+                id: ActiveValue::NotSet,
+                battle_uuid: ActiveValue::Set(entry.battle_uuid),
+                user_id: ActiveValue::Set(entry.user_id),
+                mode: ActiveValue::Set(entry.mode),
+                chara: ActiveValue::Set(entry.chara),
+                chara_level: ActiveValue::Set(entry.chara_level),
+                opponent_id: ActiveValue::Set(entry.opponent_id),
+                opponent_name: ActiveValue::Set(entry.opponent_name),
+                opponent_level: ActiveValue::Set(entry.opponent_level),
+                won: ActiveValue::Set(entry.won),
+                turns: ActiveValue::Set(entry.turns),
+                damage_dealt: ActiveValue::Set(entry.damage_dealt),
+                damage_taken: ActiveValue::Set(entry.damage_taken),
+                started_at: ActiveValue::Set(entry.started_at),
+                finished_at: ActiveValue::Set(entry.finished_at),
+            }
+        }))
+        .exec(self)
+        .await?;
+        Ok(())
+    }
+
+    async fn history_of(&self, user_id: &str) -> Result<Vec<battle_history::Model>, DbErr> {
+        battle_history::Entity::find()
+            .filter(battle_history::Column::UserId.eq(user_id))
+            .order_by_asc(battle_history::Column::FinishedAt)
+            .order_by_asc(battle_history::Column::Id)
+            .all(self)
+            .await
+    }
+}
+
+fn rating_active_model(model: &rating::Model) -> rating::ActiveModel {
+    rating::ActiveModel {
+        user_id: ActiveValue::Set(model.user_id.clone()),
+        rating: ActiveValue::Set(model.rating),
+        deviation: ActiveValue::Set(model.deviation),
+        matches: ActiveValue::Set(model.matches),
+        last_match: ActiveValue::Set(model.last_match),
+    }
+}
+
+#[async_trait]
+impl<C: ConnectionTrait + TransactionTrait + Send> ScoreRepository for C {
+    async fn find_rating(&self, user_id: &str) -> Result<Option<rating::Model>, DbErr> {
+        rating::Entity::find_by_id(user_id.to_string())
+            .one(self)
+            .await
+    }
+
+    async fn update_ratings(
+        &self,
+        battle_uuid: Uuid,
+        unrated: &[rating::Model],
+        update: RatingUpdate,
+    ) -> Result<Vec<rating::Model>, DbErr> {
+        let now = Local::now().naive_local();
+        let txn = self.begin().await?;
+        // Locked in the order of the ids, so two matches between the same users can't deadlock
+        let mut order: Vec<usize> = (0..unrated.len()).collect();
+        order.sort_by_key(|&i| &unrated[i].user_id);
+        let mut current = vec![None; unrated.len()];
+        for i in order {
+            let user_id = unrated[i].user_id.clone();
+            // Inserting first also locks the row of a user who has never played
+            rating::Entity::insert(rating_active_model(&unrated[i]))
+                .on_conflict(
+                    OnConflict::column(rating::Column::UserId)
+                        .update_column(rating::Column::UserId)
+                        .to_owned(),
+                )
+                .exec(&txn)
+                .await?;
+            let model = rating::Entity::find_by_id(user_id.clone())
+                .lock_exclusive()
+                .one(&txn)
+                .await?
+                .ok_or_else(|| DbErr::RecordNotFound(format!("rating {}", user_id)))?;
+            current[i] = Some(model);
+        }
+
+        let ratings = update(current.into_iter().flatten().collect());
+        for model in &ratings {
+            rating::Entity::insert(rating_active_model(model))
+                .on_conflict(
+                    OnConflict::column(rating::Column::UserId)
+                        .update_columns([
+                            rating::Column::Rating,
+                            rating::Column::Deviation,
+                            rating::Column::Matches,
+                            rating::Column::LastMatch,
+                        ])
+                        .to_owned(),
+                )
+                .exec(&txn)
+                .await?;
+
+            rating_history::ActiveModel {
+                id: ActiveValue::NotSet,
+                user_id: ActiveValue::Set(model.user_id.clone()),
+                battle_uuid: ActiveValue::Set(battle_uuid),
+                rating: ActiveValue::Set(model.rating),
+                deviation: ActiveValue::Set(model.deviation),
+                recorded_at: ActiveValue::Set(now),
+            }
+            .insert(&txn)
+            .await?;
+        }
+        txn.commit().await?;
+        Ok(ratings)
+    }
+
+    async fn top_ratings(&self, count: u64) -> Result<Vec<rating::Model>, DbErr> {
+        rating::Entity::find()
+            .order_by_desc(rating::Column::Rating)
+            .order_by_asc(rating::Column::UserId)
+            .limit(count)
+            .all(self)
+            .await
+    }
+}
+
+#[async_trait]
+impl<C: ConnectionTrait + Send> RosterRepository for C {
+    async fn find_roster(
+        &self,
+        user_id: &str,
+        chara: &str,
+    ) -> Result<Option<roster::Model>, DbErr> {
+        roster::Entity::find_by_id((user_id.to_string(), chara.to_string()))
+            .one(self)
+            .await
+    }
+
+    async fn find_or_unlock(&self, user_id: &str, chara: &str) -> Result<roster::Model, DbErr> {
+        roster::find_or_unlock(self, user_id, chara).await
+    }
+
+    async fn unlock(&self, user_id: &str, chara: &str) -> Result<bool, DbErr> {
+        roster::unlock(self, user_id, chara).await
+    }
+
+    async fn set_level(
+        &self,
+        user_id: &str,
+        chara: &str,
+        level: i64,
+        exp: i64,
+    ) -> Result<(), DbErr> {
+        roster::Entity::update_many()
+            .col_expr(roster::Column::Level, Expr::value(level))
+            .col_expr(roster::Column::Exp, Expr::value(exp))
+            .filter(roster::Column::UserId.eq(user_id))
+            .filter(roster::Column::Chara.eq(chara))
+            .exec(self)
+            .await?;
+        Ok(())
+    }
+
+    async fn add_affinity(&self, user_id: &str, chara: &str, amount: i64) -> Result<(), DbErr> {
+        roster::add_affinity(self, user_id, chara, amount).await
+    }
+
+    async fn equipped(&self, user_id: &str, chara: &str) -> Result<Vec<equipment::Model>, DbErr> {
+        equipment::equipped(self, user_id, chara).await
+    }
+
+    async fn equip(&self, gear: equipment::Model) -> Result<Option<String>, DbErr> {
+        let current = equipment::Entity::find_by_id((
+            gear.user_id.clone(),
+            gear.chara.clone(),
+            gear.slot.clone(),
+        ))
+        .one(self)
+        .await?;
+        equipment::Entity::insert(equipment::ActiveModel {
+            user_id: ActiveValue::Set(gear.user_id),
+            chara: ActiveValue::Set(gear.chara),
+            slot: ActiveValue::Set(gear.slot),
+            equipment_id: ActiveValue::Set(gear.equipment_id),
+        })
+        .on_conflict(
+            OnConflict::columns([
+                equipment::Column::UserId,
+                equipment::Column::Chara,
+                equipment::Column::Slot,
+            ])
+            .update_column(equipment::Column::EquipmentId)
+            .to_owned(),
+        )
+        .exec(self)
+        .await?;
+        Ok(current.map(|current| current.equipment_id))
+    }
+
+    async fn record_pull(&self, pull: pull_history::Model) -> Result<(), DbErr> {
+        pull_history::ActiveModel {
+            id: ActiveValue::NotSet,
+            user_id: ActiveValue::Set(pull.user_id),
+            pool_id: ActiveValue::Set(pull.pool_id),
+            chara: ActiveValue::Set(pull.chara),
+            cost: ActiveValue::Set(pull.cost),
+            duplicate: ActiveValue::Set(pull.duplicate),
+            pulled_at: ActiveValue::Set(pull.pulled_at),
+        }
+        .insert(self)
+        .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<C: ConnectionTrait + Send> InventoryRepository for C {
+    async fn add_item(
+        &self,
+        user_id: &str,
+        item_id: &str,
+        kind: &str,
+        quantity: i64,
+    ) -> Result<(), DbErr> {
+        inventory::add_item(self, user_id, item_id, kind, quantity).await
+    }
+
+    async fn use_item(&self, user_id: &str, item_id: &str, quantity: i64) -> Result<bool, DbErr> {
+        inventory::use_item(self, user_id, item_id, quantity).await
+    }
+
+    async fn owned(&self, user_id: &str, kind: &str) -> Result<Vec<inventory::Model>, DbErr> {
+        inventory::owned(self, user_id, kind).await
+    }
+}
+
+#[async_trait]
+impl<C: ConnectionTrait + Send> ProgressRepository for C {
+    async fn find_achievement(
+        &self,
+        user_id: &str,
+        achievement_id: &str,
+    ) -> Result<Option<achievement::Model>, DbErr> {
+        achievement::Entity::find_by_id((user_id.to_string(), achievement_id.to_string()))
+            .one(self)
+            .await
+    }
+
+    async fn save_achievement(&self, achievement: achievement::Model) -> Result<(), DbErr> {
+        achievement::Entity::insert(achievement::ActiveModel {
+            user_id: ActiveValue::Set(achievement.user_id),
+            achievement_id: ActiveValue::Set(achievement.achievement_id),
+            progress: ActiveValue::Set(achievement.progress),
+            unlocked_at: ActiveValue::Set(achievement.unlocked_at),
+        })
+        .on_conflict(
+            OnConflict::columns([
+                achievement::Column::UserId,
+                achievement::Column::AchievementId,
+            ])
+            .update_columns([
+                achievement::Column::Progress,
+                achievement::Column::UnlockedAt,
+            ])
+            .to_owned(),
+        )
+        .exec(self)
+        .await?;
+        Ok(())
+    }
+
+    async fn unlocked_achievements(&self, user_id: &str) -> Result<Vec<achievement::Model>, DbErr> {
+        achievement::unlocked(self, user_id).await
+    }
+
+    async fn find_quest(
+        &self,
+        user_id: &str,
+        quest_id: &str,
+        period: &str,
+    ) -> Result<Option<quest::Model>, DbErr> {
+        quest::Entity::find_by_id((
+            user_id.to_string(),
+            quest_id.to_string(),
+            period.to_string(),
+        ))
+        .one(self)
+        .await
+    }
+
+    async fn save_quest(&self, quest: quest::Model) -> Result<(), DbErr> {
+        quest::Entity::insert(quest::ActiveModel {
+            user_id: ActiveValue::Set(quest.user_id),
+            quest_id: ActiveValue::Set(quest.quest_id),
+            period: ActiveValue::Set(quest.period),
+            progress: ActiveValue::Set(quest.progress),
+            completed_at: ActiveValue::Set(quest.completed_at),
+        })
+        .on_conflict(
+            OnConflict::columns([
+                quest::Column::UserId,
+                quest::Column::QuestId,
+                quest::Column::Period,
+            ])
+            .update_columns([quest::Column::Progress, quest::Column::CompletedAt])
+            .to_owned(),
+        )
+        .exec(self)
+        .await?;
+        Ok(())
+    }
+
+    async fn quests_in(
+        &self,
+        user_id: &str,
+        periods: Vec<String>,
+    ) -> Result<Vec<quest::Model>, DbErr> {
+        quest::progress_in(self, user_id, periods).await
+    }
+
+    async fn find_run(
+        &self,
+        user_id: &str,
+        incident_id: &str,
+    ) -> Result<Option<incident_run::Model>, DbErr> {
+        incident_run::Entity::find_by_id((user_id.to_string(), incident_id.to_string()))
+            .one(self)
+            .await
+    }
+
+    async fn save_run(&self, run: incident_run::Model) -> Result<(), DbErr> {
+        incident_run::save_run(
+            self,
+            &run.user_id,
+            &run.incident_id,
+            run.run,
+            run.updated_at,
+        )
+        .await
+    }
+
+    async fn delete_run(&self, user_id: &str, incident_id: &str) -> Result<(), DbErr> {
+        if let Some(run) = self.find_run(user_id, incident_id).await? {
+            run.delete(self).await?;
+        }
+        Ok(())
+    }
+
+    async fn record_clear(&self, clear: incident_clear::Model) -> Result<(), DbErr> {
+        incident_clear::ActiveModel {
+            id: ActiveValue::NotSet,
+            user_id: ActiveValue::Set(clear.user_id),
+            incident_id: ActiveValue::Set(clear.incident_id),
+            chara: ActiveValue::Set(clear.chara),
+            elapsed_seconds: ActiveValue::Set(clear.elapsed_seconds),
+            defeats: ActiveValue::Set(clear.defeats),
+            cleared_at: ActiveValue::Set(clear.cleared_at),
+        }
+        .insert(self)
+        .await?;
+        Ok(())
+    }
+
+    async fn best_times(
+        &self,
+        incident_id: &str,
+        limit: usize,
+    ) -> Result<Vec<incident_clear::Model>, DbErr> {
+        incident_clear::best_times(self, incident_id, limit).await
+    }
+}
+
+#[async_trait]
+impl<C: ConnectionTrait + TransactionTrait + Send> TournamentRepository for C {
+    async fn find_tournament(
+        &self,
+        tournament_uuid: Uuid,
+    ) -> Result<Option<tournament::Model>, DbErr> {
+        tournament::Entity::find_by_id(tournament_uuid)
+            .one(self)
+            .await
+    }
+
+    async fn current_tournament(&self, guild_id: &str) -> Result<Option<tournament::Model>, DbErr> {
+        tournament::Entity::find()
+            .filter(tournament::Column::GuildId.eq(guild_id))
+            .filter(tournament::Column::Status.ne(tournament::STATUS_FINISHED))
+            .one(self)
+            .await
+    }
+
+    async fn save_tournament(&self, tournament: tournament::Model) -> Result<(), DbErr> {
+        tournament::Entity::insert(tournament::ActiveModel {
+            tournament_uuid: ActiveValue::Set(tournament.tournament_uuid),
+            guild_id: ActiveValue::Set(tournament.guild_id),
+            name: ActiveValue::Set(tournament.name),
+            format: ActiveValue::Set(tournament.format),
+            status: ActiveValue::Set(tournament.status),
+            participants: ActiveValue::Set(tournament.participants),
+            bracket: ActiveValue::Set(tournament.bracket),
+            match_timeout_minutes: ActiveValue::Set(tournament.match_timeout_minutes),
+            created_at: ActiveValue::Set(tournament.created_at),
+        })
+        .on_conflict(
+            OnConflict::column(tournament::Column::TournamentUuid)
+                .update_columns([
+                    tournament::Column::Name,
+                    tournament::Column::Status,
+                    tournament::Column::Participants,
+                    tournament::Column::Bracket,
+                    tournament::Column::MatchTimeoutMinutes,
+                ])
+                .to_owned(),
+        )
+        .exec(self)
+        .await?;
+        Ok(())
+    }
+
+    async fn update_tournament(
+        &self,
+        tournament_uuid: Uuid,
+        update: TournamentUpdate,
+    ) -> Result<Option<tournament::Model>, DbErr> {
+        let txn = self.begin().await?;
+        let current = tournament::Entity::find_by_id(tournament_uuid)
+            .lock_exclusive()
+            .one(&txn)
+            .await?;
+        let updated = match current.as_ref().and_then(update) {
+            Some(updated) => updated,
+            None => return Ok(None),
+        };
+        txn.save_tournament(updated.clone()).await?;
+        txn.commit().await?;
+        Ok(Some(updated))
+    }
+}
+
+#[async_trait]
+impl<C: ConnectionTrait + TransactionTrait + Send + Sync> TransactionRepository for C {
+    type Transaction = DatabaseTransaction;
+
+    async fn begin_transaction(&self) -> Result<DatabaseTransaction, DbErr> {
+        self.begin().await
+    }
+}
+
+#[async_trait]
+impl RepositoryTransaction for DatabaseTransaction {
+    async fn commit(self) -> Result<(), DbErr> {
+        DatabaseTransaction::commit(self).await
+    }
+
+    async fn rollback(self) -> Result<(), DbErr> {
+        DatabaseTransaction::rollback(self).await
+    }
+}
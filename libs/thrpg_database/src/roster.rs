@@ -0,0 +1,98 @@
+use chrono::{Local, NaiveDateTime};
+use sea_orm::{entity::prelude::*, sea_query::Expr, ActiveValue, ConnectionTrait, DeriveEntityModel};
+
+/// A character owned by a user
+/// Each character has its own level and exp
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "roster")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub user_id: String,
+    /// `chara/{chara}.toml`
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub chara: String,
+    pub level: i64,
+    pub exp: i64,
+    /// Bond between the user and the character
+    pub affinity: i64,
+    pub unlocked_at: NaiveDateTime,
+}
+
+#[derive(Clone, Copy, Debug, EnumIter)]
+pub enum Relation {
+    UserData,
+}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        match self {
+            &Self::UserData => Entity::belongs_to(crate::userdata::Entity)
+                .from(Column::UserId)
+                .to(crate::userdata::Column::UserId)
+                .into(),
+        }
+    }
+}
+
+impl Related<crate::userdata::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserData.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// Get the character of the user, adding it to the roster at level 1 if the user does not own it
+pub async fn find_or_unlock<C: ConnectionTrait>(
+    db: &C,
+    user_id: &str,
+    chara: &str,
+) -> Result<Model, DbErr> {
+    match Entity::find_by_id((user_id.to_string(), chara.to_string()))
+        .one(db)
+        .await?
+    {
+        Some(model) => Ok(model),
+        None => {
+            ActiveModel {
+                user_id: ActiveValue::Set(user_id.to_string()),
+                chara: ActiveValue::Set(chara.to_string()),
+                level: ActiveValue::Set(1),
+                exp: ActiveValue::Set(1),
+                affinity: ActiveValue::Set(0),
+                unlocked_at: ActiveValue::Set(Local::now().naive_local()),
+            }
+            .insert(db)
+            .await
+        }
+    }
+}
+
+/// Add the character to the roster of the user
+/// Returns `false` if the user already owns it
+pub async fn unlock<C: ConnectionTrait>(db: &C, user_id: &str, chara: &str) -> Result<bool, DbErr> {
+    if Entity::find_by_id((user_id.to_string(), chara.to_string()))
+        .one(db)
+        .await?
+        .is_some()
+    {
+        return Ok(false);
+    }
+    find_or_unlock(db, user_id, chara).await?;
+    Ok(true)
+}
+
+pub async fn add_affinity<C: ConnectionTrait>(
+    db: &C,
+    user_id: &str,
+    chara: &str,
+    amount: i64,
+) -> Result<(), DbErr> {
+    Entity::update_many()
+        .col_expr(Column::Affinity, Expr::col(Column::Affinity).add(amount))
+        .filter(Column::UserId.eq(user_id))
+        .filter(Column::Chara.eq(chara))
+        .exec(db)
+        .await?;
+    Ok(())
+}
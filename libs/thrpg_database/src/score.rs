@@ -1,30 +1,380 @@
-use crate::redis_connect::AsyncConnection;
-use redis::AsyncCommands;
+//! Leaderboards of scores, in Redis sorted sets or in the `score` table when Redis is not configured
+//! Equal scores are ordered by member, descending, like `ZREVRANGE`
+use async_trait::async_trait;
+use chrono::{Datelike, Duration, Local, NaiveDateTime, NaiveTime, TimeZone};
+use redis::{aio::MultiplexedConnection, AsyncCommands};
+use sea_orm::{
+    entity::prelude::*, sea_query::{Expr, OnConflict}, ActiveValue, Condition, DatabaseConnection,
+    DeriveEntityModel, QueryOrder, QuerySelect,
+};
+use std::collections::HashMap;
+use std::sync::Mutex;
 
-#[derive(Debug,Clone)]
+/// Redis key of a board
+pub fn score_key(board: &str) -> String {
+    format!("thrpg:score:{}", board)
+}
+
+/// Best score of a member on a board
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "score")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub board: String,
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub member: String,
+    pub score: i64,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Clone, Copy, Debug, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        panic!("No RelationDef")
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+#[derive(Debug, Clone)]
 pub struct ScoreData {
     key: String,
     member: String,
     score: u64,
+    /// Redis drops the board after this, the database keeps it
+    expires_at: Option<NaiveDateTime>,
+}
+
+impl ScoreData {
+    /// A score on the board `key` that is kept forever
+    pub fn new(key: impl Into<String>, member: impl Into<String>, score: u64) -> Self {
+        Self {
+            key: key.into(),
+            member: member.into(),
+            score,
+            expires_at: None,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn member(&self) -> &str {
+        &self.member
+    }
+
+    pub fn score(&self) -> u64 {
+        self.score
+    }
+}
+
+/// Period of a time-windowed board
+/// Each period is a board of its own, named by [Window::board]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Window {
+    AllTime,
+    Daily,
+    /// Weeks start on Monday
+    Weekly,
+}
+
+impl Window {
+    /// Board of `key` for the period that contains `now`
+    pub fn board(&self, key: &str, now: NaiveDateTime) -> String {
+        match self {
+            Window::AllTime => key.to_string(),
+            Window::Daily => format!("{}:daily:{}", key, now.format("%Y-%m-%d")),
+            Window::Weekly => format!("{}:weekly:{}", key, now.format("%G-W%V")),
+        }
+    }
+
+    /// `None` for [Window::AllTime]
+    pub fn length(&self) -> Option<Duration> {
+        match self {
+            Window::AllTime => None,
+            Window::Daily => Some(Duration::days(1)),
+            Window::Weekly => Some(Duration::weeks(1)),
+        }
+    }
+
+    /// When the period that contains `now` ends, `None` for [Window::AllTime]
+    pub fn ends_at(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        let midnight = now.date().and_time(NaiveTime::from_hms_opt(0, 0, 0).unwrap());
+        match self {
+            Window::AllTime => None,
+            Window::Daily => Some(midnight + Duration::days(1)),
+            Window::Weekly => {
+                Some(midnight + Duration::days(7 - now.weekday().num_days_from_monday() as i64))
+            }
+        }
+    }
+
+    /// A score on the board of the period that contains `now`
+    /// The previous period can still be read for one more period
+    pub fn score(&self, key: &str, member: impl Into<String>, score: u64, now: NaiveDateTime) -> ScoreData {
+        ScoreData {
+            key: self.board(key, now),
+            member: member.into(),
+            score,
+            expires_at: self.ends_at(now).zip(self.length()).map(|(ends_at, length)| ends_at + length),
+        }
+    }
+}
+
+#[async_trait]
+pub trait Leaderboard: Send + Sync {
+    /// Set the score of the member, even if it is lower than before
+    async fn add(&self, data: &ScoreData) -> anyhow::Result<()>;
+    /// Set the score only if the member has none or a lower one
+    /// Returns `true` if the score was set
+    async fn update_if_better(&self, data: &ScoreData) -> anyhow::Result<bool>;
+    /// Members with the highest score, best first
+    async fn top(&self, key: &str, count: usize) -> anyhow::Result<Vec<(String, u64)>>;
+    /// Position of the member, 1 is the best, `None` if the member has no score
+    async fn rank(&self, key: &str, member: &str) -> anyhow::Result<Option<u64>>;
+}
+
+/// Compares and sets in one step, `ZADD GT` needs Redis 6.2
+const UPDATE_IF_BETTER: &str = r"
+local current = redis.call('ZSCORE', KEYS[1], ARGV[2])
+if current and tonumber(current) >= tonumber(ARGV[1]) then
+    return 0
+end
+redis.call('ZADD', KEYS[1], ARGV[1], ARGV[2])
+if ARGV[3] ~= '' then
+    redis.call('EXPIREAT', KEYS[1], ARGV[3])
+end
+return 1
+";
+
+/// Boards in Redis sorted sets
+#[derive(Clone)]
+pub struct RedisLeaderboard {
+    connection: MultiplexedConnection,
+}
+
+impl RedisLeaderboard {
+    pub async fn connect(url: impl Into<String>) -> redis::RedisResult<Self> {
+        let connection = crate::redis_connect::connect_multiplexed(url).await?;
+        Ok(Self { connection })
+    }
+}
+
+/// `EXPIREAT` takes a unix time, the times of the bot are local
+fn unix_time(time: NaiveDateTime) -> i64 {
+    Local
+        .from_local_datetime(&time)
+        .earliest()
+        .map_or_else(|| time.and_utc().timestamp(), |time| time.timestamp())
 }
 
-pub async fn score_add(connect: &mut AsyncConnection, data: ScoreData) -> anyhow::Result<()> {
-    let _: () = connect
-        .zadd(data.key, data.member, data.score)
+#[async_trait]
+impl Leaderboard for RedisLeaderboard {
+    async fn add(&self, data: &ScoreData) -> anyhow::Result<()> {
+        let mut connection = self.connection.clone();
+        let key = score_key(&data.key);
+        let mut pipe = redis::pipe();
+        pipe.atomic().zadd(&key, &data.member, data.score).ignore();
+        if let Some(expires_at) = data.expires_at {
+            pipe.expire_at(&key, unix_time(expires_at) as usize).ignore();
+        }
+        let _: () = pipe.query_async(&mut connection).await?;
+        Ok(())
+    }
+
+    async fn update_if_better(&self, data: &ScoreData) -> anyhow::Result<bool> {
+        let mut connection = self.connection.clone();
+        let updated: i64 = redis::Script::new(UPDATE_IF_BETTER)
+            .key(score_key(&data.key))
+            .arg(data.score)
+            .arg(&data.member)
+            .arg(data.expires_at.map_or(String::new(), |time| unix_time(time).to_string()))
+            .invoke_async(&mut connection)
+            .await?;
+        Ok(updated == 1)
+    }
+
+    async fn top(&self, key: &str, count: usize) -> anyhow::Result<Vec<(String, u64)>> {
+        // ZREVRANGE 0 -1 would be the whole board
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+        let mut connection = self.connection.clone();
+        let top: Vec<(String, u64)> = connection
+            .zrevrange_withscores(score_key(key), 0, count as isize - 1)
+            .await?;
+        Ok(top)
+    }
+
+    async fn rank(&self, key: &str, member: &str) -> anyhow::Result<Option<u64>> {
+        let mut connection = self.connection.clone();
+        let rank: Option<u64> = connection.zrevrank(score_key(key), member).await?;
+        Ok(rank.map(|rank| rank + 1))
+    }
+}
+
+/// Boards in the `score` table, on Postgres or SQLite
+#[derive(Clone)]
+pub struct DatabaseLeaderboard {
+    connection: DatabaseConnection,
+}
+
+impl DatabaseLeaderboard {
+    pub fn new(connection: DatabaseConnection) -> Self {
+        Self { connection }
+    }
+
+    async fn upsert(&self, data: &ScoreData, on_conflict: OnConflict) -> Result<(), DbErr> {
+        Entity::insert(ActiveModel {
+            board: ActiveValue::Set(data.key.clone()),
+            member: ActiveValue::Set(data.member.clone()),
+            score: ActiveValue::Set(data.score as i64),
+            updated_at: ActiveValue::Set(Local::now().naive_local()),
+        })
+        .on_conflict(on_conflict)
+        .exec(&self.connection)
         .await?;
-    Ok(())
+        Ok(())
+    }
 }
 
-pub async fn score_update(connect: &mut AsyncConnection, data: ScoreData) -> anyhow::Result<()> {
-    let (_, stop): (u64, u64) = connect
-        .zrevrange_withscores(&data.key, 0, 10)
+#[async_trait]
+impl Leaderboard for DatabaseLeaderboard {
+    async fn add(&self, data: &ScoreData) -> anyhow::Result<()> {
+        self.upsert(
+            data,
+            OnConflict::columns([Column::Board, Column::Member])
+                .update_columns([Column::Score, Column::UpdatedAt])
+                .to_owned(),
+        )
         .await?;
+        Ok(())
+    }
 
-    if data.score >= stop {
-        connect
-            .zadd(data.key, data.member, data.score)
+    async fn update_if_better(&self, data: &ScoreData) -> anyhow::Result<bool> {
+        let current = Entity::find_by_id((data.key.clone(), data.member.clone()))
+            .one(&self.connection)
             .await?;
+        if current.is_some_and(|current| current.score >= data.score as i64) {
+            return Ok(false);
+        }
+        // Another process may have stored a better score since it was read
+        let better = |column: &str| {
+            format!(
+                "CASE WHEN excluded.score > \"score\".score THEN excluded.{0} ELSE \"score\".{0} END",
+                column
+            )
+        };
+        self.upsert(
+            data,
+            OnConflict::columns([Column::Board, Column::Member])
+                .update_exprs([
+                    (Column::Score, Expr::cust(&better("score"))),
+                    (Column::UpdatedAt, Expr::cust(&better("updated_at"))),
+                ])
+                .to_owned(),
+        )
+        .await?;
+        Ok(true)
     }
 
-    Ok(())
+    async fn top(&self, key: &str, count: usize) -> anyhow::Result<Vec<(String, u64)>> {
+        let top = Entity::find()
+            .filter(Column::Board.eq(key))
+            .order_by_desc(Column::Score)
+            .order_by_desc(Column::Member)
+            .limit(count as u64)
+            .all(&self.connection)
+            .await?;
+        Ok(top
+            .into_iter()
+            .map(|score| (score.member, score.score as u64))
+            .collect())
+    }
+
+    async fn rank(&self, key: &str, member: &str) -> anyhow::Result<Option<u64>> {
+        let current = match Entity::find_by_id((key.to_string(), member.to_string()))
+            .one(&self.connection)
+            .await?
+        {
+            Some(current) => current,
+            None => return Ok(None),
+        };
+        let ahead = Entity::find()
+            .filter(Column::Board.eq(key))
+            .filter(
+                Condition::any().add(Column::Score.gt(current.score)).add(
+                    Condition::all()
+                        .add(Column::Score.eq(current.score))
+                        .add(Column::Member.gt(member)),
+                ),
+            )
+            .count(&self.connection)
+            .await?;
+        Ok(Some(ahead as u64 + 1))
+    }
+}
+
+/// Boards of this process only
+#[derive(Debug, Default)]
+pub struct MemoryLeaderboard {
+    boards: Mutex<HashMap<String, HashMap<String, u64>>>,
+}
+
+impl MemoryLeaderboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn sorted(&self, key: &str) -> Vec<(String, u64)> {
+        let mut board: Vec<(String, u64)> = self
+            .boards
+            .lock()
+            .unwrap()
+            .get(key)
+            .map(|board| board.iter().map(|(member, score)| (member.clone(), *score)).collect())
+            .unwrap_or_default();
+        board.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| b.0.cmp(&a.0)));
+        board
+    }
+}
+
+#[async_trait]
+impl Leaderboard for MemoryLeaderboard {
+    async fn add(&self, data: &ScoreData) -> anyhow::Result<()> {
+        self.boards
+            .lock()
+            .unwrap()
+            .entry(data.key.clone())
+            .or_default()
+            .insert(data.member.clone(), data.score);
+        Ok(())
+    }
+
+    async fn update_if_better(&self, data: &ScoreData) -> anyhow::Result<bool> {
+        let mut boards = self.boards.lock().unwrap();
+        let board = boards.entry(data.key.clone()).or_default();
+        match board.get(&data.member) {
+            Some(current) if *current >= data.score => Ok(false),
+            _ => {
+                board.insert(data.member.clone(), data.score);
+                Ok(true)
+            }
+        }
+    }
+
+    async fn top(&self, key: &str, count: usize) -> anyhow::Result<Vec<(String, u64)>> {
+        Ok(self.sorted(key).into_iter().take(count).collect())
+    }
+
+    async fn rank(&self, key: &str, member: &str) -> anyhow::Result<Option<u64>> {
+        Ok(self
+            .sorted(key)
+            .iter()
+            .position(|(m, _)| m == member)
+            .map(|rank| rank as u64 + 1))
+    }
 }
@@ -0,0 +1,98 @@
+//! Battles in progress, kept outside of the process so another shard or a restarted bot can pick them up
+//! Only saving or finishing a battle writes it to the database
+use async_trait::async_trait;
+use redis::{aio::MultiplexedConnection, AsyncCommands};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Redis key of the live battle of a user
+pub fn session_key(user_id: &str) -> String {
+    format!("thrpg:session:{}", user_id)
+}
+
+/// Live battles by user, stored as JSON
+#[async_trait]
+pub trait SessionCache: Send + Sync {
+    /// The battle as it was last stored, `None` once the TTL has passed
+    async fn load(&self, user_id: &str) -> anyhow::Result<Option<String>>;
+    /// Overwrite the battle of the user, the TTL starts again
+    async fn store(&self, user_id: &str, battle: &str, ttl: Duration) -> anyhow::Result<()>;
+    async fn remove(&self, user_id: &str) -> anyhow::Result<()>;
+}
+
+/// Sessions in Redis, shared by every process connected to the same server
+#[derive(Clone)]
+pub struct RedisSessionCache {
+    connection: MultiplexedConnection,
+}
+
+impl RedisSessionCache {
+    pub async fn connect(url: impl Into<String>) -> redis::RedisResult<Self> {
+        let connection = crate::redis_connect::connect_multiplexed(url).await?;
+        Ok(Self { connection })
+    }
+}
+
+#[async_trait]
+impl SessionCache for RedisSessionCache {
+    async fn load(&self, user_id: &str) -> anyhow::Result<Option<String>> {
+        let mut connection = self.connection.clone();
+        let battle: Option<String> = connection.get(session_key(user_id)).await?;
+        Ok(battle)
+    }
+
+    async fn store(&self, user_id: &str, battle: &str, ttl: Duration) -> anyhow::Result<()> {
+        let mut connection = self.connection.clone();
+        // SETEX rejects 0
+        let seconds = ttl.as_secs().max(1) as usize;
+        let _: () = connection.set_ex(session_key(user_id), battle, seconds).await?;
+        Ok(())
+    }
+
+    async fn remove(&self, user_id: &str) -> anyhow::Result<()> {
+        let mut connection = self.connection.clone();
+        let _: () = connection.del(session_key(user_id)).await?;
+        Ok(())
+    }
+}
+
+/// Sessions of this process only, used when Redis is not configured
+#[derive(Debug, Default)]
+pub struct MemorySessionCache {
+    sessions: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+impl MemorySessionCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl SessionCache for MemorySessionCache {
+    async fn load(&self, user_id: &str) -> anyhow::Result<Option<String>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        match sessions.get(user_id) {
+            Some((battle, expires)) if *expires > Instant::now() => Ok(Some(battle.clone())),
+            Some(_) => {
+                sessions.remove(user_id);
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn store(&self, user_id: &str, battle: &str, ttl: Duration) -> anyhow::Result<()> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .insert(user_id.to_string(), (battle.to_string(), Instant::now() + ttl));
+        Ok(())
+    }
+
+    async fn remove(&self, user_id: &str) -> anyhow::Result<()> {
+        self.sessions.lock().unwrap().remove(user_id);
+        Ok(())
+    }
+}
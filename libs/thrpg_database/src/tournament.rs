@@ -0,0 +1,37 @@
+use chrono::NaiveDateTime;
+use sea_orm::{entity::prelude::*, DeriveEntityModel};
+use uuid::Uuid;
+
+pub const STATUS_REGISTRATION: &str = "Registration";
+pub const STATUS_RUNNING: &str = "Running";
+pub const STATUS_FINISHED: &str = "Finished";
+
+/// Community tournament held in a guild
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "tournament")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub tournament_uuid: Uuid,
+    pub guild_id: String,
+    pub name: String,
+    pub format: String,
+    /// [STATUS_REGISTRATION], [STATUS_RUNNING] or [STATUS_FINISHED]
+    pub status: String,
+    /// Discord user ids of the registered participants
+    pub participants: serde_json::Value,
+    /// Bracket state, made when the tournament starts
+    pub bracket: Option<serde_json::Value>,
+    pub match_timeout_minutes: i64,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Clone, Copy, Debug, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        panic!("No RelationDef")
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
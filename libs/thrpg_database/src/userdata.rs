@@ -1,4 +1,5 @@
-use sea_orm::{entity::prelude::*, DeriveEntityModel};
+use chrono::NaiveDateTime;
+use sea_orm::{entity::prelude::*, sea_query::Expr, ConnectionTrait, DeriveEntityModel};
 use uuid::Uuid;
 //
 // Make SeaORM entity
@@ -6,31 +7,77 @@ use uuid::Uuid;
 #[derive(Debug, Clone, PartialEq, DeriveEntityModel)]
 #[sea_orm(table_name = "userdata")]
 pub struct Model {
-    #[sea_orm(primary_key)]
+    #[sea_orm(primary_key, auto_increment = false)]
     pub user_id: String,
+    /// Active character, a pointer into [roster](crate::roster)
     pub player: String,
-    pub level: i64,
-    pub exp: i64,
+    /// Owned character called as an assist, a pointer into [roster](crate::roster)
+    pub assist: Option<String>,
     pub battle_uuid: Option<Uuid>,
+    pub gold: i64,
+    /// Points at `stamina_updated_at`, regeneration is computed when it is read
+    pub stamina: i64,
+    pub stamina_updated_at: NaiveDateTime,
+    /// Set by `/delete`, the account is purged when the restore window has passed
+    pub deleted_at: Option<NaiveDateTime>,
 }
 
 #[derive(Clone, Copy, Debug, EnumIter)]
 pub enum Relation {
-    Fruit,
+    Roster,
 }
 
 impl RelationTrait for Relation {
     fn def(&self) -> RelationDef {
         match self {
-            &Self::Fruit => Entity::has_many(Entity).into(),
+            &Self::Roster => Entity::has_many(crate::roster::Entity).into(),
         }
     }
 }
 
-impl Related<Entity> for Entity {
+impl Related<crate::roster::Entity> for Entity {
     fn to() -> RelationDef {
-        Relation::Fruit.def()
+        Relation::Roster.def()
     }
 }
 
 impl ActiveModelBehavior for ActiveModel {}
+
+/// Take gold from the user only if the balance is enough
+/// The check and the update are one statement, so the same gold is never spent twice
+pub async fn spend_gold<C: ConnectionTrait>(db: &C, user_id: &str, amount: i64) -> Result<bool, DbErr> {
+    let result = Entity::update_many()
+        .col_expr(Column::Gold, Expr::col(Column::Gold).sub(amount))
+        .filter(Column::UserId.eq(user_id))
+        .filter(Column::Gold.gte(amount))
+        .exec(db)
+        .await?;
+    Ok(result.rows_affected == 1)
+}
+
+pub async fn add_gold<C: ConnectionTrait>(db: &C, user_id: &str, amount: i64) -> Result<(), DbErr> {
+    Entity::update_many()
+        .col_expr(Column::Gold, Expr::col(Column::Gold).add(amount))
+        .filter(Column::UserId.eq(user_id))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+/// Write the stamina only if nobody changed it since `before` was read
+pub async fn update_stamina<C: ConnectionTrait>(
+    db: &C,
+    user_id: &str,
+    before: (i64, NaiveDateTime),
+    after: (i64, NaiveDateTime),
+) -> Result<bool, DbErr> {
+    let result = Entity::update_many()
+        .col_expr(Column::Stamina, Expr::value(after.0))
+        .col_expr(Column::StaminaUpdatedAt, Expr::value(after.1))
+        .filter(Column::UserId.eq(user_id))
+        .filter(Column::Stamina.eq(before.0))
+        .filter(Column::StaminaUpdatedAt.eq(before.1))
+        .exec(db)
+        .await?;
+    Ok(result.rows_affected == 1)
+}
@@ -0,0 +1,132 @@
+//! Runs on an in-memory SQLite database, or on the empty database in `THRPG_TEST_DATABASE_URL`
+use chrono::{Duration, Local};
+use thrpg_database::account::{delete_user_data, export_user, mark_deleted, purge_deleted, restore_user};
+use thrpg_database::database_connect::connect;
+use thrpg_database::migration::{Migrator, MigratorTrait};
+use thrpg_database::repository::{BattleRepository, UserRepository};
+use thrpg_database::{battle_history, duel, inventory, playdata, roster, userdata};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn export_and_delete_every_row() {
+    let url = std::env::var("THRPG_TEST_DATABASE_URL").unwrap_or_else(|_| "sqlite::memory:".to_string());
+    let db = connect(url).await.unwrap();
+    Migrator::fresh(&db).await.unwrap();
+
+    let now = Local::now().naive_local();
+    let battle_uuid = Uuid::new_v4();
+    for user_id in ["user", "other"] {
+        db.insert_user(userdata::Model {
+            user_id: user_id.to_string(),
+            player: "reimu".to_string(),
+            assist: None,
+            battle_uuid: None,
+            gold: 0,
+            stamina: 10,
+            stamina_updated_at: now,
+            deleted_at: None,
+        })
+        .await
+        .unwrap();
+        roster::find_or_unlock(&db, user_id, "reimu").await.unwrap();
+    }
+    db.save_battle(playdata::Model {
+        battle_uuid,
+        player: serde_json::json!({}),
+        enemy: serde_json::json!({}),
+        elapesd_turns: 0,
+        start_time: now,
+        play_mode: "Simple".to_string(),
+        enemy_level: 1,
+    })
+    .await
+    .unwrap();
+    db.set_battle("user", Some(battle_uuid)).await.unwrap();
+    inventory::add_item(&db, "user", "potion", "item", 2).await.unwrap();
+    db.record_duel(duel::Model {
+        battle_uuid,
+        winner_id: "other".to_string(),
+        loser_id: "user".to_string(),
+        winner_chara: "reimu".to_string(),
+        loser_chara: "reimu".to_string(),
+        elapsed_turns: 3,
+        forfeit: false,
+        finished_at: now,
+    })
+    .await
+    .unwrap();
+    let entry = |user_id: &str, opponent_id: &str| battle_history::Model {
+        id: 0,
+        battle_uuid,
+        user_id: user_id.to_string(),
+        mode: "Duel".to_string(),
+        chara: "reimu".to_string(),
+        chara_level: 1,
+        opponent_id: Some(opponent_id.to_string()),
+        opponent_name: "霊夢".to_string(),
+        opponent_level: 1,
+        won: user_id == "other",
+        turns: 3,
+        damage_dealt: 0,
+        damage_taken: 0,
+        started_at: now,
+        finished_at: now,
+    };
+    db.record_history(vec![entry("user", "other"), entry("other", "user")])
+        .await
+        .unwrap();
+
+    let archive = export_user(&db, "user").await.unwrap();
+    assert_eq!(archive["userdata"]["user_id"], "user");
+    assert_eq!(archive["playdata"].as_array().unwrap().len(), 1);
+    assert_eq!(archive["inventory"][0]["quantity"], 2);
+    assert_eq!(archive["duel"].as_array().unwrap().len(), 1);
+    assert_eq!(archive["battle_history"].as_array().unwrap().len(), 1);
+
+    assert!(delete_user_data(&db, "user").await.unwrap());
+    assert!(!delete_user_data(&db, "user").await.unwrap());
+    let archive = export_user(&db, "user").await.unwrap();
+    assert!(archive["userdata"].is_null());
+    for table in ["roster", "inventory", "duel", "battle_history"] {
+        assert!(archive[table].as_array().unwrap().is_empty(), "{}", table);
+    }
+    assert!(db.find_battle(battle_uuid).await.unwrap().is_none());
+    // The other user keeps the history without knowing who the opponent was
+    let other = db.history_of("other").await.unwrap();
+    assert_eq!(other[0].opponent_id, None);
+    assert!(db.find_user("other").await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn restore_until_purged() {
+    let db = connect("sqlite::memory:").await.unwrap();
+    Migrator::up(&db, None).await.unwrap();
+
+    let now = Local::now().naive_local();
+    for user_id in ["early", "late"] {
+        db.insert_user(userdata::Model {
+            user_id: user_id.to_string(),
+            player: "reimu".to_string(),
+            assist: None,
+            battle_uuid: None,
+            gold: 0,
+            stamina: 10,
+            stamina_updated_at: now,
+            deleted_at: None,
+        })
+        .await
+        .unwrap();
+    }
+    assert!(!mark_deleted(&db, "nobody", now).await.unwrap());
+    assert!(mark_deleted(&db, "early", now - Duration::days(31)).await.unwrap());
+    assert!(!mark_deleted(&db, "early", now).await.unwrap());
+    assert!(restore_user(&db, "early").await.unwrap());
+    assert!(!restore_user(&db, "early").await.unwrap());
+
+    mark_deleted(&db, "early", now - Duration::days(31)).await.unwrap();
+    mark_deleted(&db, "late", now).await.unwrap();
+    let purged = purge_deleted(&db, now - Duration::days(30)).await.unwrap();
+    assert_eq!(purged, ["early"]);
+    assert!(db.find_user("early").await.unwrap().is_none());
+    assert!(db.find_user("late").await.unwrap().unwrap().deleted_at.is_some());
+}
@@ -0,0 +1,37 @@
+//! Runs on an in-memory SQLite database, or on the empty database in `THRPG_TEST_DATABASE_URL`
+use chrono::Local;
+use std::time::Duration;
+use thrpg_database::database_connect::connect;
+use thrpg_database::guild_settings::{self, GuildSettingsCache};
+use thrpg_database::migration::{Migrator, MigratorTrait};
+
+#[tokio::test]
+async fn save_and_cache_settings() {
+    let url = std::env::var("THRPG_TEST_DATABASE_URL").unwrap_or_else(|_| "sqlite::memory:".to_string());
+    let db = connect(url).await.unwrap();
+    Migrator::fresh(&db).await.unwrap();
+
+    let cache = GuildSettingsCache::new(Duration::from_secs(60));
+    assert_eq!(cache.get(&db, "guild").await.unwrap(), None);
+
+    let mut settings = guild_settings::Model::empty("guild", Local::now().naive_local());
+    settings.prefix = Some("rpg!".to_string());
+    settings.set_battle_channel_ids(vec![1, 2]);
+    guild_settings::save(&db, settings.clone()).await.unwrap();
+    // Still the cached value until the guild is invalidated
+    assert_eq!(cache.get(&db, "guild").await.unwrap(), None);
+    cache.invalidate("guild");
+    let saved = cache.get(&db, "guild").await.unwrap().unwrap();
+    assert_eq!(saved.prefix.as_deref(), Some("rpg!"));
+    assert_eq!(saved.battle_channel_ids(), Some(vec![1, 2]));
+
+    settings.stamina_enabled = Some(false);
+    settings.set_battle_channel_ids(Vec::new());
+    guild_settings::save(&db, settings).await.unwrap();
+    let saved = guild_settings::find(&db, "guild").await.unwrap().unwrap();
+    assert_eq!(saved.stamina_enabled, Some(false));
+    assert_eq!(saved.battle_channel_ids(), None);
+
+    guild_settings::reset(&db, "guild").await.unwrap();
+    assert_eq!(guild_settings::find(&db, "guild").await.unwrap(), None);
+}
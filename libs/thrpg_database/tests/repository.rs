@@ -0,0 +1,373 @@
+//! [MemoryRepository] must behave like the database it stands in for
+use chrono::{Duration, Local};
+use thrpg_database::battle_history::{self, BattleStats};
+use thrpg_database::database_connect::connect;
+use thrpg_database::migration::{Migrator, MigratorTrait};
+use thrpg_database::repository::{
+    test_user, BattleRepository, InventoryRepository, MemoryRepository, RatingUpdate, Repository,
+    RepositoryTransaction, ScoreRepository, TransactionRepository, UserRepository,
+};
+use thrpg_database::{
+    achievement, equipment, incident_clear, incident_run, playdata, pull_history, quest, rating,
+    tournament, userdata,
+};
+use uuid::Uuid;
+
+async fn check<R: UserRepository + BattleRepository + ScoreRepository>(repo: &R) {
+    let now = Local::now().naive_local();
+    repo.insert_user(userdata::Model {
+        gold: 100,
+        stamina: 10,
+        stamina_updated_at: now,
+        ..test_user("user")
+    })
+    .await
+    .unwrap();
+    assert!(repo.spend_gold("user", 60).await.unwrap());
+    assert!(!repo.spend_gold("user", 60).await.unwrap());
+    repo.set_player("user", "marisa").await.unwrap();
+    assert!(repo.set_assist("nobody", None).await.is_err());
+
+    let battle_uuid = Uuid::new_v4();
+    let battle = playdata::Model {
+        battle_uuid,
+        player: serde_json::json!({ "hp": 10 }),
+        enemy: serde_json::json!({ "hp": 20 }),
+        elapesd_turns: 0,
+        start_time: now,
+        play_mode: "Simple".to_string(),
+        enemy_level: 1,
+    };
+    repo.save_battle(battle.clone()).await.unwrap();
+    repo.save_battle(playdata::Model {
+        elapesd_turns: 3,
+        enemy_level: 4,
+        ..battle
+    })
+    .await
+    .unwrap();
+    repo.set_battle("user", Some(battle_uuid)).await.unwrap();
+    let user = repo.find_user("user").await.unwrap().unwrap();
+    assert_eq!((user.gold, user.player.as_str()), (40, "marisa"));
+    let saved = repo
+        .find_battle(user.battle_uuid.unwrap())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!((saved.elapesd_turns, saved.enemy_level), (3, 4));
+
+    let unrated = unrated(&["winner", "loser"]);
+    repo.update_ratings(battle_uuid, &unrated, win())
+        .await
+        .unwrap();
+    let ratings = repo
+        .update_ratings(battle_uuid, &unrated, win())
+        .await
+        .unwrap();
+    assert_eq!((ratings[1].rating, ratings[1].matches), (1468.0, 2));
+    let top: Vec<String> = repo
+        .top_ratings(1)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.user_id)
+        .collect();
+    assert_eq!(top, ["winner"]);
+    assert_eq!(repo.find_rating("loser").await.unwrap().unwrap().matches, 2);
+    assert_eq!(
+        repo.find_rating("winner").await.unwrap().unwrap().rating,
+        1532.0
+    );
+
+    let entry = |won: bool, chara: &str, minutes: i64| battle_history::Model {
+        id: 0,
+        battle_uuid,
+        user_id: "user".to_string(),
+        mode: "Story".to_string(),
+        chara: chara.to_string(),
+        chara_level: 1,
+        opponent_id: None,
+        opponent_name: "ルーミア".to_string(),
+        opponent_level: 1,
+        won,
+        turns: 4,
+        damage_dealt: 30,
+        damage_taken: 10,
+        started_at: now + Duration::minutes(minutes) - Duration::seconds(60),
+        finished_at: now + Duration::minutes(minutes),
+    };
+    // Recorded out of order, read back oldest first
+    repo.record_history(vec![entry(true, "reimu", 3), entry(true, "marisa", 1)])
+        .await
+        .unwrap();
+    repo.record_history(vec![entry(false, "marisa", 2), entry(true, "marisa", 4)])
+        .await
+        .unwrap();
+    let stats = BattleStats::from_history(&repo.history_of("user").await.unwrap());
+    assert_eq!((stats.battles, stats.wins, stats.losses), (4, 3, 1));
+    assert_eq!((stats.longest_win_streak, stats.current_win_streak), (2, 2));
+    assert_eq!(stats.favorite_chara.as_deref(), Some("marisa"));
+    assert_eq!(stats.average_seconds, 60.0);
+    assert!(repo.history_of("nobody").await.unwrap().is_empty());
+
+    assert!(repo.delete_user("user").await.unwrap());
+    assert!(!repo.delete_user("user").await.unwrap());
+}
+
+async fn check_progress<R: Repository + TransactionRepository>(repo: &R) {
+    let now = Local::now().naive_local();
+    repo.insert_user(userdata::Model {
+        stamina: 10,
+        stamina_updated_at: now,
+        ..test_user("user")
+    })
+    .await
+    .unwrap();
+
+    assert!(repo.find_roster("user", "reimu").await.unwrap().is_none());
+    assert!(repo.unlock("user", "reimu").await.unwrap());
+    assert!(!repo.unlock("user", "reimu").await.unwrap());
+    repo.set_level("user", "reimu", 3, 40).await.unwrap();
+    repo.add_affinity("user", "reimu", 5).await.unwrap();
+    let reimu = repo.find_or_unlock("user", "reimu").await.unwrap();
+    assert_eq!((reimu.level, reimu.exp, reimu.affinity), (3, 40, 5));
+    repo.record_pull(pull_history::Model {
+        id: 0,
+        user_id: "user".to_string(),
+        pool_id: "normal".to_string(),
+        chara: "reimu".to_string(),
+        cost: 100,
+        duplicate: true,
+        pulled_at: now,
+    })
+    .await
+    .unwrap();
+
+    let gear = |id: &str| equipment::Model {
+        user_id: "user".to_string(),
+        chara: "reimu".to_string(),
+        slot: "Weapon".to_string(),
+        equipment_id: id.to_string(),
+    };
+    assert_eq!(repo.equip(gear("gohei")).await.unwrap(), None);
+    assert_eq!(
+        repo.equip(gear("hakkero")).await.unwrap().as_deref(),
+        Some("gohei")
+    );
+    assert_eq!(
+        repo.equipped("user", "reimu").await.unwrap(),
+        [gear("hakkero")]
+    );
+
+    repo.add_item("user", "potion", "Item", 2).await.unwrap();
+    assert!(repo.use_item("user", "potion", 2).await.unwrap());
+    assert!(!repo.use_item("user", "potion", 1).await.unwrap());
+    assert!(repo.owned("user", "Item").await.unwrap().is_empty());
+
+    let progress = |progress: i64, unlocked: bool| achievement::Model {
+        user_id: "user".to_string(),
+        achievement_id: "first_win".to_string(),
+        progress,
+        unlocked_at: unlocked.then_some(now),
+    };
+    repo.save_achievement(progress(0, false)).await.unwrap();
+    assert!(repo.unlocked_achievements("user").await.unwrap().is_empty());
+    repo.save_achievement(progress(1, true)).await.unwrap();
+    assert_eq!(
+        repo.find_achievement("user", "first_win").await.unwrap(),
+        Some(progress(1, true))
+    );
+    assert_eq!(repo.unlocked_achievements("user").await.unwrap().len(), 1);
+
+    let daily = |progress: i64| quest::Model {
+        user_id: "user".to_string(),
+        quest_id: "win3".to_string(),
+        period: "2022-08-01".to_string(),
+        progress,
+        completed_at: None,
+    };
+    repo.save_quest(daily(1)).await.unwrap();
+    repo.save_quest(daily(2)).await.unwrap();
+    assert_eq!(
+        repo.find_quest("user", "win3", "2022-08-01").await.unwrap(),
+        Some(daily(2))
+    );
+    assert_eq!(
+        repo.quests_in("user", vec!["2022-08-01".to_string()])
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+    assert!(repo
+        .quests_in("user", vec!["2022-08-02".to_string()])
+        .await
+        .unwrap()
+        .is_empty());
+
+    repo.save_run(incident_run::Model {
+        user_id: "user".to_string(),
+        incident_id: "scarlet".to_string(),
+        run: serde_json::json!({ "stage": 2 }),
+        updated_at: now,
+    })
+    .await
+    .unwrap();
+    let run = repo.find_run("user", "scarlet").await.unwrap().unwrap();
+    assert_eq!(run.run, serde_json::json!({ "stage": 2 }));
+    repo.delete_run("user", "scarlet").await.unwrap();
+    assert!(repo.find_run("user", "scarlet").await.unwrap().is_none());
+    for (user_id, seconds) in [("a", 90), ("b", 60), ("a", 30)] {
+        repo.record_clear(incident_clear::Model {
+            id: 0,
+            user_id: user_id.to_string(),
+            incident_id: "scarlet".to_string(),
+            chara: "reimu".to_string(),
+            elapsed_seconds: seconds,
+            defeats: 0,
+            cleared_at: now,
+        })
+        .await
+        .unwrap();
+    }
+    let best: Vec<(String, i64)> = repo
+        .best_times("scarlet", 10)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|clear| (clear.user_id, clear.elapsed_seconds))
+        .collect();
+    assert_eq!(best, [("a".to_string(), 30), ("b".to_string(), 60)]);
+
+    let cup = tournament::Model {
+        tournament_uuid: Uuid::new_v4(),
+        guild_id: "guild".to_string(),
+        name: "THRPG Cup".to_string(),
+        format: "SingleElimination".to_string(),
+        status: tournament::STATUS_REGISTRATION.to_string(),
+        participants: serde_json::json!([]),
+        bracket: None,
+        match_timeout_minutes: 60,
+        created_at: now,
+    };
+    repo.save_tournament(cup.clone()).await.unwrap();
+    assert_eq!(
+        repo.current_tournament("guild").await.unwrap(),
+        Some(cup.clone())
+    );
+    let joined = repo
+        .update_tournament(
+            cup.tournament_uuid,
+            Box::new(|t| {
+                Some(tournament::Model {
+                    participants: serde_json::json!(["user"]),
+                    ..t.clone()
+                })
+            }),
+        )
+        .await
+        .unwrap();
+    assert_eq!(joined.unwrap().participants, serde_json::json!(["user"]));
+    let unchanged = repo
+        .update_tournament(cup.tournament_uuid, Box::new(|_| None))
+        .await
+        .unwrap();
+    assert!(unchanged.is_none());
+    assert_eq!(
+        repo.find_tournament(cup.tournament_uuid).await.unwrap().unwrap().participants,
+        serde_json::json!(["user"])
+    );
+    let finished = tournament::Model {
+        status: tournament::STATUS_FINISHED.to_string(),
+        ..cup.clone()
+    };
+    repo.save_tournament(finished.clone()).await.unwrap();
+    assert!(repo.current_tournament("guild").await.unwrap().is_none());
+    assert_eq!(
+        repo.find_tournament(cup.tournament_uuid).await.unwrap(),
+        Some(finished)
+    );
+
+    let txn = repo.begin_transaction().await.unwrap();
+    assert!(txn.spend_gold("user", 0).await.unwrap());
+    txn.add_gold("user", 100).await.unwrap();
+    txn.add_item("user", "potion", "Item", 1).await.unwrap();
+    txn.rollback().await.unwrap();
+    assert_eq!(repo.find_user("user").await.unwrap().unwrap().gold, 0);
+    assert!(repo.owned("user", "Item").await.unwrap().is_empty());
+    let txn = repo.begin_transaction().await.unwrap();
+    txn.add_gold("user", 100).await.unwrap();
+    txn.commit().await.unwrap();
+    assert_eq!(repo.find_user("user").await.unwrap().unwrap().gold, 100);
+}
+
+fn unrated(user_ids: &[&str]) -> Vec<rating::Model> {
+    user_ids
+        .iter()
+        .map(|user_id| rating::Model {
+            user_id: user_id.to_string(),
+            rating: 1500.0,
+            deviation: 350.0,
+            matches: 0,
+            last_match: Local::now().naive_local(),
+        })
+        .collect()
+}
+
+/// The first user wins 16 from the second
+fn win() -> RatingUpdate {
+    Box::new(|current| {
+        current
+            .into_iter()
+            .zip([16.0, -16.0])
+            .map(|(model, change)| rating::Model {
+                rating: model.rating + change,
+                matches: model.matches + 1,
+                ..model
+            })
+            .collect()
+    })
+}
+
+#[tokio::test]
+async fn memory_repository() {
+    check(&MemoryRepository::new()).await;
+    check_progress(&MemoryRepository::new()).await;
+}
+
+#[tokio::test]
+async fn sqlite_repository() {
+    let db = connect("sqlite::memory:").await.unwrap();
+    Migrator::up(&db, None).await.unwrap();
+    check(&db).await;
+    check_progress(&db).await;
+}
+
+/// Runs on an in-memory SQLite database, or on the empty database in `THRPG_TEST_DATABASE_URL`
+#[tokio::test(flavor = "multi_thread")]
+async fn concurrent_matches_keep_every_rating_update() {
+    let url =
+        std::env::var("THRPG_TEST_DATABASE_URL").unwrap_or_else(|_| "sqlite::memory:".to_string());
+    let db = connect(url).await.unwrap();
+    Migrator::fresh(&db).await.unwrap();
+
+    let matches: Vec<_> = (0..10)
+        .map(|i| {
+            let db = db.clone();
+            // Both orders of the same two users
+            let users = if i % 2 == 0 { ["a", "b"] } else { ["b", "a"] };
+            tokio::spawn(async move {
+                db.update_ratings(Uuid::new_v4(), &unrated(&users), win())
+                    .await
+                    .unwrap();
+            })
+        })
+        .collect();
+    for task in matches {
+        task.await.unwrap();
+    }
+    let a = db.find_rating("a").await.unwrap().unwrap();
+    let b = db.find_rating("b").await.unwrap().unwrap();
+    assert_eq!((a.matches, b.matches), (10, 10));
+    assert_eq!((a.rating, b.rating), (1500.0, 1500.0));
+}
@@ -0,0 +1,137 @@
+//! Runs on an in-memory SQLite database, or on the empty database in `THRPG_TEST_DATABASE_URL`
+use thrpg_database::database_connect::connect;
+use thrpg_database::migration::{check_schema, Migrator, MigratorTrait};
+use thrpg_database::{playdata, roster, userdata};
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend, EntityTrait, Statement};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn entities_match_migrated_schema() {
+    let url = std::env::var("THRPG_TEST_DATABASE_URL").unwrap_or_else(|_| "sqlite::memory:".to_string());
+    let db = connect(url).await.unwrap();
+    Migrator::fresh(&db).await.unwrap();
+
+    let mismatches = check_schema(&db).await.unwrap();
+    assert!(mismatches.is_empty(), "{:#?}", mismatches);
+}
+
+/// Tables of the first `sqls/init.sql`, before any of the migrations
+const LEGACY_TABLES: [&str; 2] = [
+    "CREATE TABLE userdata (
+        user_id text NOT NULL PRIMARY KEY,
+        player text,
+        level bigint,
+        exp bigint,
+        battle_uuid Uuid
+    )",
+    "CREATE TABLE playdata (
+        battle_uuid Uuid NOT NULL PRIMARY KEY,
+        player Json,
+        enemy Json,
+        elapesd_turns bigint,
+        start_time timestamp,
+        start_turn bigint,
+        play_mode Text
+    )",
+];
+
+/// A PostgreSQL database made by the old `init.sql`, `None` without `THRPG_TEST_DATABASE_URL`
+async fn legacy_database(rows: &[&str]) -> Option<DatabaseConnection> {
+    let url = std::env::var("THRPG_TEST_DATABASE_URL").ok()?;
+    // The migration looks up the names of the characters in `chara/` of the repository
+    std::env::set_current_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/../..")).unwrap();
+    let db = connect(url).await.unwrap();
+    let tables = db
+        .query_all(Statement::from_string(
+            DbBackend::Postgres,
+            "SELECT table_name FROM information_schema.tables WHERE table_schema = current_schema()"
+                .to_owned(),
+        ))
+        .await
+        .unwrap();
+    for table in tables {
+        let name: String = table.try_get("", "table_name").unwrap();
+        execute(&db, &format!("DROP TABLE \"{}\" CASCADE", name)).await;
+    }
+    for sql in LEGACY_TABLES.iter().chain(rows) {
+        execute(&db, sql).await;
+    }
+    Some(db)
+}
+
+async fn execute(db: &DatabaseConnection, sql: &str) {
+    db.execute(Statement::from_string(DbBackend::Postgres, sql.to_owned()))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn legacy_database_keeps_its_data() {
+    let battle_uuid = Uuid::new_v4();
+    let insert_battle = format!(
+        "INSERT INTO playdata (battle_uuid, player, enemy, start_turn)
+            VALUES ('{}', '{{}}', '{{}}', 3)",
+        battle_uuid
+    );
+    // The old bot saved `Reimu` for new users and `charabase.name` after `setchara`
+    let db = match legacy_database(&[
+        "INSERT INTO userdata (user_id, player, level, exp) VALUES ('user', 'Reimu', 5, 300)",
+        "INSERT INTO userdata (user_id, player, level, exp) VALUES ('other', '霧雨魔理沙', 2, 50)",
+        &insert_battle,
+    ])
+    .await
+    {
+        Some(db) => db,
+        None => return,
+    };
+    Migrator::up(&db, None).await.unwrap();
+
+    let mismatches = check_schema(&db).await.unwrap();
+    assert!(mismatches.is_empty(), "{:#?}", mismatches);
+    let mut roster: Vec<_> = roster::Entity::find()
+        .all(&db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| (r.user_id, r.chara, r.level, r.exp, r.affinity))
+        .collect();
+    roster.sort();
+    assert_eq!(
+        roster,
+        [
+            ("other".to_string(), "marisa".to_string(), 2, 50, 0),
+            ("user".to_string(), "reimu".to_string(), 5, 300, 0),
+        ]
+    );
+    let user = userdata::Entity::find_by_id("user".to_string()).one(&db).await.unwrap().unwrap();
+    assert_eq!((user.player.as_str(), user.gold, user.stamina), ("reimu", 0, 100));
+    let battle = playdata::Entity::find_by_id(battle_uuid).one(&db).await.unwrap().unwrap();
+    assert_eq!(
+        (battle.elapesd_turns, battle.play_mode.as_str(), battle.enemy_level),
+        (0, "Simple", 1)
+    );
+}
+
+#[tokio::test]
+async fn legacy_rows_without_a_character_are_reported() {
+    let db = match legacy_database(&[
+        "INSERT INTO userdata (user_id, player, level, exp) VALUES ('user', 'reimu', 5, 300)",
+        "INSERT INTO userdata (user_id, level, exp) VALUES ('nobody', 1, 1)",
+        "INSERT INTO userdata (user_id, player, level, exp) VALUES ('sanae', 'Sanae', 1, 1)",
+    ])
+    .await
+    {
+        Some(db) => db,
+        None => return,
+    };
+    let error = Migrator::up(&db, None).await.unwrap_err().to_string();
+    assert!(error.contains("userdata without a character: nobody"), "{}", error);
+    assert!(error.contains("userdata with a character not in chara/: sanae (Sanae)"), "{}", error);
+
+    // Nothing is moved until the rows are fixed
+    execute(&db, "UPDATE userdata SET player = 'marisa' WHERE user_id = 'nobody'").await;
+    execute(&db, "DELETE FROM userdata WHERE user_id = 'sanae'").await;
+    Migrator::up(&db, None).await.unwrap();
+    let roster = roster::Entity::find().all(&db).await.unwrap();
+    assert_eq!(roster.len(), 2);
+}
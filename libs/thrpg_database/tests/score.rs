@@ -0,0 +1,79 @@
+//! Runs on [MemoryLeaderboard] and an in-memory SQLite database, or on the empty database in `THRPG_TEST_DATABASE_URL`
+//! and on the Redis server in `THRPG_TEST_REDIS_URL` if it is set
+use chrono::NaiveDate;
+use thrpg_database::database_connect::connect;
+use thrpg_database::migration::{Migrator, MigratorTrait};
+use thrpg_database::score::{
+    DatabaseLeaderboard, Leaderboard, MemoryLeaderboard, RedisLeaderboard, ScoreData, Window,
+};
+
+async fn check<L: Leaderboard>(board: &L, key: &str) {
+    assert!(board.top(key, 3).await.unwrap().is_empty());
+    assert_eq!(board.rank(key, "reimu").await.unwrap(), None);
+
+    board.add(&ScoreData::new(key, "reimu", 300)).await.unwrap();
+    board.add(&ScoreData::new(key, "marisa", 500)).await.unwrap();
+    board.add(&ScoreData::new(key, "sakuya", 300)).await.unwrap();
+    // Equal scores are ordered by member, descending
+    assert_eq!(
+        board.top(key, 3).await.unwrap(),
+        [("marisa".to_string(), 500), ("sakuya".to_string(), 300), ("reimu".to_string(), 300)]
+    );
+    assert_eq!(board.top(key, 1).await.unwrap().len(), 1);
+    assert!(board.top(key, 0).await.unwrap().is_empty());
+    assert_eq!(board.rank(key, "marisa").await.unwrap(), Some(1));
+    assert_eq!(board.rank(key, "reimu").await.unwrap(), Some(3));
+
+    assert!(!board.update_if_better(&ScoreData::new(key, "marisa", 400)).await.unwrap());
+    assert!(!board.update_if_better(&ScoreData::new(key, "marisa", 500)).await.unwrap());
+    assert!(board.update_if_better(&ScoreData::new(key, "reimu", 600)).await.unwrap());
+    assert!(board.update_if_better(&ScoreData::new(key, "youmu", 100)).await.unwrap());
+    assert_eq!(board.rank(key, "reimu").await.unwrap(), Some(1));
+    assert_eq!(board.rank(key, "youmu").await.unwrap(), Some(4));
+
+    // add overwrites even a better score
+    board.add(&ScoreData::new(key, "reimu", 200)).await.unwrap();
+    assert_eq!(board.rank(key, "reimu").await.unwrap(), Some(3));
+
+    // Each period is a board of its own
+    let monday = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap().and_hms_opt(12, 0, 0).unwrap();
+    let daily = Window::Daily.score(key, "reimu", 100, monday);
+    board.update_if_better(&daily).await.unwrap();
+    assert_eq!(board.top(daily.key(), 3).await.unwrap(), [("reimu".to_string(), 100)]);
+    let tuesday = Window::Daily.board(key, monday + chrono::Duration::days(1));
+    assert!(board.top(&tuesday, 3).await.unwrap().is_empty());
+}
+
+#[test]
+fn window_boards() {
+    let sunday = NaiveDate::from_ymd_opt(2026, 10, 25).unwrap().and_hms_opt(23, 59, 0).unwrap();
+    assert_eq!(Window::AllTime.board("stage", sunday), "stage");
+    assert_eq!(Window::Daily.board("stage", sunday), "stage:daily:2026-10-25");
+    assert_eq!(Window::Weekly.board("stage", sunday), "stage:weekly:2026-W43");
+    assert_eq!(
+        Window::Weekly.ends_at(sunday),
+        Some(NaiveDate::from_ymd_opt(2026, 10, 26).unwrap().and_hms_opt(0, 0, 0).unwrap())
+    );
+    assert_eq!(Window::AllTime.ends_at(sunday), None);
+}
+
+#[tokio::test]
+async fn memory_leaderboard() {
+    check(&MemoryLeaderboard::new(), "stage").await;
+}
+
+#[tokio::test]
+async fn database_leaderboard() {
+    let url = std::env::var("THRPG_TEST_DATABASE_URL").unwrap_or_else(|_| "sqlite::memory:".to_string());
+    let db = connect(url).await.unwrap();
+    Migrator::fresh(&db).await.unwrap();
+    check(&DatabaseLeaderboard::new(db), "stage").await;
+}
+
+#[tokio::test]
+async fn redis_leaderboard() {
+    if let Ok(url) = std::env::var("THRPG_TEST_REDIS_URL") {
+        let key = format!("test:{}", uuid::Uuid::new_v4());
+        check(&RedisLeaderboard::connect(url).await.unwrap(), &key).await;
+    }
+}
@@ -0,0 +1,28 @@
+//! Runs on [MemorySessionCache], and on the Redis server in `THRPG_TEST_REDIS_URL` if it is set
+use std::time::Duration;
+use thrpg_database::session::{MemorySessionCache, RedisSessionCache, SessionCache};
+
+async fn check<S: SessionCache>(sessions: &S) {
+    assert_eq!(sessions.load("user").await.unwrap(), None);
+    sessions.store("user", "{\"turn\":1}", Duration::from_secs(60)).await.unwrap();
+    sessions.store("user", "{\"turn\":2}", Duration::from_secs(60)).await.unwrap();
+    assert_eq!(sessions.load("user").await.unwrap().as_deref(), Some("{\"turn\":2}"));
+    sessions.remove("user").await.unwrap();
+    assert_eq!(sessions.load("user").await.unwrap(), None);
+
+    sessions.store("user", "{}", Duration::from_secs(1)).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert_eq!(sessions.load("user").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn memory_sessions() {
+    check(&MemorySessionCache::new()).await;
+}
+
+#[tokio::test]
+async fn redis_sessions() {
+    if let Ok(url) = std::env::var("THRPG_TEST_REDIS_URL") {
+        check(&RedisSessionCache::connect(url).await.unwrap()).await;
+    }
+}
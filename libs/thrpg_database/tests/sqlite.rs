@@ -0,0 +1,60 @@
+//! The helpers must behave the same on SQLite, contributors run the bot on it without Docker
+use chrono::Local;
+use sea_orm::{ActiveModelTrait, ActiveValue, EntityTrait};
+use uuid::Uuid;
+use thrpg_database::database_connect::connect;
+use thrpg_database::migration::{Migrator, MigratorTrait};
+use thrpg_database::{inventory, playdata, roster, userdata};
+
+#[tokio::test]
+async fn helpers_work_on_sqlite() {
+    let db = connect("sqlite::memory:").await.unwrap();
+    Migrator::up(&db, None).await.unwrap();
+
+    userdata::ActiveModel {
+        user_id: ActiveValue::Set("user".to_string()),
+        player: ActiveValue::Set("reimu".to_string()),
+        assist: ActiveValue::Set(None),
+        battle_uuid: ActiveValue::Set(None),
+        gold: ActiveValue::Set(100),
+        stamina: ActiveValue::Set(10),
+        stamina_updated_at: ActiveValue::Set(Local::now().naive_local()),
+        deleted_at: ActiveValue::Set(None),
+    }
+    .insert(&db)
+    .await
+    .unwrap();
+    assert!(userdata::spend_gold(&db, "user", 60).await.unwrap());
+    assert!(!userdata::spend_gold(&db, "user", 60).await.unwrap());
+    userdata::add_gold(&db, "user", 20).await.unwrap();
+    assert!(userdata::spend_gold(&db, "user", 60).await.unwrap());
+
+    let reimu = roster::find_or_unlock(&db, "user", "reimu").await.unwrap();
+    assert_eq!(reimu.level, 1);
+    assert!(!roster::unlock(&db, "user", "reimu").await.unwrap());
+    roster::add_affinity(&db, "user", "reimu", 5).await.unwrap();
+    let reimu = roster::find_or_unlock(&db, "user", "reimu").await.unwrap();
+    assert_eq!(reimu.affinity, 5);
+
+    inventory::add_item(&db, "user", "potion", "item", 2).await.unwrap();
+    inventory::add_item(&db, "user", "potion", "item", 1).await.unwrap();
+    assert!(inventory::use_item(&db, "user", "potion", 3).await.unwrap());
+    assert!(!inventory::use_item(&db, "user", "potion", 1).await.unwrap());
+    assert!(inventory::owned(&db, "user", "item").await.unwrap().is_empty());
+
+    let battle_uuid = Uuid::new_v4();
+    playdata::ActiveModel {
+        battle_uuid: ActiveValue::Set(battle_uuid),
+        player: ActiveValue::Set(serde_json::json!({ "hp": 10 })),
+        enemy: ActiveValue::Set(serde_json::json!({ "hp": 20 })),
+        elapesd_turns: ActiveValue::Set(0),
+        start_time: ActiveValue::Set(Local::now().naive_local()),
+        play_mode: ActiveValue::Set("Normal".to_string()),
+        enemy_level: ActiveValue::Set(1),
+    }
+    .insert(&db)
+    .await
+    .unwrap();
+    let battle = playdata::Entity::find_by_id(battle_uuid).one(&db).await.unwrap().unwrap();
+    assert_eq!(battle.enemy["hp"], 20);
+}
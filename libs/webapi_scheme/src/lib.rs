@@ -1,9 +1,14 @@
 mod model;
 
+use axum::extract::{Extension, Path};
+use axum::http::StatusCode;
 use axum::response;
 use extension::extension_config::{ExtensionConfig, Extensiontype};
 use setting_config::{config_parse_toml};
 use crate::model::BOTInfo;
+use sea_orm::DatabaseConnection;
+use thrpg_database::battle_history::BattleStats;
+use thrpg_database::repository::BattleRepository;
 
 /// GET : {url}/info
 pub async fn bot_info() -> response::Json<BOTInfo> {
@@ -25,6 +30,19 @@ pub async fn user(id: u64) -> response::Json<model::UserInfo> {
     todo!()
 }
 
+/// GET {url}/stats/:user_id
+/// A user without any battle gets zeros
+pub async fn user_stats(
+    Path(user_id): Path<String>,
+    Extension(db): Extension<DatabaseConnection>,
+) -> Result<response::Json<model::UserStats>, StatusCode> {
+    let history = db
+        .history_of(&user_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(response::Json(BattleStats::from_history(&history)))
+}
+
 /// GET {url}/extensions
 pub async fn extensions(
     extension_type: Option<Extensiontype>,
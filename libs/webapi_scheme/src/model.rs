@@ -13,8 +13,22 @@ pub struct UserInfo {
 
 pub type BOTExtensions = Vec<ExtensionConfig>;
 
+pub type UserStats = thrpg_database::battle_history::BattleStats;
+
 #[derive(Deserialize, Serialize)]
-pub struct Ranking {}
+pub struct Ranking {
+    pub ranking_type: RankingType,
+    pub entries: Vec<RankingEntry>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct RankingEntry {
+    pub rank: u64,
+    pub user_id: String,
+    pub score: f64,
+    /// Only used by [RankingType::Rating](RankingType::Rating)
+    pub deviation: Option<f64>,
+}
 
 #[derive(Deserialize, Serialize)]
 pub struct Rankings {}
@@ -23,6 +37,8 @@ pub struct Rankings {}
 pub enum RankingType {
     Social,
     Local,
+    /// PvP rating
+    Rating,
 }
 
 #[derive(Deserialize, Serialize)]
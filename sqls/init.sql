@@ -1,24 +1,7 @@
 /*
 PostgreSQL's SQL file
+Tables are created by the migrations of thrpg_database when the bot starts
+Run `bot migrate status` to see which ones are applied
+Databases made by an older version of this file are upgraded by `bot migrate up`
 */
 CREATE DATABASE thrpg;
-
-\connect thrpg
-
-CREATE TABLE userdata (
-	user_id 	text 	NOT NULL PRIMARY KEY,
-	player 		text,
-	level 		bigint,
-	exp 		bigint,
-	battle_uuid Uuid
-);
-
-CREATE TABLE playdata (
-	battle_uuid 	Uuid 	NOT NULL PRIMARY KEY,
-    player 		Json,
-    enemy 		Json,
-    elapesd_turns 	bigint,
-    start_time 	timestamp,
-    start_turn 	bigint,
-	play_mode Text
-);